@@ -0,0 +1,5 @@
+DROP INDEX IF EXISTS idx_collector_site_id;
+
+ALTER TABLE collector DROP COLUMN site_id;
+
+DROP TABLE IF EXISTS site;
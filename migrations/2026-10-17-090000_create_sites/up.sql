@@ -0,0 +1,12 @@
+CREATE TABLE IF NOT EXISTS site (
+    id TEXT PRIMARY KEY NOT NULL,
+    domain TEXT NOT NULL UNIQUE,
+    name TEXT NOT NULL,
+    timezone TEXT NOT NULL DEFAULT 'UTC',
+    settings TEXT NOT NULL DEFAULT '{}',
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP
+);
+
+ALTER TABLE collector ADD COLUMN site_id TEXT REFERENCES site (id);
+
+CREATE INDEX IF NOT EXISTS idx_collector_site_id ON collector (site_id);
@@ -0,0 +1,11 @@
+CREATE TABLE IF NOT EXISTS geo_cache (
+    network TEXT PRIMARY KEY NOT NULL,
+    city TEXT NOT NULL,
+    country TEXT NOT NULL,
+    latitude REAL,
+    longitude REAL,
+    timezone TEXT,
+    provider TEXT NOT NULL,
+    expires_at TIMESTAMP NOT NULL,
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP
+);
@@ -0,0 +1,4 @@
+ALTER TABLE collector DROP COLUMN engine;
+ALTER TABLE collector DROP COLUMN device_type;
+ALTER TABLE collector DROP COLUMN browser_version;
+ALTER TABLE collector DROP COLUMN os_version;
@@ -0,0 +1,4 @@
+ALTER TABLE collector ADD COLUMN os_version TEXT;
+ALTER TABLE collector ADD COLUMN browser_version TEXT;
+ALTER TABLE collector ADD COLUMN device_type TEXT;
+ALTER TABLE collector ADD COLUMN engine TEXT;
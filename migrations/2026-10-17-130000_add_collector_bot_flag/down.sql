@@ -0,0 +1,4 @@
+DROP INDEX IF EXISTS idx_collector_is_bot;
+
+ALTER TABLE collector DROP COLUMN bot_reason;
+ALTER TABLE collector DROP COLUMN is_bot;
@@ -0,0 +1,4 @@
+ALTER TABLE collector ADD COLUMN is_bot BOOLEAN NOT NULL DEFAULT 0;
+ALTER TABLE collector ADD COLUMN bot_reason TEXT;
+
+CREATE INDEX IF NOT EXISTS idx_collector_is_bot ON collector (is_bot);
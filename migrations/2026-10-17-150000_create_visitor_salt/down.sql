@@ -0,0 +1,5 @@
+DROP INDEX IF EXISTS idx_collector_visitor_id;
+
+ALTER TABLE collector DROP COLUMN visitor_id;
+
+DROP TABLE IF EXISTS visitor_salt;
@@ -0,0 +1,9 @@
+CREATE TABLE IF NOT EXISTS visitor_salt (
+    day DATE PRIMARY KEY NOT NULL,
+    salt TEXT NOT NULL,
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP
+);
+
+ALTER TABLE collector ADD COLUMN visitor_id TEXT;
+
+CREATE INDEX IF NOT EXISTS idx_collector_visitor_id ON collector (visitor_id);
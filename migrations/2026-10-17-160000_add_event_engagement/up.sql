@@ -0,0 +1 @@
+ALTER TABLE event ADD COLUMN engagement_ms INTEGER;
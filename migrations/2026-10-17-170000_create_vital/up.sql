@@ -0,0 +1,12 @@
+CREATE TABLE IF NOT EXISTS vital (
+    id TEXT PRIMARY KEY NOT NULL,
+    collector_id TEXT NOT NULL,
+    url TEXT NOT NULL,
+    name TEXT NOT NULL,
+    value REAL NOT NULL,
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+    FOREIGN KEY (collector_id) REFERENCES collector (id)
+);
+
+CREATE INDEX IF NOT EXISTS idx_vital_created_at ON vital (created_at);
+CREATE INDEX IF NOT EXISTS idx_vital_collector_id ON vital (collector_id);
@@ -0,0 +1,2 @@
+DROP TABLE IF EXISTS error_occurrence;
+DROP TABLE IF EXISTS error_group;
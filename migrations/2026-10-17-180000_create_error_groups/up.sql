@@ -0,0 +1,30 @@
+CREATE TABLE IF NOT EXISTS error_group (
+    id TEXT PRIMARY KEY NOT NULL,
+    site_id TEXT,
+    message TEXT NOT NULL,
+    source TEXT,
+    count INTEGER NOT NULL DEFAULT 0,
+    first_seen TIMESTAMP NOT NULL,
+    last_seen TIMESTAMP NOT NULL,
+    FOREIGN KEY (site_id) REFERENCES site (id)
+);
+
+CREATE INDEX IF NOT EXISTS idx_error_group_last_seen ON error_group (last_seen);
+
+CREATE TABLE IF NOT EXISTS error_occurrence (
+    id TEXT PRIMARY KEY NOT NULL,
+    error_group_id TEXT NOT NULL,
+    collector_id TEXT NOT NULL,
+    url TEXT NOT NULL,
+    message TEXT NOT NULL,
+    source TEXT,
+    line_number INTEGER,
+    column_number INTEGER,
+    stack TEXT,
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+    FOREIGN KEY (error_group_id) REFERENCES error_group (id),
+    FOREIGN KEY (collector_id) REFERENCES collector (id)
+);
+
+CREATE INDEX IF NOT EXISTS idx_error_occurrence_group ON error_occurrence (error_group_id, created_at);
+CREATE INDEX IF NOT EXISTS idx_error_occurrence_collector_id ON error_occurrence (collector_id);
@@ -0,0 +1,7 @@
+ALTER TABLE event DROP COLUMN click_id;
+ALTER TABLE event DROP COLUMN ref_param;
+ALTER TABLE event DROP COLUMN utm_content;
+ALTER TABLE event DROP COLUMN utm_term;
+ALTER TABLE event DROP COLUMN utm_campaign;
+ALTER TABLE event DROP COLUMN utm_medium;
+ALTER TABLE event DROP COLUMN utm_source;
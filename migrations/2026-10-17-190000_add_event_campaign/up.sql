@@ -0,0 +1,7 @@
+ALTER TABLE event ADD COLUMN utm_source TEXT;
+ALTER TABLE event ADD COLUMN utm_medium TEXT;
+ALTER TABLE event ADD COLUMN utm_campaign TEXT;
+ALTER TABLE event ADD COLUMN utm_term TEXT;
+ALTER TABLE event ADD COLUMN utm_content TEXT;
+ALTER TABLE event ADD COLUMN ref_param TEXT;
+ALTER TABLE event ADD COLUMN click_id TEXT;
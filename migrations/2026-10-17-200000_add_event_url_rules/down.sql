@@ -0,0 +1,2 @@
+ALTER TABLE event DROP COLUMN path_template;
+ALTER TABLE event DROP COLUMN raw_url;
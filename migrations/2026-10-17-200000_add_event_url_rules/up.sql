@@ -0,0 +1,2 @@
+ALTER TABLE event ADD COLUMN raw_url TEXT;
+ALTER TABLE event ADD COLUMN path_template TEXT;
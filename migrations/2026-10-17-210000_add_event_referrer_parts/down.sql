@@ -0,0 +1,4 @@
+ALTER TABLE event DROP COLUMN channel;
+ALTER TABLE event DROP COLUMN referrer_path;
+ALTER TABLE event DROP COLUMN referrer_domain;
+ALTER TABLE event DROP COLUMN referrer_host;
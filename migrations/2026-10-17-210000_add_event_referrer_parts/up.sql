@@ -0,0 +1,4 @@
+ALTER TABLE event ADD COLUMN referrer_host TEXT;
+ALTER TABLE event ADD COLUMN referrer_domain TEXT;
+ALTER TABLE event ADD COLUMN referrer_path TEXT;
+ALTER TABLE event ADD COLUMN channel TEXT;
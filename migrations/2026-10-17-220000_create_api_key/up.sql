@@ -0,0 +1,11 @@
+CREATE TABLE IF NOT EXISTS api_key (
+    id TEXT PRIMARY KEY NOT NULL,
+    site_id TEXT NOT NULL,
+    name TEXT NOT NULL,
+    key_hash TEXT NOT NULL UNIQUE,
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+    last_used_at TIMESTAMP,
+    FOREIGN KEY (site_id) REFERENCES site (id)
+);
+
+CREATE INDEX IF NOT EXISTS idx_api_key_site_id ON api_key (site_id);
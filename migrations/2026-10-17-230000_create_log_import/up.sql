@@ -0,0 +1,13 @@
+CREATE TABLE IF NOT EXISTS log_import (
+    id TEXT PRIMARY KEY NOT NULL,
+    site_id TEXT NOT NULL,
+    path TEXT NOT NULL,
+    fingerprint TEXT NOT NULL,
+    lines INTEGER NOT NULL DEFAULT 0,
+    collectors INTEGER NOT NULL DEFAULT 0,
+    events INTEGER NOT NULL DEFAULT 0,
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+    updated_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+    FOREIGN KEY (site_id) REFERENCES site (id),
+    UNIQUE (site_id, fingerprint)
+);
@@ -0,0 +1,3 @@
+DROP INDEX IF EXISTS idx_collector_visitor_source;
+
+ALTER TABLE collector DROP COLUMN source;
@@ -0,0 +1,4 @@
+-- Collectors created before sources were recorded are assumed to come from the tracker
+ALTER TABLE collector ADD COLUMN source TEXT NOT NULL DEFAULT 'tracker';
+
+CREATE INDEX IF NOT EXISTS idx_collector_visitor_source ON collector (visitor_id, source);
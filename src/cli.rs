@@ -0,0 +1,234 @@
+use std::{path::PathBuf, sync::Arc};
+
+use chrono::Duration;
+use rocket::{Build, Ignite, Rocket};
+
+use crate::{
+    AppState, DbConn,
+    models::{ApiKey, Collector, Site},
+    services::{
+        access_log::LogFormat,
+        log_import::{self, LogSessions},
+    },
+};
+
+/// Help printed for unknown commands
+pub const USAGE: &str = "\
+Usage: website_stats [COMMAND]
+
+Commands:
+  serve              Start the server (default)
+  anonymize-origins  Rewrite stored visitor IP addresses with the IP_ANONYMIZATION mode
+  create-api-key SITE_ID NAME
+                     Create a key for POST /api/track and print it
+  revoke-api-key KEY_ID
+                     Delete a key, refusing the requests made with it
+  import-logs SITE_ID FILE... [--format FORMAT] [--session-timeout MINUTES]
+                     Import the page views of access logs, oldest file first. FORMAT is
+                     combined (default), common, or an nginx log_format string";
+
+/// Inactivity after which imported hits start a new session, by default
+const DEFAULT_SESSION_TIMEOUT_MINUTES: i64 = 30;
+
+/// # `connect`
+/// Ignites the application without launching it, to reuse its configuration and database pool.
+///
+/// ## Arguments
+/// * `rocket` - The configured application
+///
+/// ## Errors
+/// If the application fails to ignite or the database is unavailable.
+///
+/// ## Returns
+/// * `Result<(Rocket<Ignite>, DbConn), String>` - The application and a database connection
+pub async fn connect(rocket: Rocket<Build>) -> Result<(Rocket<Ignite>, DbConn), String> {
+    let rocket = rocket
+        .ignite()
+        .await
+        .map_err(|e| format!("Failed to start: {e}"))?;
+    let conn = DbConn::get_one(&rocket)
+        .await
+        .ok_or("Failed to connect to the database")?;
+
+    Ok((rocket, conn))
+}
+
+/// # `anonymize_origins`
+/// Rewrites the stored origin of every collector with the configured anonymization mode.
+///
+/// ## Arguments
+/// * `rocket` - The configured application
+///
+/// ## Errors
+/// If the database cannot be reached or updated.
+pub async fn anonymize_origins(rocket: Rocket<Build>) -> Result<(), String> {
+    let (rocket, conn) = connect(rocket).await?;
+    let anonymizer = rocket
+        .state::<Arc<AppState>>()
+        .ok_or("Application state is missing")?
+        .ip_anonymizer
+        .clone();
+    let mode = anonymizer.mode();
+
+    let rewritten = Collector::anonymize_origins(anonymizer, &conn)
+        .await
+        .map_err(|e| format!("Failed to anonymize origins: {e}"))?;
+    println!("Rewrote {rewritten} origins with the {mode} mode");
+
+    Ok(())
+}
+
+/// # `create_api_key`
+/// Creates an API key for a site and prints it. Only its hash is stored, so it cannot be
+/// shown again.
+///
+/// ## Arguments
+/// * `rocket` - The configured application
+/// * `site_id` - The site the key records events for
+/// * `name` - What the key is used for
+///
+/// ## Errors
+/// If the site is unknown or the database cannot be reached.
+pub async fn create_api_key(
+    rocket: Rocket<Build>,
+    site_id: Option<String>,
+    name: Option<String>,
+) -> Result<(), String> {
+    let (Some(site_id), Some(name)) = (site_id, name) else {
+        return Err(format!("Missing site ID or key name\n\n{USAGE}"));
+    };
+    let (_rocket, conn) = connect(rocket).await?;
+
+    let site = Site::find(site_id.clone(), &conn)
+        .await
+        .map_err(|e| format!("Failed to find site: {e}"))?
+        .ok_or(format!("Unknown site {site_id}"))?;
+
+    let (key, secret) = ApiKey::generate(site.id, name);
+    let id = ApiKey::insert(key, &conn)
+        .await
+        .map_err(|e| format!("Failed to create API key: {e}"))?;
+    println!("Created API key {id} for {}: {secret}", site.domain);
+    println!("Store it now, it cannot be shown again");
+
+    Ok(())
+}
+
+/// # `revoke_api_key`
+/// Deletes an API key, so that the backends still using it are refused.
+///
+/// ## Arguments
+/// * `rocket` - The configured application
+/// * `id` - The ID of the key, as printed by `create-api-key`
+///
+/// ## Errors
+/// If the key is unknown or the database cannot be reached.
+pub async fn revoke_api_key(rocket: Rocket<Build>, id: Option<String>) -> Result<(), String> {
+    let Some(id) = id else {
+        return Err(format!("Missing key ID\n\n{USAGE}"));
+    };
+    let (_rocket, conn) = connect(rocket).await?;
+
+    if !ApiKey::revoke(id.clone(), &conn)
+        .await
+        .map_err(|e| format!("Failed to revoke API key: {e}"))?
+    {
+        return Err(format!("Unknown API key {id}"));
+    }
+    println!("Revoked API key {id}");
+
+    Ok(())
+}
+
+/// # `import_logs`
+/// Imports the page views of access logs into a site, printing the progress. Files
+/// already imported are skipped, and partly imported ones resume where they stopped.
+/// Sessions carry over from one file to the next, which should be given oldest first.
+///
+/// ## Arguments
+/// * `rocket` - The configured application
+/// * `args` - The site ID, the files, and the `--format` and `--session-timeout` options
+///
+/// ## Errors
+/// If the arguments are invalid, the site is unknown, a file cannot be read or the
+/// database fails.
+pub async fn import_logs(rocket: Rocket<Build>, args: &[String]) -> Result<(), String> {
+    let mut format = "combined".to_string();
+    let mut session_timeout = DEFAULT_SESSION_TIMEOUT_MINUTES;
+    let mut positional = Vec::new();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--format" => format.clone_from(args.next().ok_or("Missing log format")?),
+            "--session-timeout" => {
+                session_timeout = args
+                    .next()
+                    .and_then(|minutes| minutes.parse().ok())
+                    .filter(|minutes| *minutes > 0)
+                    .ok_or("Session timeout must be a number of minutes")?;
+            }
+            _ => positional.push(arg.clone()),
+        }
+    }
+    let Some((site_id, files)) = positional
+        .split_first()
+        .filter(|(_, files)| !files.is_empty())
+    else {
+        return Err(format!("Missing site ID or log file\n\n{USAGE}"));
+    };
+    let format: LogFormat = format.parse()?;
+
+    let (rocket, conn) = connect(rocket).await?;
+    let state = rocket
+        .state::<Arc<AppState>>()
+        .ok_or("Application state is missing")?;
+
+    let site = Site::find(site_id.clone(), &conn)
+        .await
+        .map_err(|e| format!("Failed to find site: {e}"))?
+        .ok_or(format!("Unknown site {site_id}"))?;
+    let mut sessions = LogSessions::historical(site, Duration::minutes(session_timeout));
+
+    for file in files {
+        let path = PathBuf::from(file);
+        let progress = log_import::import_logs(
+            &path,
+            &format,
+            &mut sessions,
+            |progress| {
+                eprintln!(
+                    "{}: {:.1}%, {} lines, {} page views in {} sessions",
+                    path.display(),
+                    progress.percent(),
+                    progress.lines,
+                    progress.page_views,
+                    progress.sessions
+                );
+            },
+            state,
+            &conn,
+        )
+        .await?;
+
+        if progress.resumed_lines == progress.lines {
+            println!("{}: already imported", path.display());
+            continue;
+        }
+        println!(
+            "{}: imported {} page views in {} sessions from {} lines",
+            path.display(),
+            progress.page_views,
+            progress.sessions,
+            progress.lines - progress.resumed_lines
+        );
+        println!(
+            "  skipped {} lines imported before, {} bot hits, {} other requests and {} unreadable lines",
+            progress.resumed_lines,
+            progress.bot_hits,
+            progress.other_requests,
+            progress.invalid_lines
+        );
+    }
+
+    Ok(())
+}
@@ -1,12 +1,59 @@
-use std::env;
+use std::{env, str::FromStr};
 
 use rocket::figment::Figment;
 
+use crate::services::{anonymization::IpAnonymization, privacy::PrivacyPolicy};
+
+/// # `GeoProvider`
+/// Source used to resolve the location of visitors.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum GeoProvider {
+    /// The `IPInfo` HTTP API
+    IpInfo,
+    /// A local MaxMind / DB-IP `.mmdb` database
+    Mmdb,
+    /// A static CIDR-to-city table, meant for development and tests
+    Static,
+}
+
+impl FromStr for GeoProvider {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "ipinfo" => Ok(GeoProvider::IpInfo),
+            "mmdb" => Ok(GeoProvider::Mmdb),
+            "static" => Ok(GeoProvider::Static),
+            other => Err(format!("Unknown geolocation provider: {other}")),
+        }
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct AppConfig {
     pub port: i16,
     pub address: String,
     pub dev: bool,
+    pub geo_provider: GeoProvider,
+    pub geoip_database: Option<String>,
+    pub geo_static_table: Option<String>,
+    pub ipinfo_token: String,
+    pub geo_cache_ttl: i64,
+    pub user_agent_rules: Option<String>,
+    pub bot_networks: Option<String>,
+    pub referrer_sources: Option<String>,
+    pub geoip_asn_database: Option<String>,
+    pub ip_anonymization: IpAnonymization,
+    pub ip_hash_key: Option<String>,
+    pub privacy_signals: PrivacyPolicy,
+    pub collector_token_secret: Option<String>,
+    pub collector_token_ttl: i64,
+    pub rate_limit_session: u32,
+    pub rate_limit_event: u32,
+    pub rate_limit_pixel: u32,
+    pub rate_limit_vitals: u32,
+    pub rate_limit_errors: u32,
+    pub access_logs: Option<String>,
 }
 
 impl AppConfig {
@@ -23,8 +70,107 @@ impl AppConfig {
             .unwrap_or("false".to_string())
             .parse()
             .unwrap_or(false);
+        let geoip_database = env::var("GEOIP_DATABASE")
+            .ok()
+            .filter(|path| !path.is_empty());
+        let geo_static_table = env::var("GEO_STATIC_TABLE")
+            .ok()
+            .filter(|path| !path.is_empty());
+        let ipinfo_token = env::var("IPINFO_TOKEN").unwrap_or_default();
+        let geo_cache_ttl: i64 = env::var("GEO_CACHE_TTL")
+            .unwrap_or("604800".to_string())
+            .parse()
+            .unwrap_or(604_800);
+        let user_agent_rules = env::var("USER_AGENT_RULES")
+            .ok()
+            .filter(|path| !path.is_empty());
+        let bot_networks = env::var("BOT_NETWORKS")
+            .ok()
+            .filter(|path| !path.is_empty());
+        let referrer_sources = env::var("REFERRER_SOURCES")
+            .ok()
+            .filter(|path| !path.is_empty());
+        let geoip_asn_database = env::var("GEOIP_ASN_DATABASE")
+            .ok()
+            .filter(|path| !path.is_empty());
+        let ip_anonymization = env::var("IP_ANONYMIZATION")
+            .ok()
+            .and_then(|mode| mode.parse().ok())
+            .unwrap_or(IpAnonymization::Truncate);
+        let ip_hash_key = env::var("IP_HASH_KEY").ok().filter(|key| !key.is_empty());
+        let privacy_signals = env::var("PRIVACY_SIGNALS")
+            .ok()
+            .and_then(|policy| policy.parse().ok())
+            .unwrap_or(PrivacyPolicy::Aggregate);
+        let collector_token_secret = env::var("COLLECTOR_TOKEN_SECRET")
+            .ok()
+            .filter(|secret| !secret.is_empty());
+        let collector_token_ttl: i64 = env::var("COLLECTOR_TOKEN_TTL")
+            .unwrap_or("86400".to_string())
+            .parse()
+            .unwrap_or(86_400);
+        let rate_limit_session: u32 = env::var("RATE_LIMIT_SESSION")
+            .unwrap_or("60".to_string())
+            .parse()
+            .unwrap_or(60);
+        let rate_limit_event: u32 = env::var("RATE_LIMIT_EVENT")
+            .unwrap_or("120".to_string())
+            .parse()
+            .unwrap_or(120);
+        // The other ingestion routes default to the limit of the route they used to share
+        let rate_limit_pixel: u32 = env::var("RATE_LIMIT_PIXEL")
+            .ok()
+            .and_then(|limit| limit.parse().ok())
+            .unwrap_or(rate_limit_session);
+        let rate_limit_vitals: u32 = env::var("RATE_LIMIT_VITALS")
+            .ok()
+            .and_then(|limit| limit.parse().ok())
+            .unwrap_or(rate_limit_event);
+        let rate_limit_errors: u32 = env::var("RATE_LIMIT_ERRORS")
+            .ok()
+            .and_then(|limit| limit.parse().ok())
+            .unwrap_or(rate_limit_event);
+
+        let access_logs = env::var("ACCESS_LOGS").ok().filter(|path| !path.is_empty());
+
+        // Without an explicit choice, prefer the local database when there is one,
+        // and never hit a third-party API while developing.
+        let geo_provider = env::var("GEO_PROVIDER")
+            .ok()
+            .and_then(|provider| provider.parse().ok())
+            .unwrap_or(if geoip_database.is_some() {
+                GeoProvider::Mmdb
+            } else if dev {
+                GeoProvider::Static
+            } else {
+                GeoProvider::IpInfo
+            });
 
-        Self { port, address, dev }
+        Self {
+            port,
+            address,
+            dev,
+            geo_provider,
+            geoip_database,
+            geo_static_table,
+            ipinfo_token,
+            geo_cache_ttl,
+            user_agent_rules,
+            bot_networks,
+            referrer_sources,
+            geoip_asn_database,
+            ip_anonymization,
+            ip_hash_key,
+            privacy_signals,
+            collector_token_secret,
+            collector_token_ttl,
+            rate_limit_session,
+            rate_limit_event,
+            rate_limit_pixel,
+            rate_limit_vitals,
+            rate_limit_errors,
+            access_logs,
+        }
     }
 }
 
@@ -33,7 +33,7 @@ impl Fairing for Cors {
         let allowed_origin = origin;
 
         response.set_header(Header::new("Access-Control-Allow-Origin", allowed_origin));
-        response.set_header(Header::new("Access-Control-Allow-Methods", "GET, POST"));
+        response.set_header(Header::new("Access-Control-Allow-Methods", "GET, POST, PUT"));
         response.set_header(Header::new(
             "Access-Control-Allow-Headers",
             "Content-Type, Authorization",
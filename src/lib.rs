@@ -1,18 +1,30 @@
 pub mod api_response;
+pub mod cli;
 pub mod config;
 pub mod cors;
+pub mod log_tailer;
+pub mod logger;
+pub mod metrics;
 pub mod models;
 pub mod paginated;
+pub mod rate_limiter;
+pub mod request_logger;
 pub mod routes;
 pub mod schema;
 pub mod services;
 pub mod sql_functions;
-pub mod logger;
-pub mod request_logger;
+#[cfg(test)]
+pub(crate) mod test_support;
+
+use std::sync::Arc;
 
 use rocket::http::Status;
 use rocket::request::{FromRequest, Outcome, Request};
 use rocket_sync_db_pools::database;
+use services::{
+    privacy::PrivacySignal,
+    user_agent::{DeviceType, UserAgentParser},
+};
 
 /// # Database Connection Pool
 /// Provides a SQLite connection pool using rocket_sync_db_pools.
@@ -23,13 +35,61 @@ pub struct DbConn(diesel::SqliteConnection);
 pub struct AppState {
     pub address: String,
     pub dev_mode: bool,
+    pub geo_locator: Box<dyn services::location::GeoLocator>,
+    pub geo_cache_ttl: i64,
+    pub metrics: metrics::Metrics,
+    pub user_agent_parser: UserAgentParser,
+    pub bot_detector: services::bot_detection::BotDetector,
+    pub ip_anonymizer: services::anonymization::IpAnonymizer,
+    pub privacy_policy: services::privacy::PrivacyPolicy,
+    pub token_signer: services::collector_token::CollectorTokenSigner,
+    pub tracking_script: services::tracking_script::TrackingScript,
+    pub error_fingerprinter: services::error_fingerprint::ErrorFingerprinter,
+    pub referrer_classifier: services::referrer::ReferrerClassifier,
 }
 
 #[derive(Debug)]
 pub struct UserAgentInfo {
     os: String,
+    os_version: Option<String>,
     browser: String,
-    // raw_user_agent: String,
+    browser_version: Option<String>,
+    device_type: DeviceType,
+    engine: String,
+    raw_user_agent: String,
+    /// Real browsers always send `Accept-Language`, headless ones and scripts often don't
+    headless: bool,
+    /// Opt-out signal (`DNT` or `Sec-GPC`) sent by the browser, if any
+    privacy_signal: Option<PrivacySignal>,
+}
+
+impl UserAgentInfo {
+    /// # `relayed`
+    /// Describes a visitor from the user agent a backend relayed, rather than from the
+    /// request itself, which comes from that backend.
+    ///
+    /// ## Arguments
+    /// * `user_agent` - The `User-Agent` of the visitor, empty if unknown
+    /// * `parser` - The user agent parser
+    ///
+    /// ## Returns
+    /// * `UserAgentInfo` - The visitor, without headless nor privacy signals
+    #[must_use]
+    pub fn relayed(user_agent: &str, parser: &UserAgentParser) -> Self {
+        let parsed = parser.parse(user_agent);
+
+        UserAgentInfo {
+            os: parsed.os,
+            os_version: parsed.os_version,
+            browser: parsed.browser,
+            browser_version: parsed.browser_version,
+            device_type: parsed.device_type,
+            engine: parsed.engine,
+            raw_user_agent: user_agent.to_string(),
+            headless: false,
+            privacy_signal: None,
+        }
+    }
 }
 
 // Implementation of FromRequest to extract User-Agent
@@ -41,55 +101,77 @@ impl<'r> FromRequest<'r> for UserAgentInfo {
         // Extract the User-Agent header
         match request.headers().get_one("User-Agent") {
             Some(user_agent) => {
-                // Parse the user agent string
-                let os = parse_os(user_agent);
-                let browser = parse_browser(user_agent);
+                // Parse the user agent string with the configured rules
+                let parsed = match request.rocket().state::<Arc<AppState>>() {
+                    Some(state) => state.user_agent_parser.parse(user_agent),
+                    None => UserAgentParser::bundled().parse(user_agent),
+                };
 
-                Outcome::Success(UserAgentInfo { os, browser })
+                Outcome::Success(UserAgentInfo {
+                    os: parsed.os,
+                    os_version: parsed.os_version,
+                    browser: parsed.browser,
+                    browser_version: parsed.browser_version,
+                    device_type: parsed.device_type,
+                    engine: parsed.engine,
+                    raw_user_agent: user_agent.to_string(),
+                    headless: request.headers().get_one("Accept-Language").is_none(),
+                    privacy_signal: PrivacySignal::from_headers(request.headers()),
+                })
             }
             None => Outcome::Error((Status::BadRequest, ())),
         }
     }
 }
 
-// Function to parse OS information from user agent
-fn parse_os(user_agent: &str) -> String {
-    let user_agent = user_agent.to_lowercase();
-
-    if user_agent.contains("windows") {
-        "Windows".to_string();
-    } else if user_agent.contains("mac os") || user_agent.contains("macos") {
-        "MacOS".to_string();
-    } else if user_agent.contains("linux") {
-        "Linux".to_string();
-    } else if user_agent.contains("android") {
-        "Android".to_string();
-    } else if user_agent.contains("iphone") || user_agent.contains("ipad") {
-        "iOS".to_string();
-    }
+/// # `PageOrigin`
+/// Origin (`scheme://host:port`) of the page a request comes from, taken from the
+/// `Origin` header or, for scripts loaded with a `<script>` tag, from the `Referer`.
+#[derive(Debug, Clone, Default)]
+pub struct PageOrigin(pub Option<String>);
 
-    "Unknown OS".to_string()
-}
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for PageOrigin {
+    type Error = ();
+
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        let origin = request
+            .headers()
+            .get_one("Origin")
+            .filter(|origin| *origin != "null")
+            .or_else(|| request.headers().get_one("Referer"))
+            .and_then(|url| url::Url::parse(url).ok())
+            .map(|url| url.origin().ascii_serialization())
+            .filter(|origin| origin != "null");
 
-// Function to parse browser information from user agent
-fn parse_browser(user_agent: &str) -> String {
-    let user_agent = user_agent.to_lowercase();
-
-    if user_agent.contains("firefox") {
-        "Firefox".to_string();
-    } else if user_agent.contains("chrome") && !user_agent.contains("chromium") {
-        "Chrome".to_string();
-    } else if user_agent.contains("chromium") {
-        "Chromium".to_string();
-    } else if user_agent.contains("safari") && !user_agent.contains("chrome") {
-        "Safari".to_string();
-    } else if user_agent.contains("edge") {
-        "Edge".to_string();
-    } else if user_agent.contains("opera") {
-        "Opera".to_string();
+        Outcome::Success(PageOrigin(origin))
     }
+}
+
+/// # `BearerToken`
+/// Token sent in an `Authorization: Bearer <token>` header, if any.
+#[derive(Debug, Clone, Default)]
+pub struct BearerToken(pub Option<String>);
 
-    "Unknown Browser".to_string()
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for BearerToken {
+    type Error = ();
+
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        let token = request
+            .headers()
+            .get_one("Authorization")
+            .and_then(|header| {
+                header
+                    .strip_prefix("Bearer ")
+                    .or_else(|| header.strip_prefix("bearer "))
+            })
+            .map(str::trim)
+            .filter(|token| !token.is_empty())
+            .map(ToString::to_string);
+
+        Outcome::Success(BearerToken(token))
+    }
 }
 
 // CONSTS
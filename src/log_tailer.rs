@@ -0,0 +1,623 @@
+use std::{
+    collections::VecDeque,
+    fs::{self, File},
+    io::{BufRead, BufReader, Read, Seek, SeekFrom},
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use chrono::Utc;
+use rocket::{
+    Orbit, Rocket, Shutdown,
+    fairing::{Fairing, Info, Kind},
+    tokio::{self, time::sleep},
+};
+use serde::Deserialize;
+
+use crate::{
+    AppState, DbConn,
+    logger::Logger,
+    metrics::Metrics,
+    models::{Collector, Event, Site, VisitorSalt},
+    services::{
+        access_log::{LogEntry, LogFormat},
+        log_import::{LogHit, LogSessions},
+    },
+};
+
+/// How often followed logs are checked for new lines
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+/// How long page views of deduplicated sources wait, so that the tracking script of the
+/// same page has time to start its session
+const DEDUP_DELAY: Duration = Duration::from_secs(10);
+/// A visitor with events from the tracking script this recent runs it
+const DEDUP_WINDOW_MINUTES: i64 = 30;
+/// How long a rotated log is still read, for lines the server wrote before reopening it
+const ROTATION_GRACE: Duration = Duration::from_secs(60);
+/// Bytes compared to notice that a log was truncated and written again
+const HEAD_BYTES: usize = 256;
+/// Inactivity after which log hits start a new session, by default
+const DEFAULT_SESSION_TIMEOUT_MINUTES: i64 = 30;
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawSource {
+    site_id: String,
+    path: PathBuf,
+    #[serde(default)]
+    format: Option<String>,
+    #[serde(default)]
+    dedup: Option<bool>,
+    #[serde(default)]
+    session_timeout: Option<i64>,
+}
+
+/// # `AccessLogSource`
+/// An access log followed for a site, read from the `ACCESS_LOGS` file:
+///
+/// ```json
+/// [{"site_id": "SITE_ID", "path": "/var/log/nginx/access.log", "format": "combined",
+///   "dedup": true, "session_timeout": 30}]
+/// ```
+#[derive(Debug, Clone)]
+pub struct AccessLogSource {
+    pub site_id: String,
+    pub path: PathBuf,
+    /// Layout of the lines, `combined` by default
+    pub format: LogFormat,
+    /// Skip the page views of visitors the tracking script records, `true` by default
+    pub dedup: bool,
+    /// Inactivity after which a visitor starts a new session, 30 minutes by default
+    pub session_timeout: chrono::Duration,
+}
+
+impl AccessLogSource {
+    /// # `from_json`
+    /// Reads the followed access logs from the content of a sources file.
+    ///
+    /// ## Arguments
+    /// * `json` - The sources, a JSON array
+    ///
+    /// ## Errors
+    /// If the JSON is malformed or a source has an invalid format or timeout.
+    ///
+    /// ## Returns
+    /// * `Result<Vec<AccessLogSource>, String>` - The sources
+    pub fn from_json(json: &str) -> Result<Vec<Self>, String> {
+        let raw: Vec<RawSource> =
+            serde_json::from_str(json).map_err(|e| format!("Invalid access logs file: {e}"))?;
+
+        raw.into_iter()
+            .map(|source| {
+                let format = source
+                    .format
+                    .as_deref()
+                    .unwrap_or("combined")
+                    .parse()
+                    .map_err(|e| format!("{}: {e}", source.path.display()))?;
+                let session_timeout = source
+                    .session_timeout
+                    .unwrap_or(DEFAULT_SESSION_TIMEOUT_MINUTES);
+                if session_timeout <= 0 {
+                    return Err(format!(
+                        "{}: session timeout must be a number of minutes",
+                        source.path.display()
+                    ));
+                }
+
+                Ok(AccessLogSource {
+                    site_id: source.site_id,
+                    path: source.path,
+                    format,
+                    dedup: source.dedup.unwrap_or(true),
+                    session_timeout: chrono::Duration::minutes(session_timeout),
+                })
+            })
+            .collect()
+    }
+
+    /// # `from_file`
+    /// Reads the followed access logs from a sources file on disk.
+    ///
+    /// ## Arguments
+    /// * `path` - Path to the sources file
+    ///
+    /// ## Errors
+    /// If the file cannot be read or is not a valid sources file.
+    ///
+    /// ## Returns
+    /// * `Result<Vec<AccessLogSource>, String>` - The sources
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Vec<Self>, String> {
+        let content = fs::read_to_string(path.as_ref())
+            .map_err(|e| format!("Failed to read {}: {e}", path.as_ref().display()))?;
+
+        Self::from_json(&content)
+    }
+}
+
+/// Identity of a file, to notice when a log is replaced by a new one
+#[cfg(unix)]
+fn file_id(metadata: &fs::Metadata) -> Option<u64> {
+    use std::os::unix::fs::MetadataExt;
+
+    Some(metadata.ino())
+}
+
+#[cfg(not(unix))]
+fn file_id(_metadata: &fs::Metadata) -> Option<u64> {
+    None
+}
+
+/// A log file read as it grows. Follows the path rather than the file, so that logs
+/// rotated by renaming (`create`) or truncating (`copytruncate`) keep being read.
+struct LogFollower {
+    path: PathBuf,
+    reader: Option<BufReader<File>>,
+    id: Option<u64>,
+    position: u64,
+    /// First bytes of the file, which change when it is truncated and written again
+    head: Vec<u8>,
+    /// Start of a line still being written
+    partial: Vec<u8>,
+    /// The previous file after a rotation, and when it was rotated
+    rotated: Option<(BufReader<File>, Vec<u8>, Instant)>,
+}
+
+impl LogFollower {
+    /// Starts at the end of the log: older lines are for `import-logs`.
+    fn new(path: PathBuf) -> Self {
+        let mut follower = Self {
+            path,
+            reader: None,
+            id: None,
+            position: 0,
+            head: Vec::new(),
+            partial: Vec::new(),
+            rotated: None,
+        };
+        if let Ok(file) = File::open(&follower.path) {
+            let metadata = file.metadata().ok();
+            follower.id = metadata.as_ref().and_then(file_id);
+            follower.head = read_head(&follower.path);
+            let mut reader = BufReader::new(file);
+            follower.position = metadata
+                .map(|metadata| metadata.len())
+                .filter(|len| reader.seek(SeekFrom::Start(*len)).is_ok())
+                .unwrap_or_default();
+            follower.reader = Some(reader);
+        }
+
+        follower
+    }
+
+    /// Returns the lines written since the previous call.
+    fn read_lines(&mut self) -> Vec<String> {
+        let mut lines = Vec::new();
+
+        if let Some((reader, partial, rotated_at)) = &mut self.rotated {
+            read_complete_lines(reader, partial, &mut lines);
+            if rotated_at.elapsed() >= ROTATION_GRACE {
+                self.rotated = None;
+            }
+        }
+
+        // Without the file, it was renamed and is not created again yet
+        if let Ok(metadata) = fs::metadata(&self.path) {
+            let id = file_id(&metadata);
+            let head = read_head(&self.path);
+
+            if self.reader.is_none() || id != self.id {
+                if let Some(mut reader) = self.reader.take() {
+                    let mut partial = std::mem::take(&mut self.partial);
+                    read_complete_lines(&mut reader, &mut partial, &mut lines);
+                    self.rotated = Some((reader, partial, Instant::now()));
+                }
+                self.reader = File::open(&self.path).ok().map(BufReader::new);
+                self.id = id;
+                self.position = 0;
+            } else if (metadata.len() < self.position || !head.starts_with(&self.head))
+                && let Some(reader) = &mut self.reader
+                && reader.seek(SeekFrom::Start(0)).is_ok()
+            {
+                // Truncated in place
+                self.partial.clear();
+                self.position = 0;
+            }
+            self.head = head;
+        }
+
+        if let Some(reader) = &mut self.reader {
+            self.position += read_complete_lines(reader, &mut self.partial, &mut lines);
+        }
+
+        lines
+    }
+}
+
+/// Reads the first bytes of a file.
+fn read_head(path: &Path) -> Vec<u8> {
+    let mut head = Vec::with_capacity(HEAD_BYTES);
+    if let Ok(file) = File::open(path) {
+        let _ = file.take(HEAD_BYTES as u64).read_to_end(&mut head);
+    }
+
+    head
+}
+
+/// Reads the complete lines available, keeping the start of an unfinished one.
+fn read_complete_lines(
+    reader: &mut BufReader<File>,
+    partial: &mut Vec<u8>,
+    lines: &mut Vec<String>,
+) -> u64 {
+    let mut read_bytes = 0;
+    loop {
+        match reader.read_until(b'\n', partial) {
+            Ok(0) | Err(_) => return read_bytes,
+            Ok(read) => {
+                read_bytes += read as u64;
+                if partial.ends_with(b"\n") {
+                    let line = String::from_utf8_lossy(partial);
+                    lines.push(line.trim_end_matches(['\r', '\n']).to_string());
+                    partial.clear();
+                }
+            }
+        }
+    }
+}
+
+/// # `LogTailer`
+/// Follows access logs once the server is launched, recording their page views like
+/// `import-logs` does for old logs, so that visitors blocking `/stats.js` are counted.
+pub struct LogTailer {
+    sources: Vec<AccessLogSource>,
+}
+
+impl LogTailer {
+    #[must_use]
+    pub fn new(sources: Vec<AccessLogSource>) -> Self {
+        Self { sources }
+    }
+}
+
+#[rocket::async_trait]
+impl Fairing for LogTailer {
+    fn info(&self) -> Info {
+        Info {
+            name: "Access Log Tailer",
+            kind: Kind::Liftoff,
+        }
+    }
+
+    async fn on_liftoff(&self, rocket: &Rocket<Orbit>) {
+        let Some(state) = rocket.state::<Arc<AppState>>() else {
+            return;
+        };
+
+        for source in &self.sources {
+            let Some(conn) = DbConn::get_one(rocket).await else {
+                Logger::error(
+                    "AccessLog",
+                    &format!("No database connection to follow {}", source.path.display()),
+                );
+                continue;
+            };
+
+            tokio::spawn(follow(
+                source.clone(),
+                state.clone(),
+                conn,
+                rocket.shutdown(),
+            ));
+        }
+    }
+}
+
+/// Records the page views of a log until the server shuts down.
+async fn follow(source: AccessLogSource, state: Arc<AppState>, conn: DbConn, shutdown: Shutdown) {
+    let site = match Site::find(source.site_id.clone(), &conn).await {
+        Ok(Some(site)) => site,
+        Ok(None) => {
+            Logger::error(
+                "AccessLog",
+                &format!(
+                    "Unknown site {}, not following {}",
+                    source.site_id,
+                    source.path.display()
+                ),
+            );
+            return;
+        }
+        Err(e) => {
+            Logger::error("AccessLog", &format!("Failed to find site: {e}"));
+            return;
+        }
+    };
+    Logger::info(
+        "AccessLog",
+        &format!("Following {} for {}", source.path.display(), site.domain),
+    );
+
+    let delay = if source.dedup {
+        DEDUP_DELAY
+    } else {
+        Duration::ZERO
+    };
+    let mut follower = LogFollower::new(source.path.clone());
+    let mut sessions = LogSessions::live(site, source.session_timeout);
+    let mut pending: VecDeque<(Instant, LogEntry)> = VecDeque::new();
+
+    loop {
+        tokio::select! {
+            () = shutdown.clone() => break,
+            () = sleep(POLL_INTERVAL) => {}
+        }
+
+        for line in follower.read_lines() {
+            match source.format.parse_line(&line) {
+                Some(entry) if entry.is_page_view() => pending.push_back((Instant::now(), entry)),
+                Some(_) => {}
+                None => Logger::debug(
+                    "AccessLog",
+                    &format!("Unreadable line in {}: {line}", source.path.display()),
+                ),
+            }
+        }
+
+        while let Some((_, entry)) = pending
+            .front()
+            .filter(|(read_at, _)| read_at.elapsed() >= delay)
+        {
+            if let Err(e) = record(entry, &source, &mut sessions, &state, &conn).await {
+                Logger::error("AccessLog", &e);
+            }
+            pending.pop_front();
+        }
+
+        sessions.close_inactive(Utc::now().naive_utc());
+    }
+}
+
+/// Records a page view read from a log, unless the tracking script recorded the visitor.
+async fn record(
+    entry: &LogEntry,
+    source: &AccessLogSource,
+    sessions: &mut LogSessions,
+    state: &AppState,
+    conn: &DbConn,
+) -> Result<(), String> {
+    if source.dedup && !entry.user_agent.is_empty() {
+        let salt = VisitorSalt::today(conn)
+            .await
+            .map_err(|e| format!("Failed to get visitor salt: {e}"))?;
+        let visitor_id =
+            VisitorSalt::visitor_id(&salt, Some(&source.site_id), entry.ip, &entry.user_agent);
+        let since = Utc::now().naive_utc() - chrono::Duration::minutes(DEDUP_WINDOW_MINUTES);
+        let tracked = Event::recorded_for_visitor(source.site_id.clone(), visitor_id, since, conn)
+            .await
+            .map_err(|e| format!("Failed to check tracked events: {e}"))?;
+        if tracked {
+            Metrics::increment(&state.metrics.access_log_deduplicated);
+            return Ok(());
+        }
+    }
+
+    if let LogHit::PageView { collector, event } = sessions.record(entry, state, conn).await? {
+        if let Some(collector) = collector {
+            Collector::insert(collector, conn)
+                .await
+                .map_err(|e| format!("Failed to record collector: {e}"))?;
+        }
+        Event::insert(event, conn)
+            .await
+            .map_err(|e| format!("Failed to record event: {e}"))?;
+        Metrics::increment(&state.metrics.access_log_page_views);
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        UserAgentInfo,
+        models::{CollectorSource, EventQuery, SiteQuery},
+        routes::event::build_event,
+        schema::event,
+        services::session::build_collector,
+        test_support,
+    };
+    use diesel::{QueryDsl, RunQueryDsl};
+    use std::{env, io::Write, sync::atomic::Ordering};
+
+    const USER_AGENT: &str =
+        "Mozilla/5.0 (X11; Linux x86_64; rv:128.0) Gecko/20100101 Firefox/128.0";
+
+    fn temp_path(name: &str) -> PathBuf {
+        env::temp_dir().join(format!("log_tailer_{}_{name}", ulid::Ulid::new()))
+    }
+
+    fn append(path: &Path, content: &str) {
+        let mut file = fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .expect("writable temp dir");
+        file.write_all(content.as_bytes()).expect("writable log");
+    }
+
+    #[test]
+    fn follows_appended_lines() {
+        let path = temp_path("access.log");
+        append(&path, "old\n");
+        let mut follower = LogFollower::new(path.clone());
+
+        // Lines written before the server started are for `import-logs`
+        assert!(follower.read_lines().is_empty());
+
+        append(&path, "first\nsec");
+        assert_eq!(follower.read_lines(), ["first"]);
+        append(&path, "ond\n");
+        assert_eq!(follower.read_lines(), ["second"]);
+
+        fs::remove_file(&path).ok();
+    }
+
+    #[test]
+    fn follows_rotated_logs() {
+        let path = temp_path("access.log");
+        let rotated = path.with_extension("log.1");
+        append(&path, "before\n");
+        let mut follower = LogFollower::new(path.clone());
+
+        append(&path, "one\n");
+        fs::rename(&path, &rotated).expect("log renamed");
+        // The server still writes to the old file until it reopens its logs
+        append(&rotated, "late\n");
+        assert_eq!(follower.read_lines(), ["one", "late"]);
+
+        append(&path, "fresh\n");
+        append(&rotated, "later\n");
+        assert_eq!(follower.read_lines(), ["later", "fresh"]);
+
+        fs::remove_file(&path).ok();
+        fs::remove_file(&rotated).ok();
+    }
+
+    #[test]
+    fn follows_truncated_logs() {
+        let path = temp_path("access.log");
+        append(&path, "a long line that was already read\n");
+        let mut follower = LogFollower::new(path.clone());
+
+        // Shorter than what was read
+        fs::write(&path, "short\n").expect("log truncated");
+        assert_eq!(follower.read_lines(), ["short"]);
+
+        // Truncated then written past the previous position before the next poll
+        fs::write(
+            &path,
+            "another line, longer than everything written before\nand a second one\n",
+        )
+        .expect("log truncated");
+        assert_eq!(
+            follower.read_lines(),
+            [
+                "another line, longer than everything written before",
+                "and a second one"
+            ]
+        );
+
+        fs::remove_file(&path).ok();
+    }
+
+    #[rocket::async_test]
+    async fn records_log_only_visitors() {
+        let rocket = test_support::rocket().ignite().await.expect("valid rocket");
+        let conn = DbConn::get_one(&rocket).await.expect("database connection");
+        let state = test_support::app_state();
+        let site: Site = SiteQuery {
+            domain: "example.com".to_string(),
+            name: "Example".to_string(),
+            timezone: None,
+            settings: None,
+        }
+        .into();
+        Site::insert(site.clone(), &conn)
+            .await
+            .expect("site inserted");
+        let source = AccessLogSource {
+            site_id: site.id.clone(),
+            path: PathBuf::from("access.log"),
+            format: "combined".parse().expect("valid format"),
+            dedup: true,
+            session_timeout: chrono::Duration::minutes(5),
+        };
+        let entry = |target: &str| LogEntry {
+            ip: "203.0.113.7".parse().expect("valid IP"),
+            time: Utc::now().naive_utc(),
+            method: "GET".to_string(),
+            target: target.to_string(),
+            status: 200,
+            referrer: None,
+            user_agent: USER_AGENT.to_string(),
+            host: None,
+            scheme: None,
+        };
+        let events = || async {
+            conn.run(|c| event::table.count().get_result::<i64>(c))
+                .await
+                .expect("event count")
+        };
+
+        // Migrations come with sample events
+        let seeded = events().await;
+
+        let mut sessions = LogSessions::live(site.clone(), source.session_timeout);
+        record(&entry("/"), &source, &mut sessions, &state, &conn)
+            .await
+            .expect("page view recorded");
+        record(&entry("/pricing"), &source, &mut sessions, &state, &conn)
+            .await
+            .expect("page view recorded");
+        assert_eq!(events().await, seeded + 2);
+
+        // After a restart the session is forgotten, the visitor's log events must not hide it
+        let mut sessions = LogSessions::live(site.clone(), source.session_timeout);
+        record(&entry("/docs"), &source, &mut sessions, &state, &conn)
+            .await
+            .expect("page view recorded");
+        assert_eq!(events().await, seeded + 3);
+        assert_eq!(
+            state
+                .metrics
+                .access_log_deduplicated
+                .load(Ordering::Relaxed),
+            0
+        );
+
+        // Once the tracking script records the visitor, their log hits are skipped
+        let collector = build_collector(
+            Some(site.id.clone()),
+            Some(entry("/").ip),
+            UserAgentInfo::relayed(USER_AGENT, &state.user_agent_parser),
+            false,
+            CollectorSource::Tracker,
+            &state,
+            &conn,
+        )
+        .await
+        .expect("collector built");
+        let collector_id = Collector::insert(collector, &conn)
+            .await
+            .expect("collector inserted");
+        let tracked = EventQuery {
+            url: "https://example.com/docs".to_string(),
+            referrer: None,
+            name: "enter".to_string(),
+            collector_id,
+            token: None,
+            site_id: Some(site.id.clone()),
+            webdriver: false,
+            props: None,
+            engagement_ms: None,
+        };
+        Event::insert(build_event(tracked, Some(&site), &state), &conn)
+            .await
+            .expect("event inserted");
+
+        record(&entry("/blog"), &source, &mut sessions, &state, &conn)
+            .await
+            .expect("page view skipped");
+        assert_eq!(events().await, seeded + 4);
+        assert_eq!(
+            state
+                .metrics
+                .access_log_deduplicated
+                .load(Ordering::Relaxed),
+            1
+        );
+    }
+}
@@ -31,11 +31,11 @@ impl Logger {
         let log_message = format!("[{timestamp}] {level} - {module}: {message}\n");
 
         let log_dir = "logs";
-        if !Path::new(log_dir).exists() {
-            if let Err(e) = create_dir_all(log_dir) {
-                eprintln!("Failed to create log directory: {e}");
-                return;
-            }
+        if !Path::new(log_dir).exists()
+            && let Err(e) = create_dir_all(log_dir)
+        {
+            eprintln!("Failed to create log directory: {e}");
+            return;
         }
 
         let log_file = format!("{log_dir}/application.log");
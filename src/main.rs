@@ -1,26 +1,53 @@
+use std::{env, path::Path, process, sync::Arc};
+
 use rocket::{
-    Request, catch, catchers,
+    Build, Request, Rocket, State, catch, catchers,
     figment::Figment,
     fs::FileServer,
     http::Status,
-    launch, options, routes,
+    options, routes,
     serde::json::{Json, Value, json},
 };
 use website_stats::{
     AppState, DbConn, RequestLogger,
     api_response::ApiResponse,
+    cli,
     config::AppConfig,
     cors::Cors,
+    log_tailer::{AccessLogSource, LogTailer},
+    logger::Logger,
+    metrics::Metrics,
+    rate_limiter::{RateLimiter, RateLimits},
     routes::{
+        api::api_track,
         city::{city_get, city_insert},
         collector::collector_stats_js,
-        event::{event_get, event_insert},
-        session::{session_get_map_data, session_get_sessions},
+        error::{error_get, error_get_one, error_insert},
+        event::{event_get, event_insert, event_insert_batch},
+        metrics::metrics_get,
+        pixel::collector_pixel,
+        session::{session_get_map_data, session_get_sessions, session_start},
+        site::{site_get, site_get_one, site_insert, site_update},
         summary::{
-            summary_get_browsers, summary_get_events, summary_get_five_minutes, summary_get_hourly,
-            summary_get_os_browsers, summary_get_percentages, summary_get_referrers,
-            summary_get_urls, summary_get_weekly_event_counts,
+            summary_get_bots, summary_get_browsers, summary_get_campaigns, summary_get_channels,
+            summary_get_events, summary_get_five_minutes, summary_get_hourly, summary_get_mediums,
+            summary_get_os_browsers, summary_get_percentages, summary_get_properties,
+            summary_get_referrers, summary_get_session_duration, summary_get_sources,
+            summary_get_time_on_page, summary_get_urls, summary_get_vitals,
+            summary_get_weekly_event_counts,
         },
+        vital::vital_insert,
+    },
+    services::{
+        anonymization::{IpAnonymization, IpAnonymizer},
+        bot_detection::BotDetector,
+        collector_token::CollectorTokenSigner,
+        error_fingerprint::ErrorFingerprinter,
+        ip_location::IpInfoLocator,
+        location::build_locator,
+        referrer::ReferrerClassifier,
+        tracking_script::TrackingScript,
+        user_agent::UserAgentParser,
     },
 };
 
@@ -40,59 +67,197 @@ pub fn global_options_handler() -> Status {
 /// Handles GET requests to the root path ("/").
 /// Serves as a simple health check endpoint.
 ///
+/// ## Arguments
+/// * `state` - The application state
+///
 /// ## Returns
-/// A static string greeting message
+/// A greeting message and the URL of the current version of the tracking script
 #[rocket::get("/")]
-fn root() -> Json<Value> {
+fn root(state: &State<Arc<AppState>>) -> Json<Value> {
     ApiResponse::success(json!({
         "message": "Hello, visitors!",
+        "script": format!("{}/stats.js?v={}", state.address, state.tracking_script.version),
     }))
 }
 
 /// # `rocket`
-/// Configures and launches the Rocket application.
+/// Configures the Rocket application, launched by `main` or ignited by CLI commands.
 /// Sets up database connection, runs migrations, configures CORS, and mounts routes.
 ///
 /// ## Returns
 /// The configured Rocket instance
-#[launch]
-fn rocket() -> _ {
+fn rocket() -> Rocket<Build> {
     dotenv::dotenv().ok();
 
     let app_config = AppConfig::new();
     let dev_mode = app_config.dev;
     let address = app_config.address.clone();
+    let tracking_script = TrackingScript::new(&address);
+    let geo_locator = build_locator(&app_config).unwrap_or_else(|e| {
+        Logger::error("GeoIP", &e);
+        eprintln!("{e}, falling back to IPInfo");
+        Box::new(IpInfoLocator::new(app_config.ipinfo_token.clone()))
+    });
+    Logger::info(
+        "GeoIP",
+        &format!("Using the {} geolocation provider", geo_locator.name()),
+    );
+    let user_agent_parser = match &app_config.user_agent_rules {
+        Some(path) => UserAgentParser::from_file(path).unwrap_or_else(|e| {
+            Logger::error("UserAgent", &e);
+            eprintln!("{e}, falling back to the bundled user agent rules");
+            UserAgentParser::bundled()
+        }),
+        None => UserAgentParser::bundled(),
+    };
+    let referrer_classifier = match &app_config.referrer_sources {
+        Some(path) => ReferrerClassifier::from_file(path).unwrap_or_else(|e| {
+            Logger::error("Referrer", &e);
+            eprintln!("{e}, falling back to the bundled referrer sources");
+            ReferrerClassifier::bundled()
+        }),
+        None => ReferrerClassifier::bundled(),
+    };
+    let access_logs = match &app_config.access_logs {
+        Some(path) => AccessLogSource::from_file(path).unwrap_or_else(|e| {
+            Logger::error("AccessLog", &e);
+            eprintln!("{e}, access logs will not be followed");
+            Vec::new()
+        }),
+        None => Vec::new(),
+    };
+    let bot_detector = BotDetector::new(
+        app_config.bot_networks.as_ref().map(Path::new),
+        app_config.geoip_asn_database.as_deref(),
+    )
+    .unwrap_or_else(|e| {
+        Logger::error("BotDetection", &e);
+        eprintln!("{e}, falling back to the bundled bot networks");
+        BotDetector::default()
+    });
+    let ip_anonymizer = IpAnonymizer::new(
+        app_config.ip_anonymization,
+        app_config.ip_hash_key.as_deref(),
+    )
+    .unwrap_or_else(|e| {
+        Logger::error("Anonymization", &e);
+        eprintln!("{e}, falling back to truncating IP addresses");
+        IpAnonymizer::new(IpAnonymization::Truncate, None).expect("Truncating needs no key")
+    });
+    if app_config.collector_token_secret.is_none() {
+        Logger::warn(
+            "CollectorToken",
+            "COLLECTOR_TOKEN_SECRET is not set, tokens will not survive a restart",
+        );
+    }
+    let token_signer = CollectorTokenSigner::new(
+        app_config.collector_token_secret.as_deref(),
+        app_config.collector_token_ttl,
+    );
+    let rate_limiter = RateLimiter::new(RateLimits {
+        session: app_config.rate_limit_session,
+        event: app_config.rate_limit_event,
+        pixel: app_config.rate_limit_pixel,
+        vitals: app_config.rate_limit_vitals,
+        errors: app_config.rate_limit_errors,
+    });
+    let geo_cache_ttl = app_config.geo_cache_ttl;
+    let privacy_policy = app_config.privacy_signals;
     let figment: Figment = app_config.into();
-    let app_state = AppState { address, dev_mode };
+    let app_state = AppState {
+        address,
+        dev_mode,
+        geo_locator,
+        geo_cache_ttl,
+        metrics: Metrics::default(),
+        user_agent_parser,
+        bot_detector,
+        ip_anonymizer,
+        privacy_policy,
+        token_signer,
+        tracking_script,
+        error_fingerprinter: ErrorFingerprinter::new(),
+        referrer_classifier,
+    };
 
     rocket::build()
         .configure(figment)
         .attach(DbConn::fairing())
         .attach(Cors)
         .attach(RequestLogger)
-        .manage(app_state)
+        .attach(rate_limiter)
+        .attach(LogTailer::new(access_logs))
+        .manage(Arc::new(app_state))
         .register("/", catchers![default_catcher])
         .mount("/", routes![root, global_options_handler])
+        .mount("/api", routes![api_track])
         .mount("/city", routes![city_insert, city_get])
-        .mount("/event", routes![event_insert, event_get])
+        .mount("/errors", routes![error_insert, error_get, error_get_one])
+        .mount(
+            "/event",
+            routes![event_insert, event_insert_batch, event_get],
+        )
+        .mount("/metrics", routes![metrics_get])
+        .mount("/pixel.gif", routes![collector_pixel])
+        .mount(
+            "/site",
+            routes![site_insert, site_get, site_get_one, site_update],
+        )
         .mount(
             "/session",
-            routes![session_get_sessions, session_get_map_data],
+            routes![session_start, session_get_sessions, session_get_map_data],
         )
         .mount(
             "/summary",
             routes![
+                summary_get_bots,
                 summary_get_browsers,
+                summary_get_campaigns,
+                summary_get_channels,
                 summary_get_events,
                 summary_get_five_minutes,
                 summary_get_hourly,
+                summary_get_mediums,
                 summary_get_os_browsers,
                 summary_get_percentages,
+                summary_get_properties,
                 summary_get_referrers,
+                summary_get_session_duration,
+                summary_get_sources,
+                summary_get_time_on_page,
                 summary_get_urls,
+                summary_get_vitals,
                 summary_get_weekly_event_counts
             ],
         )
         .mount("/stats.js", routes![collector_stats_js])
+        .mount("/vitals", routes![vital_insert])
         .mount("/ui", FileServer::from("ui"))
 }
+
+/// # `main`
+/// Starts the server, or runs the command given on the command line.
+#[rocket::main]
+async fn main() {
+    let args: Vec<String> = env::args().collect();
+
+    let result = match args.get(1).map(String::as_str) {
+        None | Some("serve") => rocket()
+            .launch()
+            .await
+            .map(|_| ())
+            .map_err(|e| e.to_string()),
+        Some("anonymize-origins") => cli::anonymize_origins(rocket()).await,
+        Some("create-api-key") => {
+            cli::create_api_key(rocket(), args.get(2).cloned(), args.get(3).cloned()).await
+        }
+        Some("revoke-api-key") => cli::revoke_api_key(rocket(), args.get(2).cloned()).await,
+        Some("import-logs") => cli::import_logs(rocket(), &args[2..]).await,
+        Some(other) => Err(format!("Unknown command: {other}\n\n{}", cli::USAGE)),
+    };
+
+    if let Err(e) = result {
+        eprintln!("{e}");
+        process::exit(1);
+    }
+}
@@ -0,0 +1,93 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use serde_json::{Value, json};
+
+use crate::services::privacy::{PrivacyPolicy, PrivacySignal};
+
+/// # `Metrics`
+/// In-memory operational counters, reset when the server restarts.
+#[derive(Debug, Default)]
+pub struct Metrics {
+    pub geo_cache_hits: AtomicU64,
+    pub geo_cache_misses: AtomicU64,
+    pub geo_cache_stale_hits: AtomicU64,
+    pub do_not_track_skipped: AtomicU64,
+    pub do_not_track_aggregated: AtomicU64,
+    pub global_privacy_control_skipped: AtomicU64,
+    pub global_privacy_control_aggregated: AtomicU64,
+    pub rate_limited_ip: AtomicU64,
+    pub rate_limited_collector: AtomicU64,
+    pub access_log_page_views: AtomicU64,
+    pub access_log_deduplicated: AtomicU64,
+}
+
+impl Metrics {
+    /// # `increment`
+    /// Adds one to a counter.
+    ///
+    /// ## Arguments
+    /// * `counter` - The counter to increment
+    pub fn increment(counter: &AtomicU64) {
+        counter.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// # `privacy`
+    /// Returns the counter of requests whose tracking was limited by a privacy signal.
+    ///
+    /// ## Arguments
+    /// * `signal` - The signal sent by the visitor
+    /// * `policy` - The policy applied to it
+    ///
+    /// ## Returns
+    /// * `Option<&AtomicU64>` - The counter, `None` when the signal is ignored
+    #[must_use]
+    pub fn privacy(&self, signal: PrivacySignal, policy: PrivacyPolicy) -> Option<&AtomicU64> {
+        match (signal, policy) {
+            (_, PrivacyPolicy::Ignore) => None,
+            (PrivacySignal::DoNotTrack, PrivacyPolicy::Skip) => Some(&self.do_not_track_skipped),
+            (PrivacySignal::DoNotTrack, PrivacyPolicy::Aggregate) => {
+                Some(&self.do_not_track_aggregated)
+            }
+            (PrivacySignal::GlobalPrivacyControl, PrivacyPolicy::Skip) => {
+                Some(&self.global_privacy_control_skipped)
+            }
+            (PrivacySignal::GlobalPrivacyControl, PrivacyPolicy::Aggregate) => {
+                Some(&self.global_privacy_control_aggregated)
+            }
+        }
+    }
+
+    /// # `snapshot`
+    /// Returns the current value of every counter.
+    ///
+    /// ## Returns
+    /// * `Value` - The counters as JSON
+    #[must_use]
+    pub fn snapshot(&self) -> Value {
+        json!({
+            "geo_cache": {
+                "hits": self.geo_cache_hits.load(Ordering::Relaxed),
+                "misses": self.geo_cache_misses.load(Ordering::Relaxed),
+                "stale_hits": self.geo_cache_stale_hits.load(Ordering::Relaxed),
+            },
+            "privacy": {
+                "do_not_track": {
+                    "skipped": self.do_not_track_skipped.load(Ordering::Relaxed),
+                    "aggregated": self.do_not_track_aggregated.load(Ordering::Relaxed),
+                },
+                "global_privacy_control": {
+                    "skipped": self.global_privacy_control_skipped.load(Ordering::Relaxed),
+                    "aggregated": self.global_privacy_control_aggregated.load(Ordering::Relaxed),
+                },
+            },
+            "rate_limited": {
+                "ip": self.rate_limited_ip.load(Ordering::Relaxed),
+                "collector": self.rate_limited_collector.load(Ordering::Relaxed),
+            },
+            "access_log": {
+                "page_views": self.access_log_page_views.load(Ordering::Relaxed),
+                "deduplicated": self.access_log_deduplicated.load(Ordering::Relaxed),
+            },
+        })
+    }
+}
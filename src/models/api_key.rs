@@ -0,0 +1,243 @@
+use chrono::{NaiveDateTime, Utc};
+use diesel::{
+    ExpressionMethods, OptionalExtension, QueryDsl, QueryResult, RunQueryDsl,
+    prelude::{Insertable, Queryable},
+};
+use rand::{RngCore, rngs::OsRng};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use ulid::Ulid;
+
+use crate::{DbConn, schema::api_key, services::hex};
+
+/// Prefix of every API key, to recognize them in configuration files and leaks
+const API_KEY_PREFIX: &str = "wsk_";
+/// Random bytes in an API key
+const API_KEY_LENGTH: usize = 24;
+
+/// # `ApiKey`
+/// Key letting a backend record events for a site through `POST /api/track`.
+///
+/// Only the SHA-256 hash of the key is stored: the key itself is shown once, when created.
+#[derive(Queryable, Insertable, Serialize, Deserialize, Debug, Clone)]
+#[diesel(table_name = api_key)]
+#[serde(crate = "rocket::serde")]
+pub struct ApiKey {
+    pub id: String,
+    pub site_id: String,
+    pub name: String,
+    #[serde(skip_serializing)]
+    pub key_hash: String,
+    pub created_at: Option<NaiveDateTime>,
+    pub last_used_at: Option<NaiveDateTime>,
+}
+
+impl ApiKey {
+    /// # `generate`
+    /// Generates a new random key for a site.
+    ///
+    /// ## Arguments
+    /// * `site_id` - The site the key records events for
+    /// * `name` - What the key is used for, e.g. `billing backend`
+    ///
+    /// ## Returns
+    /// * `(ApiKey, String)` - The key to store, and the secret to hand out
+    #[must_use]
+    pub fn generate(site_id: String, name: String) -> (Self, String) {
+        let mut bytes = [0u8; API_KEY_LENGTH];
+        OsRng.fill_bytes(&mut bytes);
+        let secret = format!("{API_KEY_PREFIX}{}", hex::encode(&bytes));
+
+        let key = ApiKey {
+            id: Ulid::new().to_string(),
+            site_id,
+            name,
+            key_hash: Self::hash(&secret),
+            created_at: None,
+            last_used_at: None,
+        };
+
+        (key, secret)
+    }
+
+    /// Hash under which a key is stored
+    fn hash(secret: &str) -> String {
+        hex::encode(&Sha256::digest(secret.trim().as_bytes()))
+    }
+
+    /// Whether a secret looks like a generated key, checked before querying the database
+    fn is_well_formed(secret: &str) -> bool {
+        secret
+            .trim()
+            .strip_prefix(API_KEY_PREFIX)
+            .is_some_and(|key| {
+                key.len() == API_KEY_LENGTH * 2 && key.chars().all(|c| c.is_ascii_hexdigit())
+            })
+    }
+
+    /// # `insert`
+    /// Inserts a new `ApiKey` into the database.
+    ///
+    /// ## Arguments
+    /// * `key` - The key to insert
+    /// * `conn` - Database connection
+    ///
+    /// ## Errors
+    /// If the key cannot be inserted (e.g. unknown site).
+    ///
+    /// ## Returns
+    /// * `QueryResult<String>` - Id of the inserted `ApiKey`
+    pub async fn insert(key: ApiKey, conn: &DbConn) -> QueryResult<String> {
+        conn.run(|c| {
+            diesel::insert_into(api_key::table)
+                .values(&key)
+                .execute(c)?;
+
+            Ok(key.id)
+        })
+        .await
+    }
+
+    /// # `authenticate`
+    /// Finds the key matching a secret, and records that it was used.
+    ///
+    /// ## Arguments
+    /// * `secret` - The key sent by the client
+    /// * `conn` - Database connection
+    ///
+    /// ## Errors
+    /// If the query fails.
+    ///
+    /// ## Returns
+    /// * `QueryResult<Option<ApiKey>>` - The key, or None if no key matches
+    pub async fn authenticate(secret: String, conn: &DbConn) -> QueryResult<Option<ApiKey>> {
+        if !Self::is_well_formed(&secret) {
+            return Ok(None);
+        }
+        let key_hash = Self::hash(&secret);
+
+        conn.run(move |c| {
+            let key: Option<ApiKey> = api_key::table
+                .filter(api_key::key_hash.eq(&key_hash))
+                .first(c)
+                .optional()?;
+
+            if let Some(key) = &key {
+                diesel::update(api_key::table.find(&key.id))
+                    .set(api_key::last_used_at.eq(Utc::now().naive_utc()))
+                    .execute(c)?;
+            }
+
+            Ok(key)
+        })
+        .await
+    }
+
+    /// # `revoke`
+    /// Deletes a key, refusing every request made with it from then on.
+    ///
+    /// ## Arguments
+    /// * `id` - The ID of the key
+    /// * `conn` - Database connection
+    ///
+    /// ## Errors
+    /// If the query fails.
+    ///
+    /// ## Returns
+    /// * `QueryResult<bool>` - Whether the key existed
+    pub async fn revoke(id: String, conn: &DbConn) -> QueryResult<bool> {
+        conn.run(move |c| {
+            let deleted = diesel::delete(api_key::table.find(id)).execute(c)?;
+
+            Ok(deleted > 0)
+        })
+        .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        models::{Site, SiteQuery},
+        test_support,
+    };
+
+    #[test]
+    fn hashes_generated_keys() {
+        let (key, secret) = ApiKey::generate("site".to_string(), "backend".to_string());
+        let (other, other_secret) = ApiKey::generate("site".to_string(), "backend".to_string());
+
+        assert!(secret.starts_with(API_KEY_PREFIX));
+        assert!(ApiKey::is_well_formed(&secret));
+        assert_ne!(secret, other_secret);
+        // Only the hash is stored, and it does not depend on surrounding whitespace
+        assert!(!key.key_hash.contains(&secret[API_KEY_PREFIX.len()..]));
+        assert_eq!(key.key_hash, ApiKey::hash(&secret));
+        assert_eq!(key.key_hash, ApiKey::hash(&format!(" {secret}\n")));
+        assert_ne!(key.key_hash, other.key_hash);
+        assert_eq!(key.key_hash.len(), 64);
+    }
+
+    #[test]
+    fn rejects_malformed_keys() {
+        let (_, secret) = ApiKey::generate("site".to_string(), "backend".to_string());
+        let cases = [
+            String::new(),
+            API_KEY_PREFIX.to_string(),
+            secret[API_KEY_PREFIX.len()..].to_string(),
+            format!("xyz_{}", &secret[API_KEY_PREFIX.len()..]),
+            secret[..secret.len() - 1].to_string(),
+            format!("{secret}0"),
+            format!("{}g", &secret[..secret.len() - 1]),
+        ];
+
+        for secret in cases {
+            assert!(!ApiKey::is_well_formed(&secret), "{secret}");
+        }
+    }
+
+    #[rocket::async_test]
+    async fn authenticates_keys() {
+        let rocket = test_support::rocket().ignite().await.expect("valid rocket");
+        let conn = DbConn::get_one(&rocket).await.expect("database connection");
+        let site: Site = SiteQuery {
+            domain: "example.com".to_string(),
+            name: "Example".to_string(),
+            timezone: None,
+            settings: None,
+        }
+        .into();
+        let site_id = Site::insert(site, &conn).await.expect("site inserted");
+
+        let (key, secret) = ApiKey::generate(site_id.clone(), "backend".to_string());
+        let id = ApiKey::insert(key, &conn).await.expect("key inserted");
+
+        let found = ApiKey::authenticate(secret.clone(), &conn)
+            .await
+            .expect("query succeeds")
+            .expect("valid key");
+        assert_eq!(found.id, id);
+        assert_eq!(found.site_id, site_id);
+        let used = ApiKey::authenticate(secret.clone(), &conn)
+            .await
+            .expect("query succeeds")
+            .expect("valid key");
+        assert!(used.last_used_at.is_some());
+
+        let (_, wrong_secret) = ApiKey::generate(site_id, "other".to_string());
+        for secret in [wrong_secret, "wsk_malformed".to_string(), String::new()] {
+            let key = ApiKey::authenticate(secret.clone(), &conn).await;
+            assert!(key.expect("query succeeds").is_none(), "{secret}");
+        }
+
+        assert!(
+            ApiKey::revoke(id.clone(), &conn)
+                .await
+                .expect("key revoked")
+        );
+        assert!(!ApiKey::revoke(id, &conn).await.expect("query succeeds"));
+        let revoked = ApiKey::authenticate(secret, &conn).await;
+        assert!(revoked.expect("query succeeds").is_none());
+    }
+}
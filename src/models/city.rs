@@ -1,5 +1,3 @@
-use std::net::IpAddr;
-
 use crate::{paginated::Paginate, sql_functions::lower};
 use chrono::NaiveDateTime;
 use diesel::{
@@ -10,10 +8,9 @@ use diesel::{
     select,
     sql_types::Integer,
 };
-use reqwest;
 use serde::{Deserialize, Serialize};
 
-use crate::services::ip_location;
+use crate::services::location::Location;
 use crate::{DbConn, paginated::PaginationResult, schema::city};
 
 #[derive(Deserialize, Identifiable, Insertable, Queryable, Serialize, Debug, Default)]
@@ -26,6 +23,7 @@ pub struct City {
     pub latitude: Option<f32>,
     pub longitude: Option<f32>,
     pub created_at: Option<NaiveDateTime>,
+    pub timezone: Option<String>,
 }
 
 #[derive(Deserialize)]
@@ -44,6 +42,21 @@ impl From<CityQuery> for City {
             latitude: None,
             longitude: None,
             created_at: None,
+            timezone: None,
+        }
+    }
+}
+
+impl From<Location> for City {
+    fn from(location: Location) -> Self {
+        City {
+            id: None,
+            name: location.city,
+            country: location.country,
+            latitude: location.latitude,
+            longitude: location.longitude,
+            created_at: None,
+            timezone: location.timezone,
         }
     }
 }
@@ -80,9 +93,11 @@ impl City {
     /// # `insert`
     /// Inserts a new city into the database and returns its ID.
     ///
+    /// Coordinates and timezone are stored as given: callers resolve them beforehand,
+    /// through the geolocation cache.
+    ///
     /// ## Arguments
     /// * `city` - The city to insert.
-    /// * `ip` - The IP address of the client.
     /// * `conn` - The database connection.
     ///
     /// ## Errors
@@ -90,18 +105,7 @@ impl City {
     ///
     /// ## Returns
     /// * `QueryResult<i32>` - The ID of the inserted city.
-    pub async fn insert(mut city: City, ip: IpAddr, conn: &DbConn) -> QueryResult<i32> {
-        // Try to get location data from IP
-        if let Ok(ip_city) = Self::from_ip(&ip.to_string()).await {
-            // Update coordinates if they're not already set
-            if city.latitude.is_none() {
-                city.latitude = ip_city.latitude;
-            }
-            if city.longitude.is_none() {
-                city.longitude = ip_city.longitude;
-            }
-        }
-
+    pub async fn insert(city: City, conn: &DbConn) -> QueryResult<i32> {
         conn.run(move |c| {
             insert_into(city::table).values(&city).execute(c)?;
 
@@ -144,28 +148,32 @@ impl City {
         .await
     }
 
-    /// # `from_ip`
-    /// Creates a new City instance from an IP address by looking up its location
+    /// # `unknown`
+    /// Returns the ID of the city of visitors without a known location, creating it if needed.
     ///
     /// ## Arguments
-    /// * `ip` - The IP address to look up
+    /// * `conn` - The database connection.
     ///
     /// ## Errors
-    /// * If the IP location lookup fails.
+    /// If the city cannot be found or inserted.
     ///
     /// ## Returns
-    /// * `Result<City, reqwest::Error>` - The city info or an error
-    pub async fn from_ip(ip: &str) -> Result<Self, reqwest::Error> {
-        let info = ip_location::get_city_info(ip).await?;
-        let (latitude, longitude) = info.coordinates().unwrap_or((0.0, 0.0));
+    /// * `QueryResult<i32>` - The ID of the unknown city.
+    pub async fn unknown(conn: &DbConn) -> QueryResult<i32> {
+        if let Some(city) =
+            Self::find_by_name_and_country(String::new(), String::new(), conn).await?
+            && let Some(id) = city.id
+        {
+            return Ok(id);
+        }
 
-        Ok(City {
-            id: None,
-            name: info.city,
-            country: info.country,
-            latitude: Some(latitude),
-            longitude: Some(longitude),
-            created_at: None,
+        conn.run(move |c| {
+            insert_into(city::table)
+                .values(&City::default())
+                .execute(c)?;
+
+            select(sql::<Integer>("last_insert_rowid()")).first(c)
         })
+        .await
     }
 }
@@ -1,12 +1,14 @@
+use std::fmt::Display;
+
 use chrono::NaiveDateTime;
 use diesel::{
-    QueryResult, RunQueryDsl,
+    Connection, ExpressionMethods, OptionalExtension, QueryDsl, QueryResult, RunQueryDsl,
     prelude::{Identifiable, Insertable, Queryable},
 };
 use serde::{Deserialize, Serialize};
 use ulid::Ulid;
 
-use crate::{DbConn, schema::collector};
+use crate::{DbConn, schema::collector, services::anonymization::IpAnonymizer};
 
 use super::Event;
 
@@ -20,6 +22,46 @@ pub struct Collector {
     pub os: Option<String>,
     pub browser: Option<String>,
     pub created_at: Option<NaiveDateTime>,
+    pub site_id: Option<String>,
+    pub os_version: Option<String>,
+    pub browser_version: Option<String>,
+    pub device_type: Option<String>,
+    pub engine: Option<String>,
+    pub is_bot: bool,
+    pub bot_reason: Option<String>,
+    /// Anonymous visitor ID, stable for a day, see `VisitorSalt::visitor_id`
+    pub visitor_id: Option<String>,
+    /// What started the session, see `CollectorSource`
+    pub source: String,
+}
+
+/// # `CollectorSource`
+/// What started the session of a collector.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CollectorSource {
+    /// The tracking script, through `POST /session`, or its `<noscript>` pixel
+    Tracker,
+    /// A backend, through `POST /api/track`
+    Api,
+    /// An access log, followed or imported
+    Log,
+}
+
+impl CollectorSource {
+    #[must_use]
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            CollectorSource::Tracker => "tracker",
+            CollectorSource::Api => "api",
+            CollectorSource::Log => "log",
+        }
+    }
+}
+
+impl Display for CollectorSource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
 }
 
 #[derive(Serialize, Deserialize)]
@@ -34,6 +76,13 @@ pub struct CollectorQuery {
     pub city_id: i32,
     pub os: Option<String>,
     pub browser: Option<String>,
+    pub site_id: Option<String>,
+    pub os_version: Option<String>,
+    pub browser_version: Option<String>,
+    pub device_type: Option<String>,
+    pub engine: Option<String>,
+    pub bot_reason: Option<String>,
+    pub visitor_id: Option<String>,
 }
 
 impl From<CollectorQuery> for Collector {
@@ -45,6 +94,15 @@ impl From<CollectorQuery> for Collector {
             os: query.os,
             browser: query.browser,
             created_at: None,
+            site_id: query.site_id,
+            os_version: query.os_version,
+            browser_version: query.browser_version,
+            device_type: query.device_type,
+            engine: query.engine,
+            is_bot: query.bot_reason.is_some(),
+            bot_reason: query.bot_reason,
+            visitor_id: query.visitor_id,
+            source: CollectorSource::Tracker.to_string(),
         }
     }
 }
@@ -72,4 +130,115 @@ impl Collector {
         })
         .await
     }
+
+    /// # `find`
+    /// Finds a `Collector` by its ID.
+    ///
+    /// ## Arguments
+    /// * `id` - Id of the `Collector`
+    /// * `conn` - Database connection
+    ///
+    /// ## Errors
+    /// If the query fails.
+    ///
+    /// ## Returns
+    /// * `QueryResult<Option<Collector>>` - The found `Collector`, or None if not found
+    pub async fn find(id: String, conn: &DbConn) -> QueryResult<Option<Collector>> {
+        conn.run(move |c| collector::table.find(id).first(c).optional())
+            .await
+    }
+
+    /// # `latest_of_visitor`
+    /// Finds the most recent `Collector` of a visitor on a site, if created since a given time.
+    ///
+    /// ## Arguments
+    /// * `site_id` - The site visited
+    /// * `visitor_id` - The anonymous visitor ID
+    /// * `since` - Oldest creation time accepted
+    /// * `conn` - Database connection
+    ///
+    /// ## Errors
+    /// If the query fails.
+    ///
+    /// ## Returns
+    /// * `QueryResult<Option<Collector>>` - The collector, or None if the visitor has no recent one
+    pub async fn latest_of_visitor(
+        site_id: String,
+        visitor_id: String,
+        since: NaiveDateTime,
+        conn: &DbConn,
+    ) -> QueryResult<Option<Collector>> {
+        conn.run(move |c| {
+            collector::table
+                .filter(collector::site_id.eq(site_id))
+                .filter(collector::visitor_id.eq(visitor_id))
+                .filter(collector::created_at.gt(since))
+                .order(collector::created_at.desc())
+                .first(c)
+                .optional()
+        })
+        .await
+    }
+
+    /// # `flag_bot`
+    /// Flags a `Collector` as a bot, keeping the first reason it was flagged for.
+    ///
+    /// ## Arguments
+    /// * `id` - Id of the `Collector`
+    /// * `reason` - Why the `Collector` is a bot
+    /// * `conn` - Database connection
+    ///
+    /// ## Errors
+    /// If the `Collector` cannot be updated.
+    ///
+    /// ## Returns
+    /// * `QueryResult<usize>` - Number of updated rows
+    pub async fn flag_bot(id: String, reason: String, conn: &DbConn) -> QueryResult<usize> {
+        conn.run(move |c| {
+            diesel::update(
+                collector::table
+                    .find(id)
+                    .filter(collector::is_bot.eq(false)),
+            )
+            .set((collector::is_bot.eq(true), collector::bot_reason.eq(reason)))
+            .execute(c)
+        })
+        .await
+    }
+
+    /// # `anonymize_origins`
+    /// Rewrites the origin of every `Collector` with the given anonymizer, in one transaction.
+    ///
+    /// ## Arguments
+    /// * `anonymizer` - The anonymizer to apply
+    /// * `conn` - Database connection
+    ///
+    /// ## Errors
+    /// If the origins cannot be read or updated.
+    ///
+    /// ## Returns
+    /// * `QueryResult<usize>` - Number of rewritten origins
+    pub async fn anonymize_origins(anonymizer: IpAnonymizer, conn: &DbConn) -> QueryResult<usize> {
+        conn.run(move |c| {
+            c.transaction(|c| {
+                let origins: Vec<(String, String)> = collector::table
+                    .select((collector::id, collector::origin))
+                    .load(c)?;
+                let mut rewritten = 0;
+
+                for (id, origin) in origins {
+                    let anonymized = anonymizer.anonymize_stored(&origin);
+                    if anonymized != origin {
+                        diesel::update(collector::table.find(id))
+                            .set(collector::origin.eq(anonymized))
+                            .execute(c)?;
+                        rewritten += 1;
+                    }
+                }
+
+                Ok(rewritten)
+            })
+        })
+        .await
+    }
 }
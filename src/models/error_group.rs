@@ -0,0 +1,380 @@
+use chrono::{NaiveDateTime, Utc};
+use diesel::{
+    Connection, ExpressionMethods, OptionalExtension, QueryDsl, QueryResult, RunQueryDsl,
+    prelude::{Insertable, Queryable, QueryableByName},
+    sql_query,
+    sql_types::{BigInt, Bool, Nullable, Text, Timestamp},
+};
+use serde::{Deserialize, Serialize};
+
+use crate::paginated::{Paginate, PaginationResult};
+use crate::{
+    DbConn,
+    models::StatsFilter,
+    schema::{collector, error_group, error_occurrence},
+    services::error_fingerprint::ErrorFingerprint,
+};
+
+/// Longest error message stored
+const MAX_ERROR_MESSAGE_LENGTH: usize = 1024;
+/// Longest script URL stored
+const MAX_ERROR_SOURCE_LENGTH: usize = 2048;
+/// Longest stack trace stored
+const MAX_ERROR_STACK_LENGTH: usize = 8192;
+/// Number of sessions and occurrences returned with an error group
+const ERROR_DETAIL_LIMIT: i64 = 50;
+
+/// Cuts a string to at most `max` characters.
+fn truncate(text: &str, max: usize) -> String {
+    text.chars().take(max).collect()
+}
+
+/// # `ErrorGroup`
+/// JavaScript errors sharing the same fingerprint, see `ErrorFingerprinter`.
+#[derive(Queryable, Insertable, Serialize, Deserialize, Debug)]
+#[diesel(table_name = error_group)]
+#[serde(crate = "rocket::serde")]
+pub struct ErrorGroup {
+    pub id: String,
+    pub site_id: Option<String>,
+    /// Normalized message, shared by the whole group
+    pub message: String,
+    /// Normalized URL of the script that threw, if known
+    pub source: Option<String>,
+    pub count: i32,
+    pub first_seen: NaiveDateTime,
+    pub last_seen: NaiveDateTime,
+}
+
+/// # `ErrorOccurrence`
+/// A single JavaScript error, as reported by a collector.
+#[derive(Queryable, Insertable, Serialize, Deserialize, Debug)]
+#[diesel(table_name = error_occurrence)]
+#[serde(crate = "rocket::serde")]
+pub struct ErrorOccurrence {
+    pub id: String,
+    pub error_group_id: String,
+    pub collector_id: String,
+    pub url: String,
+    pub message: String,
+    pub source: Option<String>,
+    pub line_number: Option<i32>,
+    pub column_number: Option<i32>,
+    pub stack: Option<String>,
+    pub created_at: Option<NaiveDateTime>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct ErrorReport {
+    pub url: String,
+    pub collector_id: String,
+    /// Signed collector token handed out by `POST /session`
+    #[serde(default)]
+    pub token: Option<String>,
+    #[serde(default)]
+    pub site_id: Option<String>,
+    pub message: String,
+    #[serde(default)]
+    pub source: Option<String>,
+    #[serde(default)]
+    pub line: Option<i32>,
+    #[serde(default)]
+    pub column: Option<i32>,
+    #[serde(default)]
+    pub stack: Option<String>,
+}
+
+impl ErrorReport {
+    /// # `into_occurrence`
+    /// Turns a report into an occurrence of a group, trimming oversized fields.
+    ///
+    /// ## Arguments
+    /// * `id` - The ID of the occurrence
+    /// * `error_group_id` - The group the error belongs to
+    /// * `url` - The cleaned URL of the page the error happened on
+    ///
+    /// ## Returns
+    /// * `ErrorOccurrence` - The occurrence to store
+    #[must_use]
+    pub fn into_occurrence(
+        self,
+        id: String,
+        error_group_id: String,
+        url: String,
+    ) -> ErrorOccurrence {
+        ErrorOccurrence {
+            id,
+            error_group_id,
+            collector_id: self.collector_id,
+            url,
+            message: truncate(&self.message, MAX_ERROR_MESSAGE_LENGTH),
+            source: self
+                .source
+                .map(|source| truncate(&source, MAX_ERROR_SOURCE_LENGTH)),
+            line_number: self.line.filter(|line| *line >= 0),
+            column_number: self.column.filter(|column| *column >= 0),
+            stack: self
+                .stack
+                .map(|stack| truncate(&stack, MAX_ERROR_STACK_LENGTH)),
+            created_at: None,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, QueryableByName)]
+pub struct ErrorUrlCount {
+    #[diesel(sql_type = Text)]
+    pub url: String,
+    #[diesel(sql_type = BigInt)]
+    pub count: i64,
+    #[diesel(sql_type = BigInt)]
+    pub sessions: i64,
+}
+
+#[derive(Serialize, Deserialize, QueryableByName)]
+pub struct ErrorSession {
+    #[diesel(sql_type = Text)]
+    pub collector_id: String,
+    #[diesel(sql_type = Nullable<Text>)]
+    pub browser: Option<String>,
+    #[diesel(sql_type = Nullable<Text>)]
+    pub os: Option<String>,
+    #[diesel(sql_type = Nullable<Text>)]
+    pub device_type: Option<String>,
+    #[diesel(sql_type = Bool)]
+    pub is_bot: bool,
+    #[diesel(sql_type = BigInt)]
+    pub count: i64,
+    #[diesel(sql_type = Timestamp)]
+    pub first_seen: NaiveDateTime,
+    #[diesel(sql_type = Timestamp)]
+    pub last_seen: NaiveDateTime,
+}
+
+impl ErrorGroup {
+    /// # `new`
+    /// Builds the group of an error seen for the first time, now.
+    ///
+    /// ## Arguments
+    /// * `fingerprint` - The fingerprint of the error
+    /// * `site_id` - The site the error happened on
+    ///
+    /// ## Returns
+    /// * `ErrorGroup` - The group, with a count of 1
+    #[must_use]
+    pub fn new(fingerprint: ErrorFingerprint, site_id: Option<String>) -> Self {
+        let now = Utc::now().naive_utc();
+
+        ErrorGroup {
+            id: fingerprint.id,
+            site_id,
+            message: truncate(&fingerprint.message, MAX_ERROR_MESSAGE_LENGTH),
+            source: fingerprint
+                .source
+                .map(|source| truncate(&source, MAX_ERROR_SOURCE_LENGTH)),
+            count: 1,
+            first_seen: now,
+            last_seen: now,
+        }
+    }
+
+    /// # `record`
+    /// Stores an error occurrence, creating its group or updating its count and last-seen date.
+    ///
+    /// ## Arguments
+    /// * `group` - The group of the error, with a count of 1
+    /// * `occurrence` - The error itself
+    /// * `conn` - Database connection
+    ///
+    /// ## Errors
+    /// If the group or the occurrence cannot be written, in which case neither is.
+    pub async fn record(
+        group: ErrorGroup,
+        occurrence: ErrorOccurrence,
+        conn: &DbConn,
+    ) -> QueryResult<()> {
+        conn.run(move |c| {
+            c.transaction(|c| {
+                diesel::insert_into(error_group::table)
+                    .values(&group)
+                    .on_conflict(error_group::id)
+                    .do_update()
+                    .set((
+                        error_group::count.eq(error_group::count + 1),
+                        error_group::last_seen.eq(group.last_seen),
+                    ))
+                    .execute(c)?;
+                diesel::insert_into(error_occurrence::table)
+                    .values(&occurrence)
+                    .execute(c)?;
+
+                Ok(())
+            })
+        })
+        .await
+    }
+
+    /// # `find`
+    /// Finds an error group by its fingerprint.
+    ///
+    /// ## Arguments
+    /// * `id` - The fingerprint of the group
+    /// * `conn` - Database connection
+    ///
+    /// ## Errors
+    /// If the query fails.
+    ///
+    /// ## Returns
+    /// * `QueryResult<Option<ErrorGroup>>` - The group, if any
+    pub async fn find(id: String, conn: &DbConn) -> QueryResult<Option<ErrorGroup>> {
+        conn.run(move |c| error_group::table.find(id).first(c).optional())
+            .await
+    }
+
+    /// # `all`
+    /// Retrieves error groups seen within a period, most recently seen first.
+    ///
+    /// ## Arguments
+    /// * `page` - The page number
+    /// * `per_page` - The number of items per page
+    /// * `start_time` - Start of the period
+    /// * `end_time` - End of the period, excluded
+    /// * `filter` - Scope of the errors; unless bots are included, only groups with at least
+    ///   one occurrence from a visitor not flagged as a bot are listed
+    /// * `conn` - Database connection
+    ///
+    /// ## Errors
+    /// If the groups cannot be retrieved
+    pub async fn all(
+        page: i64,
+        per_page: i64,
+        start_time: NaiveDateTime,
+        end_time: NaiveDateTime,
+        filter: StatsFilter,
+        conn: &DbConn,
+    ) -> QueryResult<PaginationResult<ErrorGroup>> {
+        conn.run(move |c| {
+            let mut query = error_group::table
+                .filter(error_group::last_seen.ge(start_time))
+                .filter(error_group::first_seen.lt(end_time))
+                .into_boxed();
+
+            if let Some(site) = filter.site.clone() {
+                query = query.filter(error_group::site_id.eq(site));
+            }
+            if !filter.include_bots() {
+                query = query.filter(
+                    error_group::id.eq_any(
+                        error_occurrence::table
+                            .inner_join(collector::table)
+                            .filter(collector::is_bot.eq(false))
+                            .select(error_occurrence::error_group_id),
+                    ),
+                );
+            }
+
+            query
+                .order(error_group::last_seen.desc())
+                .paginate(page)
+                .per_page(per_page)
+                .load_and_count_pages(c)
+        })
+        .await
+    }
+
+    /// # `urls`
+    /// Retrieves the pages an error group happened on, most affected first.
+    ///
+    /// ## Arguments
+    /// * `id` - The fingerprint of the group
+    /// * `conn` - Database connection
+    ///
+    /// ## Errors
+    /// If the query fails.
+    ///
+    /// ## Returns
+    /// * `QueryResult<Vec<ErrorUrlCount>>` - The occurrences and sessions of each URL
+    pub async fn urls(id: String, conn: &DbConn) -> QueryResult<Vec<ErrorUrlCount>> {
+        let sql = "
+            SELECT url,
+                   COUNT(*) AS count,
+                   COUNT(DISTINCT collector_id) AS sessions
+            FROM error_occurrence
+            WHERE error_group_id = ?1
+            GROUP BY url
+            ORDER BY count DESC
+            LIMIT ?2;
+        ";
+
+        conn.run(move |c| {
+            sql_query(sql)
+                .bind::<Text, _>(id)
+                .bind::<BigInt, _>(ERROR_DETAIL_LIMIT)
+                .load::<ErrorUrlCount>(c)
+        })
+        .await
+    }
+
+    /// # `sessions`
+    /// Retrieves the collectors an error group happened to, most recent first.
+    ///
+    /// ## Arguments
+    /// * `id` - The fingerprint of the group
+    /// * `conn` - Database connection
+    ///
+    /// ## Errors
+    /// If the query fails.
+    ///
+    /// ## Returns
+    /// * `QueryResult<Vec<ErrorSession>>` - The affected sessions, with their browser and
+    ///   how many times they hit the error
+    pub async fn sessions(id: String, conn: &DbConn) -> QueryResult<Vec<ErrorSession>> {
+        let sql = "
+            SELECT collector.id AS collector_id,
+                   collector.browser,
+                   collector.os,
+                   collector.device_type,
+                   collector.is_bot,
+                   COUNT(*) AS count,
+                   MIN(error_occurrence.created_at) AS first_seen,
+                   MAX(error_occurrence.created_at) AS last_seen
+            FROM error_occurrence
+            JOIN collector ON collector.id = error_occurrence.collector_id
+            WHERE error_occurrence.error_group_id = ?1
+            GROUP BY collector.id
+            ORDER BY last_seen DESC
+            LIMIT ?2;
+        ";
+
+        conn.run(move |c| {
+            sql_query(sql)
+                .bind::<Text, _>(id)
+                .bind::<BigInt, _>(ERROR_DETAIL_LIMIT)
+                .load::<ErrorSession>(c)
+        })
+        .await
+    }
+
+    /// # `occurrences`
+    /// Retrieves the latest occurrences of an error group, with their stack traces.
+    ///
+    /// ## Arguments
+    /// * `id` - The fingerprint of the group
+    /// * `conn` - Database connection
+    ///
+    /// ## Errors
+    /// If the query fails.
+    ///
+    /// ## Returns
+    /// * `QueryResult<Vec<ErrorOccurrence>>` - The occurrences, most recent first
+    pub async fn occurrences(id: String, conn: &DbConn) -> QueryResult<Vec<ErrorOccurrence>> {
+        conn.run(move |c| {
+            error_occurrence::table
+                .filter(error_occurrence::error_group_id.eq(id))
+                .order(error_occurrence::created_at.desc())
+                .limit(ERROR_DETAIL_LIMIT)
+                .load(c)
+        })
+        .await
+    }
+}
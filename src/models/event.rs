@@ -1,13 +1,18 @@
 use chrono::NaiveDateTime;
 use diesel::{
-    QueryDsl, QueryResult, RunQueryDsl,
+    Connection, ExpressionMethods, QueryDsl, QueryResult, RunQueryDsl,
     prelude::{Associations, Identifiable, Insertable, Queryable},
 };
 use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
 use ulid::Ulid;
 
 use crate::paginated::{Paginate, PaginationResult};
-use crate::{DbConn, models::Collector, schema::event};
+use crate::{
+    DbConn,
+    models::{Collector, CollectorSource, StatsFilter},
+    schema::{collector, event},
+};
 
 #[derive(Associations, Deserialize, Identifiable, Insertable, Queryable, Serialize, Debug)]
 #[diesel(belongs_to(Collector, foreign_key = collector_id))]
@@ -20,14 +25,118 @@ pub struct Event {
     pub name: String,
     pub collector_id: String,
     pub created_at: Option<NaiveDateTime>,
+    /// Custom properties, as a JSON object of strings and numbers
+    pub props: Option<String>,
+    /// Active time reported by an `engagement` event, in milliseconds
+    pub engagement_ms: Option<i32>,
+    /// Campaign parameters of the page URL, see `CampaignParams`
+    pub utm_source: Option<String>,
+    pub utm_medium: Option<String>,
+    pub utm_campaign: Option<String>,
+    pub utm_term: Option<String>,
+    pub utm_content: Option<String>,
+    pub ref_param: Option<String>,
+    pub click_id: Option<String>,
+    /// URL as reported, before the URL rules of the site were applied to `url`
+    pub raw_url: Option<String>,
+    /// Path template matched by `url`, see `UrlRules`
+    pub path_template: Option<String>,
+    /// External referrer, split by `ReferrerClassifier`; `None` for internal ones
+    pub referrer_host: Option<String>,
+    pub referrer_domain: Option<String>,
+    pub referrer_path: Option<String>,
+    /// Where the event comes from, see `Channel`
+    pub channel: Option<String>,
 }
 
+/// Largest number of custom properties on an event
+const MAX_EVENT_PROPS: usize = 20;
+/// Longest property name
+const MAX_EVENT_PROP_KEY_LENGTH: usize = 64;
+/// Longest string property value
+const MAX_EVENT_PROP_VALUE_LENGTH: usize = 256;
+
 #[derive(Deserialize, Debug)]
 pub struct EventQuery {
     pub url: String,
     pub referrer: Option<String>,
     pub name: String,
     pub collector_id: String,
+    /// Signed collector token handed out by `/stats.js`
+    #[serde(default)]
+    pub token: Option<String>,
+    #[serde(default)]
+    pub site_id: Option<String>,
+    /// Set by the tracking script when `navigator.webdriver` is true
+    #[serde(default)]
+    pub webdriver: bool,
+    /// Custom properties, e.g. `{"plan": "pro", "seats": 3}`
+    #[serde(default)]
+    pub props: Option<Map<String, Value>>,
+    /// Active time spent on the page since the previous `engagement` event, in milliseconds
+    #[serde(default)]
+    pub engagement_ms: Option<i32>,
+}
+
+/// # `validate_prop_key`
+/// Checks that a custom property name is short and only made of letters, digits, `_` and `-`.
+///
+/// ## Arguments
+/// * `key` - The property name
+///
+/// ## Errors
+/// * `String` - Why the name is rejected
+pub fn validate_prop_key(key: &str) -> Result<(), String> {
+    if key.is_empty() || key.len() > MAX_EVENT_PROP_KEY_LENGTH {
+        return Err(format!(
+            "Property names must be 1 to {MAX_EVENT_PROP_KEY_LENGTH} characters long"
+        ));
+    }
+    if !key
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+    {
+        return Err(format!(
+            "Invalid property name {key}: only letters, digits, _ and - are allowed"
+        ));
+    }
+
+    Ok(())
+}
+
+impl EventQuery {
+    /// # `validate_props`
+    /// Checks that custom properties are few, short, and only strings or numbers.
+    ///
+    /// ## Errors
+    /// * `String` - Why the properties are rejected
+    pub fn validate_props(&self) -> Result<(), String> {
+        let Some(props) = &self.props else {
+            return Ok(());
+        };
+
+        if props.len() > MAX_EVENT_PROPS {
+            return Err(format!(
+                "Events are limited to {MAX_EVENT_PROPS} properties"
+            ));
+        }
+
+        for (key, value) in props {
+            validate_prop_key(key)?;
+
+            match value {
+                Value::String(text) if text.chars().count() > MAX_EVENT_PROP_VALUE_LENGTH => {
+                    return Err(format!(
+                        "Property {key} is longer than {MAX_EVENT_PROP_VALUE_LENGTH} characters"
+                    ));
+                }
+                Value::String(_) | Value::Number(_) => {}
+                _ => return Err(format!("Property {key} must be a string or a number")),
+            }
+        }
+
+        Ok(())
+    }
 }
 
 impl From<EventQuery> for Event {
@@ -39,6 +148,24 @@ impl From<EventQuery> for Event {
             name: query.name,
             collector_id: query.collector_id,
             created_at: None,
+            props: query
+                .props
+                .filter(|props| !props.is_empty())
+                .map(|props| Value::Object(props).to_string()),
+            engagement_ms: query.engagement_ms,
+            utm_source: None,
+            utm_medium: None,
+            utm_campaign: None,
+            utm_term: None,
+            utm_content: None,
+            ref_param: None,
+            click_id: None,
+            raw_url: None,
+            path_template: None,
+            referrer_host: None,
+            referrer_domain: None,
+            referrer_path: None,
+            channel: None,
         }
     }
 }
@@ -67,12 +194,38 @@ impl Event {
         .await
     }
 
+    /// # `insert_batch`
+    /// Inserts several events in a single transaction, all of them or none.
+    ///
+    /// ## Arguments
+    /// * `events` - Events to insert
+    /// * `conn` - Database connection
+    ///
+    /// ## Errors
+    /// If any of the events cannot be inserted into the database.
+    ///
+    /// ## Returns
+    /// * `QueryResult<usize>` - Number of events inserted
+    pub async fn insert_batch(events: Vec<Event>, conn: &DbConn) -> QueryResult<usize> {
+        conn.run(move |c| {
+            c.transaction(|c| {
+                for event in &events {
+                    diesel::insert_into(event::table).values(event).execute(c)?;
+                }
+
+                Ok(events.len())
+            })
+        })
+        .await
+    }
+
     /// # `all`
     /// Retrieves all `transit_stops` from the database.
     ///
     /// ## Arguments
     /// * `page` - The page number
     /// * `per_page` - The number of items per page
+    /// * `filter` - Scope of the events to retrieve
     /// * `conn` - Database connection
     ///
     /// ## Errors
@@ -80,10 +233,32 @@ impl Event {
     pub async fn all(
         page: i64,
         per_page: i64,
+        filter: StatsFilter,
         conn: &DbConn,
     ) -> QueryResult<PaginationResult<Event>> {
         conn.run(move |c| {
-            event::table
+            let mut query = event::table.into_boxed();
+
+            if !filter.include_bots() {
+                query = query.filter(
+                    event::collector_id.eq_any(
+                        collector::table
+                            .select(collector::id)
+                            .filter(collector::is_bot.eq(false)),
+                    ),
+                );
+            }
+            if let Some(site) = filter.site {
+                query = query.filter(
+                    event::collector_id.eq_any(
+                        collector::table
+                            .select(collector::id)
+                            .filter(collector::site_id.eq(site)),
+                    ),
+                );
+            }
+
+            query
                 .order(event::id)
                 .paginate(page)
                 .per_page(per_page)
@@ -91,4 +266,100 @@ impl Event {
         })
         .await
     }
+
+    /// # `recorded_for_visitor`
+    /// Whether the tracking script recorded any event of a visitor on a site since a given
+    /// time. Events of collectors started by access logs or backends are ignored.
+    ///
+    /// ## Arguments
+    /// * `site_id` - The site visited
+    /// * `visitor_id` - The anonymous visitor ID
+    /// * `since` - Oldest event time considered
+    /// * `conn` - Database connection
+    ///
+    /// ## Errors
+    /// If the query fails.
+    ///
+    /// ## Returns
+    /// * `QueryResult<bool>` - Whether such an event exists
+    pub async fn recorded_for_visitor(
+        site_id: String,
+        visitor_id: String,
+        since: NaiveDateTime,
+        conn: &DbConn,
+    ) -> QueryResult<bool> {
+        conn.run(move |c| {
+            let query = event::table
+                .inner_join(collector::table)
+                .filter(collector::site_id.eq(site_id))
+                .filter(collector::visitor_id.eq(visitor_id))
+                .filter(collector::source.eq(CollectorSource::Tracker.as_str()))
+                .filter(event::created_at.gt(since));
+
+            diesel::select(diesel::dsl::exists(query)).get_result(c)
+        })
+        .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn query(props: Value) -> EventQuery {
+        EventQuery {
+            url: "https://example.com/".to_string(),
+            referrer: None,
+            name: "signup".to_string(),
+            collector_id: Ulid::new().to_string(),
+            token: None,
+            site_id: None,
+            webdriver: false,
+            props: props.as_object().cloned(),
+            engagement_ms: None,
+        }
+    }
+
+    #[test]
+    fn validates_props() {
+        let too_many: Map<String, Value> = (0..=MAX_EVENT_PROPS)
+            .map(|i| (format!("key{i}"), json!(i)))
+            .collect();
+        let cases = [
+            (json!(null), true),
+            (json!({}), true),
+            (json!({"plan": "pro", "seats": 3, "ratio": 0.5}), true),
+            (json!({"a_b-c9": "x"}), true),
+            (json!({ "k".repeat(MAX_EVENT_PROP_KEY_LENGTH): 1 }), true),
+            (
+                json!({ "v": "é".repeat(MAX_EVENT_PROP_VALUE_LENGTH) }),
+                true,
+            ),
+            (Value::Object(too_many), false),
+            (
+                json!({ "k".repeat(MAX_EVENT_PROP_KEY_LENGTH + 1): 1 }),
+                false,
+            ),
+            (
+                json!({ "v": "x".repeat(MAX_EVENT_PROP_VALUE_LENGTH + 1) }),
+                false,
+            ),
+            (json!({"": 1}), false),
+            (json!({"has space": 1}), false),
+            (json!({"quote\"": 1}), false),
+            (json!({"nested": {"plan": "pro"}}), false),
+            (json!({"list": ["pro"]}), false),
+            (json!({"flag": true}), false),
+            (json!({"nothing": null}), false),
+        ];
+
+        for (props, valid) in cases {
+            assert_eq!(
+                query(props.clone()).validate_props().is_ok(),
+                valid,
+                "{props}"
+            );
+        }
+    }
 }
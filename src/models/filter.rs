@@ -0,0 +1,20 @@
+use rocket::FromForm;
+
+/// # `StatsFilter`
+/// Query parameters shared by every read endpoint to scope results.
+///
+/// * `site` - Only include data collected for the given site ID.
+///   When absent, data from every site (and legacy rows without a site) is included.
+/// * `include_bots` - Also include visitors flagged as bots, excluded by default.
+#[derive(FromForm, Debug, Default, Clone)]
+pub struct StatsFilter {
+    pub site: Option<String>,
+    pub include_bots: Option<bool>,
+}
+
+impl StatsFilter {
+    #[must_use]
+    pub fn include_bots(&self) -> bool {
+        self.include_bots.unwrap_or(false)
+    }
+}
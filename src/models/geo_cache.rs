@@ -0,0 +1,188 @@
+use std::net::IpAddr;
+
+use chrono::{Duration, NaiveDateTime, Utc};
+use diesel::{
+    OptionalExtension, QueryDsl, QueryResult, RunQueryDsl,
+    prelude::{Insertable, Queryable},
+};
+use ipnet::IpNet;
+use serde::Serialize;
+
+use crate::{
+    DbConn,
+    logger::Logger,
+    metrics::Metrics,
+    schema::geo_cache,
+    services::location::{GeoLocator, Location, LocationError},
+};
+
+/// Prefix length used to group IPv4 addresses in the cache
+const IPV4_CACHE_PREFIX: u8 = 24;
+/// Prefix length used to group IPv6 addresses in the cache
+const IPV6_CACHE_PREFIX: u8 = 48;
+
+#[derive(Queryable, Insertable, Serialize, Debug, Clone)]
+#[diesel(table_name = geo_cache)]
+#[serde(crate = "rocket::serde")]
+pub struct GeoCacheEntry {
+    pub network: String,
+    pub city: String,
+    pub country: String,
+    pub latitude: Option<f32>,
+    pub longitude: Option<f32>,
+    pub timezone: Option<String>,
+    pub provider: String,
+    pub expires_at: NaiveDateTime,
+    pub created_at: Option<NaiveDateTime>,
+}
+
+impl From<GeoCacheEntry> for Location {
+    fn from(entry: GeoCacheEntry) -> Self {
+        Location {
+            city: entry.city,
+            country: entry.country,
+            latitude: entry.latitude,
+            longitude: entry.longitude,
+            timezone: entry.timezone,
+        }
+    }
+}
+
+impl GeoCacheEntry {
+    /// # `network_key`
+    /// Returns the network an IP address is cached under (/24 for IPv4, /48 for IPv6).
+    /// Visitors of the same network share their location, which is as precise as
+    /// providers get anyway.
+    ///
+    /// ## Arguments
+    /// * `ip` - The IP address
+    ///
+    /// ## Returns
+    /// * `String` - The network in CIDR notation
+    #[must_use]
+    pub fn network_key(ip: IpAddr) -> String {
+        let prefix = match ip {
+            IpAddr::V4(_) => IPV4_CACHE_PREFIX,
+            IpAddr::V6(_) => IPV6_CACHE_PREFIX,
+        };
+
+        IpNet::new(ip, prefix)
+            .map(|network| network.trunc().to_string())
+            .unwrap_or_else(|_| ip.to_string())
+    }
+
+    /// # `find`
+    /// Finds the cache entry of a network, expired or not.
+    ///
+    /// ## Arguments
+    /// * `network` - The network in CIDR notation
+    /// * `conn` - The database connection
+    ///
+    /// ## Errors
+    /// If the query fails.
+    ///
+    /// ## Returns
+    /// * `QueryResult<Option<GeoCacheEntry>>` - The cache entry, if any
+    pub async fn find(network: String, conn: &DbConn) -> QueryResult<Option<GeoCacheEntry>> {
+        conn.run(move |c| geo_cache::table.find(network).first(c).optional())
+            .await
+    }
+
+    /// # `upsert`
+    /// Inserts or replaces the cache entry of a network.
+    ///
+    /// ## Arguments
+    /// * `entry` - The cache entry
+    /// * `conn` - The database connection
+    ///
+    /// ## Errors
+    /// If the query fails.
+    pub async fn upsert(entry: GeoCacheEntry, conn: &DbConn) -> QueryResult<usize> {
+        conn.run(move |c| {
+            diesel::replace_into(geo_cache::table)
+                .values(&entry)
+                .execute(c)
+        })
+        .await
+    }
+
+    /// # `locate`
+    /// Resolves the location of an IP address through the cache.
+    ///
+    /// A fresh cache entry is returned without calling the provider. Otherwise the provider
+    /// is asked and its answer cached for `ttl_seconds`. If the provider fails, an expired
+    /// entry is still better than nothing and is returned instead.
+    ///
+    /// ## Arguments
+    /// * `ip` - The IP address to look up
+    /// * `locator` - The geolocation provider
+    /// * `ttl_seconds` - How long a resolved location stays fresh, `0` disables the cache
+    /// * `metrics` - Counters to report hits and misses to
+    /// * `conn` - The database connection
+    ///
+    /// ## Errors
+    /// If the provider fails and nothing is cached for the network.
+    ///
+    /// ## Returns
+    /// * `Result<Location, LocationError>` - The location of the IP address
+    pub async fn locate(
+        ip: IpAddr,
+        locator: &dyn GeoLocator,
+        ttl_seconds: i64,
+        metrics: &Metrics,
+        conn: &DbConn,
+    ) -> Result<Location, LocationError> {
+        if ttl_seconds <= 0 {
+            return locator.locate(ip).await;
+        }
+
+        let network = Self::network_key(ip);
+        let cached = Self::find(network.clone(), conn).await.unwrap_or_else(|e| {
+            Logger::error("GeoCache", &format!("Failed to read cache: {e}"));
+            None
+        });
+        let now = Utc::now().naive_utc();
+
+        if let Some(entry) = &cached
+            && entry.expires_at > now
+        {
+            Metrics::increment(&metrics.geo_cache_hits);
+            return Ok(entry.clone().into());
+        }
+
+        Metrics::increment(&metrics.geo_cache_misses);
+
+        match locator.locate(ip).await {
+            Ok(location) => {
+                let entry = GeoCacheEntry {
+                    network,
+                    city: location.city.clone(),
+                    country: location.country.clone(),
+                    latitude: location.latitude,
+                    longitude: location.longitude,
+                    timezone: location.timezone.clone(),
+                    provider: locator.name().to_string(),
+                    expires_at: now + Duration::seconds(ttl_seconds),
+                    created_at: None,
+                };
+
+                if let Err(e) = Self::upsert(entry, conn).await {
+                    Logger::error("GeoCache", &format!("Failed to write cache: {e}"));
+                }
+
+                Ok(location)
+            }
+            Err(e) => match cached {
+                Some(entry) => {
+                    Logger::warn(
+                        "GeoCache",
+                        &format!("{} failed ({e}), using stale entry", locator.name()),
+                    );
+                    Metrics::increment(&metrics.geo_cache_stale_hits);
+                    Ok(entry.into())
+                }
+                None => Err(e),
+            },
+        }
+    }
+}
@@ -0,0 +1,142 @@
+use chrono::{NaiveDateTime, Utc};
+use diesel::{
+    Connection, ExpressionMethods, OptionalExtension, QueryDsl, QueryResult, RunQueryDsl,
+    prelude::{Insertable, Queryable},
+};
+use serde::{Deserialize, Serialize};
+use ulid::Ulid;
+
+use crate::{
+    DbConn,
+    models::{Collector, Event},
+    schema::{collector, event, log_import},
+};
+
+/// # `LogImport`
+/// An access log imported for a site, and how far.
+///
+/// A log is recognized by the hash of its first line, so that importing it again only
+/// reads the lines appended since, and a rotated `access.log.1` resumes where the import
+/// of `access.log` stopped.
+#[derive(Queryable, Insertable, Serialize, Deserialize, Debug, Clone)]
+#[diesel(table_name = log_import)]
+#[serde(crate = "rocket::serde")]
+pub struct LogImport {
+    pub id: String,
+    pub site_id: String,
+    /// Path of the file the log was last imported from
+    pub path: String,
+    /// SHA-256 of the first line of the log
+    pub fingerprint: String,
+    /// Lines already imported, including those skipped
+    pub lines: i32,
+    pub collectors: i32,
+    pub events: i32,
+    pub created_at: Option<NaiveDateTime>,
+    pub updated_at: Option<NaiveDateTime>,
+}
+
+impl LogImport {
+    /// # `start`
+    /// Finds the import of a log for a site, or records a new one.
+    ///
+    /// ## Arguments
+    /// * `site_id` - The site the log belongs to
+    /// * `path` - Path of the file being imported
+    /// * `fingerprint` - SHA-256 of the first line of the log
+    /// * `conn` - Database connection
+    ///
+    /// ## Errors
+    /// If the import cannot be read or written.
+    ///
+    /// ## Returns
+    /// * `QueryResult<LogImport>` - The import, with the lines already imported
+    pub async fn start(
+        site_id: String,
+        path: String,
+        fingerprint: String,
+        conn: &DbConn,
+    ) -> QueryResult<LogImport> {
+        conn.run(move |c| {
+            c.transaction(|c| {
+                let existing: Option<LogImport> = log_import::table
+                    .filter(log_import::site_id.eq(&site_id))
+                    .filter(log_import::fingerprint.eq(&fingerprint))
+                    .first(c)
+                    .optional()?;
+                if let Some(existing) = existing {
+                    return Ok(existing);
+                }
+
+                let import = LogImport {
+                    id: Ulid::new().to_string(),
+                    site_id,
+                    path,
+                    fingerprint,
+                    lines: 0,
+                    collectors: 0,
+                    events: 0,
+                    created_at: None,
+                    updated_at: None,
+                };
+                diesel::insert_into(log_import::table)
+                    .values(&import)
+                    .execute(c)?;
+
+                Ok(import)
+            })
+        })
+        .await
+    }
+
+    /// # `commit`
+    /// Stores the collectors and events read from a chunk of the log, and moves the
+    /// import past it, in one transaction: an interrupted import resumes after the last
+    /// chunk stored.
+    ///
+    /// ## Arguments
+    /// * `id` - Id of the `LogImport`
+    /// * `path` - Path of the file being imported
+    /// * `lines` - Lines of the log imported so far, including this chunk
+    /// * `collectors` - Collectors of the sessions started in this chunk
+    /// * `events` - Events of this chunk
+    /// * `conn` - Database connection
+    ///
+    /// ## Errors
+    /// If any of the rows cannot be written, in which case none is.
+    pub async fn commit(
+        id: String,
+        path: String,
+        lines: i32,
+        collectors: Vec<Collector>,
+        events: Vec<Event>,
+        conn: &DbConn,
+    ) -> QueryResult<()> {
+        conn.run(move |c| {
+            c.transaction(|c| {
+                for chunk in collectors.chunks(500) {
+                    diesel::insert_into(collector::table)
+                        .values(chunk)
+                        .execute(c)?;
+                }
+                for chunk in events.chunks(500) {
+                    diesel::insert_into(event::table).values(chunk).execute(c)?;
+                }
+
+                #[allow(clippy::cast_possible_truncation, clippy::cast_possible_wrap)]
+                diesel::update(log_import::table.find(id))
+                    .set((
+                        log_import::path.eq(path),
+                        log_import::lines.eq(lines),
+                        log_import::collectors.eq(log_import::collectors + collectors.len() as i32),
+                        log_import::events.eq(log_import::events + events.len() as i32),
+                        log_import::updated_at.eq(Utc::now().naive_utc()),
+                    ))
+                    .execute(c)?;
+
+                Ok(())
+            })
+        })
+        .await
+    }
+}
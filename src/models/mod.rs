@@ -1,11 +1,27 @@
+mod api_key;
 mod city;
 mod collector;
+mod error_group;
 mod event;
+mod filter;
+mod geo_cache;
+mod log_import;
 mod session;
+mod site;
 mod summary;
+mod visitor_salt;
+mod vital;
 
+pub use api_key::*;
 pub use city::*;
 pub use collector::*;
+pub use error_group::*;
 pub use event::*;
+pub use filter::*;
+pub use geo_cache::*;
+pub use log_import::*;
 pub use session::*;
+pub use site::*;
 pub use summary::*;
+pub use visitor_salt::*;
+pub use vital::*;
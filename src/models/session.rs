@@ -3,14 +3,14 @@ use diesel::{
     BelongingToDsl, ExpressionMethods, GroupedBy, QueryDsl, QueryResult, RunQueryDsl,
     prelude::QueryableByName,
     result::Error,
-    sql_types::{BigInt, Float, Text, Timestamp},
+    sql_types::{BigInt, Bool, Float, Nullable, Text, Timestamp},
     sqlite::Sqlite,
 };
 use serde::{Deserialize, Serialize};
 
 use crate::{
     DbConn,
-    models::{self, CollectorWithEvents, Event},
+    models::{self, CollectorWithEvents, Event, StatsFilter},
     schema::{collector, event},
 };
 
@@ -18,6 +18,7 @@ use crate::{
 /// Retrieve the last 30 recent visitor sessions.
 ///
 /// ## Arguments
+/// * `filter` - Scope of the sessions to retrieve.
 /// * `conn` - A database connection.
 ///
 /// ## Errors
@@ -25,10 +26,22 @@ use crate::{
 ///
 /// ## Returns
 /// * `QueryResult<Vec<CollectorWithEvents>>` - The result of the query.
-pub async fn retrieve_sessions(conn: &DbConn) -> QueryResult<Vec<CollectorWithEvents>> {
+pub async fn retrieve_sessions(
+    filter: StatsFilter,
+    conn: &DbConn,
+) -> QueryResult<Vec<CollectorWithEvents>> {
     let last_30_collectors = match conn
         .run(move |c| {
-            collector::table
+            let mut query = collector::table.into_boxed();
+
+            if !filter.include_bots() {
+                query = query.filter(collector::is_bot.eq(false));
+            }
+            if let Some(site) = filter.site {
+                query = query.filter(collector::site_id.eq(site));
+            }
+
+            query
                 .order(collector::created_at.desc())
                 .limit(30)
                 .load::<models::Collector>(c)
@@ -108,6 +121,7 @@ pub struct CityCount {
 /// 3. Formats the data for map display
 ///
 /// ## Arguments
+/// * `filter` - Scope of the visitors to include
 /// * `conn` - A database connection
 ///
 /// ## Errors
@@ -119,16 +133,20 @@ pub struct CityCount {
 ///   - Relative size (normalized between 0 and 1)
 ///   - City name
 ///   - Display color
-pub async fn map(conn: &DbConn) -> QueryResult<Vec<CityCollectorCount>> {
+pub async fn map(filter: StatsFilter, conn: &DbConn) -> QueryResult<Vec<CityCollectorCount>> {
     // Calculate the timestamp for 7 days ago
     let seven_days_ago = Utc::now().naive_utc() - Duration::days(7);
 
-    // SQL query to get collector counts per city
+    // SQL query to get collector counts per city, skipping the unknown city without coordinates
     let query = "
         SELECT ci.name, ci.latitude, ci.longitude, COUNT(*) AS count
         FROM collector co
         JOIN city ci ON ci.id = co.city_id
-        WHERE co.created_at >= ?
+        WHERE co.created_at >= ?1
+          AND (?2 IS NULL OR co.site_id = ?2)
+          AND (?3 OR co.is_bot = 0)
+          AND ci.latitude IS NOT NULL
+          AND ci.longitude IS NOT NULL
         GROUP BY ci.name, ci.latitude, ci.longitude
     ";
 
@@ -137,6 +155,8 @@ pub async fn map(conn: &DbConn) -> QueryResult<Vec<CityCollectorCount>> {
         .run(move |c| {
             diesel::sql_query(query)
                 .bind::<Timestamp, _>(seven_days_ago)
+                .bind::<Nullable<Text>, _>(filter.site.clone())
+                .bind::<Bool, _>(filter.include_bots())
                 .load::<CityCount>(c)
         })
         .await
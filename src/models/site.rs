@@ -0,0 +1,161 @@
+use chrono::NaiveDateTime;
+use diesel::{
+    ExpressionMethods, OptionalExtension, QueryDsl, QueryResult, RunQueryDsl,
+    prelude::{Identifiable, Insertable, Queryable},
+};
+use serde::{Deserialize, Serialize};
+use ulid::Ulid;
+
+use crate::paginated::{Paginate, PaginationResult};
+use crate::{DbConn, schema::site, services::url_rules::UrlRules};
+
+#[derive(Queryable, Insertable, Identifiable, Serialize, Deserialize, Debug, Clone)]
+#[diesel(table_name = site)]
+#[serde(crate = "rocket::serde")]
+pub struct Site {
+    pub id: String,
+    pub domain: String,
+    pub name: String,
+    pub timezone: String,
+    pub settings: String,
+    pub created_at: Option<NaiveDateTime>,
+}
+
+#[derive(Deserialize)]
+pub struct SiteQuery {
+    pub domain: String,
+    pub name: String,
+    pub timezone: Option<String>,
+    pub settings: Option<serde_json::Value>,
+}
+
+impl SiteQuery {
+    /// # `validate`
+    /// Checks that the domain is set and that the URL rules of the settings are valid.
+    ///
+    /// ## Errors
+    /// * `String` - Why the site is rejected
+    pub fn validate(&self) -> Result<(), String> {
+        if self.domain.trim().is_empty() {
+            return Err("Site domain cannot be empty".to_string());
+        }
+
+        if let Some(settings) = &self.settings {
+            UrlRules::from_settings(settings)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl From<SiteQuery> for Site {
+    fn from(query: SiteQuery) -> Self {
+        Site {
+            id: Ulid::new().to_string(),
+            domain: query.domain.trim().to_lowercase(),
+            name: query.name,
+            timezone: query.timezone.unwrap_or_else(|| "UTC".to_string()),
+            settings: query
+                .settings
+                .unwrap_or_else(|| serde_json::json!({}))
+                .to_string(),
+            created_at: None,
+        }
+    }
+}
+
+impl Site {
+    /// # `insert`
+    /// Inserts a new `Site` into the database.
+    ///
+    /// ## Arguments
+    /// * `site` - Site data to insert
+    /// * `conn` - Database connection
+    ///
+    /// ## Errors
+    /// If the `Site` cannot be inserted into the database (e.g. duplicate domain).
+    ///
+    /// ## Returns
+    /// * `QueryResult<String>` - Id of the inserted `Site`
+    pub async fn insert(site: Site, conn: &DbConn) -> QueryResult<String> {
+        conn.run(|c| {
+            diesel::insert_into(site::table).values(&site).execute(c)?;
+
+            Ok(site.id)
+        })
+        .await
+    }
+
+    /// # `update`
+    /// Replaces the domain, name, timezone and settings of an existing `Site`.
+    ///
+    /// ## Arguments
+    /// * `site` - Site data to save, identified by its ID
+    /// * `conn` - Database connection
+    ///
+    /// ## Errors
+    /// If the `Site` cannot be updated (e.g. duplicate domain).
+    ///
+    /// ## Returns
+    /// * `QueryResult<bool>` - Whether the `Site` exists
+    pub async fn update(site: Site, conn: &DbConn) -> QueryResult<bool> {
+        conn.run(move |c| {
+            let updated = diesel::update(site::table.find(site.id))
+                .set((
+                    site::domain.eq(site.domain),
+                    site::name.eq(site.name),
+                    site::timezone.eq(site.timezone),
+                    site::settings.eq(site.settings),
+                ))
+                .execute(c)?;
+
+            Ok(updated > 0)
+        })
+        .await
+    }
+
+    /// # `find`
+    /// Finds a site by its ID.
+    ///
+    /// ## Arguments
+    /// * `id` - The ID of the site.
+    /// * `conn` - The database connection.
+    ///
+    /// ## Errors
+    /// If the query fails.
+    ///
+    /// ## Returns
+    /// * `QueryResult<Option<Site>>` - The found site, or None if not found.
+    pub async fn find(id: String, conn: &DbConn) -> QueryResult<Option<Site>> {
+        conn.run(move |c| site::table.find(id).first(c).optional())
+            .await
+    }
+
+    /// # `all`
+    /// Returns all sites in the database.
+    ///
+    /// ## Arguments
+    /// * `page` - The page number
+    /// * `per_page` - The number of items per page
+    /// * `conn` - The database connection.
+    ///
+    /// ## Errors
+    /// If the sites cannot be retrieved.
+    ///
+    /// ## Returns
+    /// * `QueryResult<PaginationResult<Site>>` - The list of sites.
+    pub async fn all(
+        page: i64,
+        per_page: i64,
+        conn: &DbConn,
+    ) -> QueryResult<PaginationResult<Site>> {
+        conn.run(move |c| {
+            site::table
+                .order(site::domain)
+                .paginate(page)
+                .per_page(per_page)
+                .load_and_count_pages(c)
+        })
+        .await
+    }
+}
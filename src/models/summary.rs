@@ -4,12 +4,12 @@ use diesel::{
     prelude::QueryableByName,
     result::Error,
     sql_query,
-    sql_types::{BigInt, Integer, Text, Timestamp},
+    sql_types::{BigInt, Bool, Double, Integer, Nullable, Text, Timestamp},
 };
 use serde::{Deserialize, Serialize};
 use serde_json::json;
 
-use crate::DbConn;
+use crate::{DbConn, models::StatsFilter, services::tracker_event::TrackerEvent};
 
 #[derive(QueryableByName, Debug, Serialize)]
 pub struct FiveMinuteEventSummary {
@@ -17,12 +17,15 @@ pub struct FiveMinuteEventSummary {
     pub interval: String,
     #[diesel(sql_type = BigInt)]
     pub count: i64,
+    #[diesel(sql_type = BigInt)]
+    pub unique_visitors: i64,
 }
 
 /// # `five_minutes`
 /// Retrieves a minute-by-minute summary of events from the database over the past 24 hours.
 ///
 /// ## Arguments
+/// * `filter` - Scope of the data to summarize.
 /// * `conn` - A database connection.
 ///
 /// ## Errors
@@ -30,14 +33,21 @@ pub struct FiveMinuteEventSummary {
 ///
 /// ## Returns
 /// `QueryResult<Vec<FiveMinuteEventSummary>>` containing the summary data.
-pub async fn five_minutes(conn: &DbConn) -> QueryResult<Vec<FiveMinuteEventSummary>> {
+pub async fn five_minutes(
+    filter: StatsFilter,
+    conn: &DbConn,
+) -> QueryResult<Vec<FiveMinuteEventSummary>> {
     let start_time = Utc::now().naive_utc() - Duration::days(1);
 
     let sql = "
-        SELECT STRFTIME('%Y-%m-%d %H:%M:00', created_at) AS interval, COUNT(*) AS count
+        SELECT STRFTIME('%Y-%m-%d %H:%M:00', event.created_at) AS interval, COUNT(*) AS count,
+               COUNT(DISTINCT collector.visitor_id) AS unique_visitors
         FROM event
-        WHERE event.created_at > ?
-        GROUP BY STRFTIME('%Y-%m-%d %H:%M', created_at)
+        JOIN collector ON collector.id = event.collector_id
+        WHERE event.created_at > ?1
+          AND (?2 IS NULL OR collector.site_id = ?2)
+          AND (?3 OR collector.is_bot = 0)
+        GROUP BY STRFTIME('%Y-%m-%d %H:%M', event.created_at)
         ORDER BY interval;
     ";
 
@@ -45,6 +55,8 @@ pub async fn five_minutes(conn: &DbConn) -> QueryResult<Vec<FiveMinuteEventSumma
         .run(move |c| {
             diesel::sql_query(sql)
                 .bind::<Timestamp, _>(start_time)
+                .bind::<Nullable<Text>, _>(filter.site.clone())
+                .bind::<Bool, _>(filter.include_bots())
                 .load::<FiveMinuteEventSummary>(c)
         })
         .await
@@ -62,6 +74,8 @@ pub struct EventCounts {
     #[diesel(sql_type = BigInt)]
     pub sessions_in_last_twenty_four_hours: i64,
     #[diesel(sql_type = BigInt)]
+    pub unique_visitors_in_last_twenty_four_hours: i64,
+    #[diesel(sql_type = BigInt)]
     pub events_in_last_twenty_four_hours: i64,
     #[diesel(sql_type = BigInt)]
     pub events_in_last_hour: i64,
@@ -73,6 +87,7 @@ pub struct EventCounts {
 /// Retrieves summary statistics of session and event counts over different time intervals (24 hours, 1 hour, 5 minutes).
 ///
 /// ## Arguments
+/// * `filter` - Scope of the data to summarize.
 /// * `conn` - A database connection.
 ///
 /// ## Errors
@@ -80,15 +95,21 @@ pub struct EventCounts {
 ///
 /// ## Returns
 /// `QueryResult<Vec<EventSummary>>` containing the summary data.
-pub async fn events(conn: &DbConn) -> QueryResult<Vec<EventCounts>> {
+pub async fn events(filter: StatsFilter, conn: &DbConn) -> QueryResult<Vec<EventCounts>> {
     let query = "SELECT \
-        (SELECT COUNT(*) FROM collector WHERE created_at >= datetime('now', '-24 hours')) AS sessions_in_last_twenty_four_hours, \
-        (SELECT COUNT(*) FROM event WHERE created_at >= datetime('now', '-24 hours')) AS events_in_last_twenty_four_hours, \
-        (SELECT COUNT(*) FROM event WHERE created_at >= datetime('now', '-5 minutes')) AS events_in_last_five_minutes, \
-        (SELECT COUNT(*) FROM event WHERE created_at >= datetime('now', '-1 hour')) AS events_in_last_hour";
+        (SELECT COUNT(*) FROM collector WHERE created_at >= datetime('now', '-24 hours') AND (?1 IS NULL OR site_id = ?1) AND (?2 OR is_bot = 0)) AS sessions_in_last_twenty_four_hours, \
+        (SELECT COUNT(DISTINCT visitor_id) FROM collector WHERE created_at >= datetime('now', '-24 hours') AND (?1 IS NULL OR site_id = ?1) AND (?2 OR is_bot = 0)) AS unique_visitors_in_last_twenty_four_hours, \
+        (SELECT COUNT(*) FROM event JOIN collector ON collector.id = event.collector_id WHERE event.created_at >= datetime('now', '-24 hours') AND (?1 IS NULL OR collector.site_id = ?1) AND (?2 OR collector.is_bot = 0)) AS events_in_last_twenty_four_hours, \
+        (SELECT COUNT(*) FROM event JOIN collector ON collector.id = event.collector_id WHERE event.created_at >= datetime('now', '-5 minutes') AND (?1 IS NULL OR collector.site_id = ?1) AND (?2 OR collector.is_bot = 0)) AS events_in_last_five_minutes, \
+        (SELECT COUNT(*) FROM event JOIN collector ON collector.id = event.collector_id WHERE event.created_at >= datetime('now', '-1 hour') AND (?1 IS NULL OR collector.site_id = ?1) AND (?2 OR collector.is_bot = 0)) AS events_in_last_hour";
 
     match conn
-        .run(move |c| sql_query(query).load::<EventCounts>(c))
+        .run(move |c| {
+            sql_query(query)
+                .bind::<Nullable<Text>, _>(filter.site.clone())
+                .bind::<Bool, _>(filter.include_bots())
+                .load::<EventCounts>(c)
+        })
         .await
     {
         Ok(query) => Ok(query),
@@ -105,12 +126,15 @@ pub struct HourlyEventSummary {
     hour: NaiveDateTime,
     #[diesel(sql_type = Integer)]
     count: i32,
+    #[diesel(sql_type = Integer)]
+    unique_visitors: i32,
 }
 
 /// # `hourly`
 /// Retrieves an hourly breakdown of events from the database over the past 24 hours.
 ///
 /// ## Arguments
+/// * `filter` - Scope of the data to summarize.
 /// * `conn` - A database connection.
 ///
 /// ## Errors
@@ -118,14 +142,18 @@ pub struct HourlyEventSummary {
 ///
 /// ## Returns
 /// `QueryResult<Vec<HourlyEventSummary>>` containing the hourly event summary data.
-pub async fn hourly(conn: &DbConn) -> QueryResult<Vec<HourlyEventSummary>> {
+pub async fn hourly(filter: StatsFilter, conn: &DbConn) -> QueryResult<Vec<HourlyEventSummary>> {
     let start_time = Utc::now().naive_utc() - Duration::days(1);
 
     let sql = "
-        SELECT strftime('%Y-%m-%d %H:00:00', created_at) AS hour, COUNT(*) AS count
+        SELECT strftime('%Y-%m-%d %H:00:00', event.created_at) AS hour, COUNT(*) AS count,
+               COUNT(DISTINCT collector.visitor_id) AS unique_visitors
         FROM event
-        WHERE created_at > ?
-        GROUP BY strftime('%Y-%m-%d %H', created_at)
+        JOIN collector ON collector.id = event.collector_id
+        WHERE event.created_at > ?1
+          AND (?2 IS NULL OR collector.site_id = ?2)
+          AND (?3 OR collector.is_bot = 0)
+        GROUP BY strftime('%Y-%m-%d %H', event.created_at)
         ORDER BY hour;
     ";
 
@@ -133,6 +161,8 @@ pub async fn hourly(conn: &DbConn) -> QueryResult<Vec<HourlyEventSummary>> {
         .run(move |c| {
             sql_query(sql)
                 .bind::<Timestamp, _>(start_time)
+                .bind::<Nullable<Text>, _>(filter.site.clone())
+                .bind::<Bool, _>(filter.include_bots())
                 .load::<HourlyEventSummary>(c)
         })
         .await
@@ -153,10 +183,33 @@ pub struct UrlEventCount {
     pub count: i64,
 }
 
+/// # `UrlGrouping`
+/// How `urls` groups events.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UrlGrouping {
+    /// The URL, once normalized by the URL rules of the site
+    Url,
+    /// The path template of the URL, e.g. `/blog/:slug`
+    Template,
+}
+
+impl UrlGrouping {
+    /// SQL expression of the grouping over the `event` table
+    fn expression(self) -> &'static str {
+        match self {
+            UrlGrouping::Url => "event.url",
+            // Events recorded before path templates existed only have their URL
+            UrlGrouping::Template => "COALESCE(event.path_template, event.url)",
+        }
+    }
+}
+
 /// # `urls`
 /// Retrieves the top 25 most visited URLs from the past 7 days, ordered by visit count.
 ///
 /// ## Arguments
+/// * `grouping` - Whether to group events by URL or by path template.
+/// * `filter` - Scope of the data to summarize.
 /// * `conn` - A database connection.
 ///
 /// ## Errors
@@ -164,22 +217,34 @@ pub struct UrlEventCount {
 ///
 /// ## Returns
 /// `QueryResult<Vec<UrlEventCount>>` containing the top 25 most visited URLs.
-pub async fn urls(conn: &DbConn) -> QueryResult<Vec<UrlEventCount>> {
+pub async fn urls(
+    grouping: UrlGrouping,
+    filter: StatsFilter,
+    conn: &DbConn,
+) -> QueryResult<Vec<UrlEventCount>> {
     let start_time = Utc::now().naive_utc() - Duration::days(7);
 
-    let sql = "
-        SELECT url, COUNT(*) AS count
+    let sql = format!(
+        "
+        SELECT {expression} AS url, COUNT(*) AS count
         FROM event
-        WHERE event.created_at > ?
-        GROUP BY url
+        JOIN collector ON collector.id = event.collector_id
+        WHERE event.created_at > ?1
+          AND (?2 IS NULL OR collector.site_id = ?2)
+          AND (?3 OR collector.is_bot = 0)
+        GROUP BY 1
         ORDER BY count DESC
         LIMIT 25;
-    ";
+    ",
+        expression = grouping.expression()
+    );
 
     match conn
         .run(move |c| {
             sql_query(sql)
                 .bind::<Timestamp, _>(start_time)
+                .bind::<Nullable<Text>, _>(filter.site.clone())
+                .bind::<Bool, _>(filter.include_bots())
                 .load::<UrlEventCount>(c)
         })
         .await
@@ -192,6 +257,454 @@ pub async fn urls(conn: &DbConn) -> QueryResult<Vec<UrlEventCount>> {
     }
 }
 
+#[derive(Serialize, Deserialize, QueryableByName)]
+pub struct UrlEngagement {
+    #[diesel(sql_type = Text)]
+    pub url: String,
+    #[diesel(sql_type = BigInt)]
+    pub pageviews: i64,
+    #[diesel(sql_type = BigInt)]
+    pub total_time_ms: i64,
+    #[diesel(sql_type = BigInt)]
+    pub average_time_ms: i64,
+}
+
+/// # `time_on_page`
+/// Retrieves the average active time spent on the 25 most visited URLs over the past 7 days.
+///
+/// Active time comes from `engagement` events, and is divided by the number of page views
+/// (`enter` and `visit` events) of each URL.
+///
+/// ## Arguments
+/// * `filter` - Scope of the data to summarize.
+/// * `conn` - A database connection.
+///
+/// ## Errors
+/// * `Error::NotFound` - If the query fails.
+///
+/// ## Returns
+/// `QueryResult<Vec<UrlEngagement>>` containing the page views, total and average active time
+/// of each URL.
+pub async fn time_on_page(filter: StatsFilter, conn: &DbConn) -> QueryResult<Vec<UrlEngagement>> {
+    let start_time = Utc::now().naive_utc() - Duration::days(7);
+
+    let sql = "
+        SELECT url,
+               pageviews,
+               total_time_ms,
+               total_time_ms / pageviews AS average_time_ms
+        FROM (
+            SELECT event.url,
+                   SUM(event.name IN ('enter', 'visit')) AS pageviews,
+                   COALESCE(SUM(event.engagement_ms), 0) AS total_time_ms
+            FROM event
+            JOIN collector ON collector.id = event.collector_id
+            WHERE event.created_at > ?1
+              AND (?2 IS NULL OR collector.site_id = ?2)
+              AND (?3 OR collector.is_bot = 0)
+              AND event.name IN ('enter', 'visit', 'engagement')
+            GROUP BY event.url
+        )
+        WHERE pageviews > 0
+        ORDER BY pageviews DESC
+        LIMIT 25;
+    ";
+
+    match conn
+        .run(move |c| {
+            sql_query(sql)
+                .bind::<Timestamp, _>(start_time)
+                .bind::<Nullable<Text>, _>(filter.site.clone())
+                .bind::<Bool, _>(filter.include_bots())
+                .load::<UrlEngagement>(c)
+        })
+        .await
+    {
+        Ok(query) => Ok(query),
+        Err(e) => {
+            eprintln!("Failed to load time on page: {e}");
+            Err(Error::NotFound)
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, QueryableByName)]
+pub struct SessionDurationSummary {
+    #[diesel(sql_type = BigInt)]
+    pub sessions: i64,
+    #[diesel(sql_type = BigInt)]
+    pub engaged_sessions: i64,
+    #[diesel(sql_type = BigInt)]
+    pub average_duration_ms: i64,
+    #[diesel(sql_type = BigInt)]
+    pub average_pageviews: i64,
+}
+
+/// # `session_durations`
+/// Retrieves the average active time of the sessions of the past 7 days.
+///
+/// The duration of a session is the sum of its `engagement` events. Sessions without any
+/// page view are left out, sessions without engagement count as zero.
+///
+/// ## Arguments
+/// * `filter` - Scope of the data to summarize.
+/// * `conn` - A database connection.
+///
+/// ## Errors
+/// * `Error::NotFound` - If the query fails.
+///
+/// ## Returns
+/// `QueryResult<SessionDurationSummary>` containing the number of sessions, of sessions with
+/// some active time, and their average duration and page views.
+pub async fn session_durations(
+    filter: StatsFilter,
+    conn: &DbConn,
+) -> QueryResult<SessionDurationSummary> {
+    let start_time = Utc::now().naive_utc() - Duration::days(7);
+
+    let sql = "
+        SELECT COUNT(*) AS sessions,
+               COALESCE(SUM(duration_ms > 0), 0) AS engaged_sessions,
+               CAST(COALESCE(AVG(duration_ms), 0) AS INTEGER) AS average_duration_ms,
+               CAST(COALESCE(AVG(pageviews), 0) AS INTEGER) AS average_pageviews
+        FROM (
+            SELECT event.collector_id,
+                   SUM(event.name IN ('enter', 'visit')) AS pageviews,
+                   COALESCE(SUM(event.engagement_ms), 0) AS duration_ms
+            FROM event
+            JOIN collector ON collector.id = event.collector_id
+            WHERE event.created_at > ?1
+              AND (?2 IS NULL OR collector.site_id = ?2)
+              AND (?3 OR collector.is_bot = 0)
+            GROUP BY event.collector_id
+        )
+        WHERE pageviews > 0;
+    ";
+
+    match conn
+        .run(move |c| {
+            sql_query(sql)
+                .bind::<Timestamp, _>(start_time)
+                .bind::<Nullable<Text>, _>(filter.site.clone())
+                .bind::<Bool, _>(filter.include_bots())
+                .get_result::<SessionDurationSummary>(c)
+        })
+        .await
+    {
+        Ok(query) => Ok(query),
+        Err(e) => {
+            eprintln!("Failed to load session durations: {e}");
+            Err(Error::NotFound)
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, QueryableByName)]
+pub struct VitalPercentiles {
+    #[diesel(sql_type = Text)]
+    pub url: String,
+    #[diesel(sql_type = Text)]
+    pub device_type: String,
+    #[diesel(sql_type = Text)]
+    pub name: String,
+    #[diesel(sql_type = BigInt)]
+    pub samples: i64,
+    #[diesel(sql_type = Double)]
+    pub p50: f64,
+    #[diesel(sql_type = Double)]
+    pub p75: f64,
+    #[diesel(sql_type = Double)]
+    pub p95: f64,
+}
+
+/// # `vitals`
+/// Retrieves the 50th, 75th and 95th percentiles of each Web Vital, per URL and device type,
+/// for the 100 most measured combinations of a date range.
+///
+/// Percentiles use the nearest-rank method: the p75 is the smallest value that at least
+/// 75% of the samples are lower than or equal to.
+///
+/// ## Arguments
+/// * `start_time` - Beginning of the range, included.
+/// * `end_time` - End of the range, excluded.
+/// * `filter` - Scope of the data to summarize.
+/// * `conn` - A database connection.
+///
+/// ## Errors
+/// * `Error::NotFound` - If the query fails.
+///
+/// ## Returns
+/// `QueryResult<Vec<VitalPercentiles>>` containing the number of samples and percentiles
+/// of each metric.
+pub async fn vitals(
+    start_time: NaiveDateTime,
+    end_time: NaiveDateTime,
+    filter: StatsFilter,
+    conn: &DbConn,
+) -> QueryResult<Vec<VitalPercentiles>> {
+    let sql = "
+        WITH ranked AS (
+            SELECT vital.url,
+                   COALESCE(collector.device_type, 'unknown') AS device_type,
+                   vital.name,
+                   vital.value,
+                   ROW_NUMBER() OVER (
+                       PARTITION BY vital.url, collector.device_type, vital.name
+                       ORDER BY vital.value
+                   ) AS rank,
+                   COUNT(*) OVER (
+                       PARTITION BY vital.url, collector.device_type, vital.name
+                   ) AS samples
+            FROM vital
+            JOIN collector ON collector.id = vital.collector_id
+            WHERE vital.created_at >= ?1
+              AND vital.created_at < ?2
+              AND (?3 IS NULL OR collector.site_id = ?3)
+              AND (?4 OR collector.is_bot = 0)
+        )
+        SELECT url,
+               device_type,
+               name,
+               samples,
+               MIN(CASE WHEN rank * 100 >= samples * 50 THEN value END) AS p50,
+               MIN(CASE WHEN rank * 100 >= samples * 75 THEN value END) AS p75,
+               MIN(CASE WHEN rank * 100 >= samples * 95 THEN value END) AS p95
+        FROM ranked
+        GROUP BY url, device_type, name
+        ORDER BY samples DESC, url, device_type, name
+        LIMIT 100;
+    ";
+
+    match conn
+        .run(move |c| {
+            sql_query(sql)
+                .bind::<Timestamp, _>(start_time)
+                .bind::<Timestamp, _>(end_time)
+                .bind::<Nullable<Text>, _>(filter.site.clone())
+                .bind::<Bool, _>(filter.include_bots())
+                .load::<VitalPercentiles>(c)
+        })
+        .await
+    {
+        Ok(query) => Ok(query),
+        Err(e) => {
+            eprintln!("Failed to load vitals: {e}");
+            Err(Error::NotFound)
+        }
+    }
+}
+
+/// # `CampaignDimension`
+/// How `campaigns` groups sessions.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CampaignDimension {
+    /// `utm_campaign`
+    Campaign,
+    /// `utm_source`, else `ref`, else the ad network of a click ID
+    Source,
+    /// `utm_medium`, else `cpc` or `paid_social` for click IDs
+    Medium,
+}
+
+impl CampaignDimension {
+    /// SQL expression of the dimension over the `event` table
+    fn expression(self) -> &'static str {
+        match self {
+            CampaignDimension::Campaign => "event.utm_campaign",
+            CampaignDimension::Source => {
+                "COALESCE(event.utm_source, event.ref_param,
+                    CASE event.click_id WHEN 'gclid' THEN 'google' WHEN 'fbclid' THEN 'facebook' END)"
+            }
+            CampaignDimension::Medium => {
+                "COALESCE(event.utm_medium,
+                    CASE event.click_id WHEN 'gclid' THEN 'cpc' WHEN 'fbclid' THEN 'paid_social' END)"
+            }
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, QueryableByName)]
+pub struct CampaignCount {
+    #[diesel(sql_type = Text)]
+    pub name: String,
+    #[diesel(sql_type = BigInt)]
+    pub sessions: i64,
+    #[diesel(sql_type = BigInt)]
+    pub visitors: i64,
+    #[diesel(sql_type = BigInt)]
+    pub conversions: i64,
+}
+
+/// # `campaigns`
+/// Retrieves the top 25 campaigns, sources or mediums of the past 7 days.
+///
+/// Only sessions with campaign parameters are counted, see `channels` for all traffic. A
+/// session is attributed to the first of its events carrying campaign parameters, and
+/// converts when it has a `goal` event, or any custom event when no goal is given, from
+/// that event on. Sessions whose campaign parameters lack the dimension, e.g. a
+/// `utm_source` without `utm_campaign`, are grouped under `(none)`.
+///
+/// ## Arguments
+/// * `dimension` - What to group sessions by.
+/// * `goal` - Name of the event counted as a conversion, if any.
+/// * `filter` - Scope of the data to summarize.
+/// * `conn` - A database connection.
+///
+/// ## Errors
+/// * `Error::NotFound` - If the query fails.
+///
+/// ## Returns
+/// `QueryResult<Vec<CampaignCount>>` containing the sessions, unique visitors and
+/// conversions of each value.
+pub async fn campaigns(
+    dimension: CampaignDimension,
+    goal: Option<String>,
+    filter: StatsFilter,
+    conn: &DbConn,
+) -> QueryResult<Vec<CampaignCount>> {
+    let start_time = Utc::now().naive_utc() - Duration::days(7);
+    let tracker_events = TrackerEvent::ALL
+        .iter()
+        .map(|event| format!("'{event}'"))
+        .collect::<Vec<_>>()
+        .join(", ");
+
+    let sql = format!(
+        "
+        WITH touches AS (
+            SELECT event.collector_id,
+                   COALESCE(collector.visitor_id, collector.id) AS visitor,
+                   {dimension} AS name,
+                   event.created_at AS touched_at,
+                   ROW_NUMBER() OVER (
+                       PARTITION BY event.collector_id
+                       ORDER BY event.created_at, event.id
+                   ) AS touch
+            FROM event
+            JOIN collector ON collector.id = event.collector_id
+            WHERE event.created_at > ?1
+              AND (?2 IS NULL OR collector.site_id = ?2)
+              AND (?3 OR collector.is_bot = 0)
+              AND (event.utm_source IS NOT NULL
+                   OR event.utm_medium IS NOT NULL
+                   OR event.utm_campaign IS NOT NULL
+                   OR event.ref_param IS NOT NULL
+                   OR event.click_id IS NOT NULL)
+        ),
+        converted AS (
+            SELECT DISTINCT touches.collector_id
+            FROM touches
+            JOIN event ON event.collector_id = touches.collector_id
+            WHERE touches.touch = 1
+              AND event.created_at >= touches.touched_at
+              AND CASE WHEN ?4 IS NULL
+                       THEN event.name NOT IN ({tracker_events})
+                       ELSE event.name = ?4
+                  END
+        )
+        SELECT COALESCE(touches.name, '(none)') AS name,
+               COUNT(*) AS sessions,
+               COUNT(DISTINCT touches.visitor) AS visitors,
+               COUNT(converted.collector_id) AS conversions
+        FROM touches
+        LEFT JOIN converted ON converted.collector_id = touches.collector_id
+        WHERE touches.touch = 1
+        GROUP BY COALESCE(touches.name, '(none)')
+        ORDER BY sessions DESC
+        LIMIT 25;
+    ",
+        dimension = dimension.expression()
+    );
+
+    match conn
+        .run(move |c| {
+            sql_query(sql)
+                .bind::<Timestamp, _>(start_time)
+                .bind::<Nullable<Text>, _>(filter.site.clone())
+                .bind::<Bool, _>(filter.include_bots())
+                .bind::<Nullable<Text>, _>(goal)
+                .load::<CampaignCount>(c)
+        })
+        .await
+    {
+        Ok(query) => Ok(query),
+        Err(e) => {
+            eprintln!("Failed to load campaigns: {e}");
+            Err(Error::NotFound)
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, QueryableByName)]
+pub struct PropertyValueCount {
+    #[diesel(sql_type = Text)]
+    pub value: String,
+    #[diesel(sql_type = BigInt)]
+    pub count: i64,
+    #[diesel(sql_type = BigInt)]
+    pub visitors: i64,
+}
+
+/// # `properties`
+/// Retrieves the top 25 values of a custom property of an event over the past 7 days.
+///
+/// ## Arguments
+/// * `event` - Name of the event.
+/// * `key` - Name of the property, already checked by `validate_prop_key`.
+/// * `filter` - Scope of the data to summarize.
+/// * `conn` - A database connection.
+///
+/// ## Errors
+/// * `Error::NotFound` - If the query fails.
+///
+/// ## Returns
+/// `QueryResult<Vec<PropertyValueCount>>` containing the top 25 values, with the number of
+/// events and of distinct collectors for each.
+pub async fn properties(
+    event: String,
+    key: String,
+    filter: StatsFilter,
+    conn: &DbConn,
+) -> QueryResult<Vec<PropertyValueCount>> {
+    let start_time = Utc::now().naive_utc() - Duration::days(7);
+    let path = format!("$.\"{key}\"");
+
+    let sql = "
+        SELECT CAST(json_extract(event.props, ?4) AS TEXT) AS value,
+               COUNT(*) AS count,
+               COUNT(DISTINCT event.collector_id) AS visitors
+        FROM event
+        JOIN collector ON collector.id = event.collector_id
+        WHERE event.created_at > ?1
+          AND (?2 IS NULL OR collector.site_id = ?2)
+          AND (?3 OR collector.is_bot = 0)
+          AND event.name = ?5
+          AND json_extract(event.props, ?4) IS NOT NULL
+        GROUP BY value
+        ORDER BY count DESC
+        LIMIT 25;
+    ";
+
+    match conn
+        .run(move |c| {
+            sql_query(sql)
+                .bind::<Timestamp, _>(start_time)
+                .bind::<Nullable<Text>, _>(filter.site.clone())
+                .bind::<Bool, _>(filter.include_bots())
+                .bind::<Text, _>(path)
+                .bind::<Text, _>(event)
+                .load::<PropertyValueCount>(c)
+        })
+        .await
+    {
+        Ok(query) => Ok(query),
+        Err(e) => {
+            eprintln!("Failed to load property values: {e}");
+            Err(Error::NotFound)
+        }
+    }
+}
+
 #[derive(Serialize, Deserialize, QueryableByName)]
 pub struct BrowserVisitCount {
     #[diesel(sql_type = Text)]
@@ -204,6 +717,7 @@ pub struct BrowserVisitCount {
 /// Retrieves statistics on the top 25 browsers used by visitors over the past 7 days.
 ///
 /// ## Arguments
+/// * `filter` - Scope of the data to summarize.
 /// * `conn` - A database connection.
 ///
 /// ## Errors
@@ -211,13 +725,15 @@ pub struct BrowserVisitCount {
 ///
 /// ## Returns
 /// `QueryResult<Vec<BrowserVisitCount>>` containing the top 25 most used browsers.
-pub async fn browsers(conn: &DbConn) -> QueryResult<Vec<BrowserVisitCount>> {
+pub async fn browsers(filter: StatsFilter, conn: &DbConn) -> QueryResult<Vec<BrowserVisitCount>> {
     let start_time = Utc::now().naive_utc() - Duration::days(7);
 
     let sql = "
         SELECT browser, COUNT(*) AS count
         FROM collector
-        WHERE collector.created_at > ?
+        WHERE collector.created_at > ?1
+          AND (?2 IS NULL OR collector.site_id = ?2)
+          AND (?3 OR collector.is_bot = 0)
           AND browser IS NOT NULL
         GROUP BY browser
         ORDER BY count DESC
@@ -228,6 +744,8 @@ pub async fn browsers(conn: &DbConn) -> QueryResult<Vec<BrowserVisitCount>> {
         .run(move |c| {
             sql_query(sql)
                 .bind::<Timestamp, _>(start_time)
+                .bind::<Nullable<Text>, _>(filter.site.clone())
+                .bind::<Bool, _>(filter.include_bots())
                 .load::<BrowserVisitCount>(c)
         })
         .await
@@ -240,6 +758,60 @@ pub async fn browsers(conn: &DbConn) -> QueryResult<Vec<BrowserVisitCount>> {
     }
 }
 
+#[derive(Serialize, Deserialize, QueryableByName)]
+pub struct BotVisitCount {
+    #[diesel(sql_type = Text)]
+    pub reason: String,
+    #[diesel(sql_type = Text)]
+    pub browser: String,
+    #[diesel(sql_type = BigInt)]
+    pub count: i64,
+}
+
+/// # `bots`
+/// Retrieves the visits flagged as bots over the past 7 days, by reason and browser.
+///
+/// ## Arguments
+/// * `filter` - Scope of the data to summarize.
+/// * `conn` - A database connection.
+///
+/// ## Errors
+/// * `Error::NotFound` - If the query fails.
+///
+/// ## Returns
+/// `QueryResult<Vec<BotVisitCount>>` containing the bot visits, most frequent first.
+pub async fn bots(filter: StatsFilter, conn: &DbConn) -> QueryResult<Vec<BotVisitCount>> {
+    let start_time = Utc::now().naive_utc() - Duration::days(7);
+
+    let sql = "
+        SELECT COALESCE(bot_reason, 'unknown') AS reason,
+               COALESCE(browser, 'Other') AS browser,
+               COUNT(*) AS count
+        FROM collector
+        WHERE collector.created_at > ?1
+          AND (?2 IS NULL OR collector.site_id = ?2)
+          AND collector.is_bot = 1
+        GROUP BY reason, browser
+        ORDER BY count DESC;
+    ";
+
+    match conn
+        .run(move |c| {
+            sql_query(sql)
+                .bind::<Timestamp, _>(start_time)
+                .bind::<Nullable<Text>, _>(filter.site)
+                .load::<BotVisitCount>(c)
+        })
+        .await
+    {
+        Ok(query) => Ok(query),
+        Err(e) => {
+            eprintln!("Failed to load bot visits: {e}");
+            Err(Error::NotFound)
+        }
+    }
+}
+
 #[derive(Serialize, Deserialize, QueryableByName)]
 pub struct OsBrowserVisitCount {
     #[diesel(sql_type = Text)]
@@ -254,6 +826,7 @@ pub struct OsBrowserVisitCount {
 /// Retrieves statistics on the top 25 OS and browser combinations used by visitors over the past 7 days.
 ///
 /// ## Arguments
+/// * `filter` - Scope of the data to summarize.
 /// * `conn` - A database connection.
 ///
 /// ## Errors
@@ -261,13 +834,18 @@ pub struct OsBrowserVisitCount {
 ///
 /// ## Returns
 /// `QueryResult<Vec<OsBrowserVisitCount>>` containing the top 25 most used browsers.
-pub async fn os_browsers(conn: &DbConn) -> QueryResult<Vec<OsBrowserVisitCount>> {
+pub async fn os_browsers(
+    filter: StatsFilter,
+    conn: &DbConn,
+) -> QueryResult<Vec<OsBrowserVisitCount>> {
     let start_time = Utc::now().naive_utc() - Duration::days(7);
 
     let sql = "
         SELECT os, browser, COUNT(*) AS count
         FROM collector
-        WHERE collector.created_at > ?
+        WHERE collector.created_at > ?1
+          AND (?2 IS NULL OR collector.site_id = ?2)
+          AND (?3 OR collector.is_bot = 0)
           AND os IS NOT NULL
           AND browser IS NOT NULL
         GROUP BY os, browser
@@ -279,6 +857,8 @@ pub async fn os_browsers(conn: &DbConn) -> QueryResult<Vec<OsBrowserVisitCount>>
         .run(move |c| {
             sql_query(sql)
                 .bind::<Timestamp, _>(start_time)
+                .bind::<Nullable<Text>, _>(filter.site.clone())
+                .bind::<Bool, _>(filter.include_bots())
                 .load::<OsBrowserVisitCount>(c)
         })
         .await
@@ -295,14 +875,23 @@ pub async fn os_browsers(conn: &DbConn) -> QueryResult<Vec<OsBrowserVisitCount>>
 pub struct ReferrerCount {
     #[diesel(sql_type = Text)]
     domain: String,
+    #[diesel(sql_type = Nullable<Text>)]
+    channel: Option<String>,
     #[diesel(sql_type = BigInt)]
     count: i64,
+    #[diesel(sql_type = BigInt)]
+    sessions: i64,
 }
 
 /// `referrers`
 /// Retrieves statistics on the top 25 referring domains that brought visitors to the site over the past 7 days.
 ///
+/// Referrers are grouped by registrable domain (`www.google.com/search` and `google.com`
+/// are both `google.com`) and channel, and internal ones are left out. Events recorded before
+/// referrers were parsed fall back to their raw referrer.
+///
 /// ## Arguments
+/// * `filter` - Scope of the data to summarize.
 /// * `conn` - A database connection.
 ///
 /// ## Errors
@@ -310,19 +899,26 @@ pub struct ReferrerCount {
 ///
 /// ## Returns
 /// `QueryResult<Vec<ReferrerCount>>` containing the top 25 most used browsers.
-pub async fn referrers(conn: &DbConn) -> QueryResult<Vec<ReferrerCount>> {
+pub async fn referrers(filter: StatsFilter, conn: &DbConn) -> QueryResult<Vec<ReferrerCount>> {
     let start_time = Utc::now().naive_utc() - Duration::days(7);
 
     let sql = "
         SELECT
             CASE
+                WHEN event.channel IS NOT NULL THEN COALESCE(event.referrer_domain, 'direct')
                 WHEN referrer IS NULL OR referrer = '' THEN 'direct'
                 ELSE COALESCE(NULLIF(SUBSTR(referrer, INSTR(referrer, '//') + 2), ''), referrer)
                 END AS domain,
-            COUNT(*) AS count
+            event.channel,
+            COUNT(*) AS count,
+            COUNT(DISTINCT event.collector_id) AS sessions
         FROM event
-        WHERE event.created_at > ?
-        GROUP BY domain
+        JOIN collector ON collector.id = event.collector_id
+        WHERE event.created_at > ?1
+          AND (?2 IS NULL OR collector.site_id = ?2)
+          AND (?3 OR collector.is_bot = 0)
+          AND event.channel IS NOT 'internal'
+        GROUP BY domain, event.channel
         ORDER BY count DESC
         LIMIT 25;
     ";
@@ -331,6 +927,8 @@ pub async fn referrers(conn: &DbConn) -> QueryResult<Vec<ReferrerCount>> {
         .run(move |c| {
             sql_query(sql)
                 .bind::<Timestamp, _>(start_time)
+                .bind::<Nullable<Text>, _>(filter.site.clone())
+                .bind::<Bool, _>(filter.include_bots())
                 .load::<ReferrerCount>(c)
         })
         .await
@@ -343,6 +941,77 @@ pub async fn referrers(conn: &DbConn) -> QueryResult<Vec<ReferrerCount>> {
     }
 }
 
+#[derive(Serialize, Deserialize, QueryableByName)]
+pub struct ChannelCount {
+    #[diesel(sql_type = Text)]
+    pub channel: String,
+    #[diesel(sql_type = BigInt)]
+    pub sessions: i64,
+    #[diesel(sql_type = BigInt)]
+    pub visitors: i64,
+}
+
+/// # `channels`
+/// Retrieves the sessions and unique visitors of each channel over the past 7 days.
+///
+/// A session belongs to the channel of its first event. Sessions recorded before
+/// referrers were classified are `direct` or `referral`, depending on their referrer.
+///
+/// ## Arguments
+/// * `filter` - Scope of the data to summarize.
+/// * `conn` - A database connection.
+///
+/// ## Errors
+/// * `Error::NotFound` - If the query fails.
+///
+/// ## Returns
+/// `QueryResult<Vec<ChannelCount>>` containing the sessions of each channel, most used first.
+pub async fn channels(filter: StatsFilter, conn: &DbConn) -> QueryResult<Vec<ChannelCount>> {
+    let start_time = Utc::now().naive_utc() - Duration::days(7);
+
+    let sql = "
+        WITH entries AS (
+            SELECT COALESCE(collector.visitor_id, collector.id) AS visitor,
+                   COALESCE(event.channel,
+                       CASE WHEN event.referrer IS NULL OR event.referrer = ''
+                            THEN 'direct' ELSE 'referral' END) AS channel,
+                   ROW_NUMBER() OVER (
+                       PARTITION BY event.collector_id
+                       ORDER BY event.created_at, event.id
+                   ) AS entry
+            FROM event
+            JOIN collector ON collector.id = event.collector_id
+            WHERE event.created_at > ?1
+              AND (?2 IS NULL OR collector.site_id = ?2)
+              AND (?3 OR collector.is_bot = 0)
+        )
+        SELECT channel,
+               COUNT(*) AS sessions,
+               COUNT(DISTINCT visitor) AS visitors
+        FROM entries
+        WHERE entry = 1
+        GROUP BY channel
+        ORDER BY sessions DESC;
+    ";
+
+    match conn
+        .run(move |c| {
+            sql_query(sql)
+                .bind::<Timestamp, _>(start_time)
+                .bind::<Nullable<Text>, _>(filter.site.clone())
+                .bind::<Bool, _>(filter.include_bots())
+                .load::<ChannelCount>(c)
+        })
+        .await
+    {
+        Ok(query) => Ok(query),
+        Err(e) => {
+            eprintln!("Failed to load channels: {e}");
+            Err(Error::NotFound)
+        }
+    }
+}
+
 #[derive(QueryableByName, Serialize, Deserialize, Debug)]
 pub struct HourlyEventCounts {
     #[diesel(sql_type = Integer)]
@@ -351,12 +1020,15 @@ pub struct HourlyEventCounts {
     pub hour: i32, // Hour of the day (0-23)
     #[diesel(sql_type = BigInt)]
     pub count: i64, // The count of events in that hour
+    #[diesel(sql_type = BigInt)]
+    pub unique_visitors: i64, // The count of distinct visitors in that hour
 }
 
 /// # `weekly`
 /// Retrieves event counts grouped by day of week and hour of day for the past 7 days, enabling time-based traffic pattern analysis.
 ///
 /// ## Arguments
+/// * `filter` - Scope of the data to summarize.
 /// * `conn` - A database connection.
 ///
 /// ## Errors
@@ -364,17 +1036,26 @@ pub struct HourlyEventCounts {
 ///
 /// ## Returns
 /// `QueryResult<Vec<HourlyEventCounts>>` containing event counts grouped by day of week and hour of day.
-pub async fn weekly(conn: &DbConn) -> QueryResult<Vec<HourlyEventCounts>> {
+pub async fn weekly(filter: StatsFilter, conn: &DbConn) -> QueryResult<Vec<HourlyEventCounts>> {
     let sql = "\
-SELECT CAST(STRFTIME('%w', created_at) AS INTEGER) AS day,
-        CAST(STRFTIME('%H', created_at) AS INTEGER) AS hour,
-        COUNT(*)                                    AS count
+SELECT CAST(STRFTIME('%w', event.created_at) AS INTEGER) AS day,
+        CAST(STRFTIME('%H', event.created_at) AS INTEGER) AS hour,
+        COUNT(*)                                          AS count,
+        COUNT(DISTINCT collector.visitor_id)              AS unique_visitors
 FROM event
-WHERE created_at >= DATETIME('now', '-7 days')
+JOIN collector ON collector.id = event.collector_id
+WHERE event.created_at >= DATETIME('now', '-7 days')
+  AND (?1 IS NULL OR collector.site_id = ?1)
+  AND (?2 OR collector.is_bot = 0)
 GROUP BY day, hour;";
 
     match conn
-        .run(move |c| sql_query(sql).load::<HourlyEventCounts>(c))
+        .run(move |c| {
+            sql_query(sql)
+                .bind::<Nullable<Text>, _>(filter.site.clone())
+                .bind::<Bool, _>(filter.include_bots())
+                .load::<HourlyEventCounts>(c)
+        })
         .await
     {
         Ok(query) => Ok(query),
@@ -394,6 +1075,7 @@ struct TrafficChange {
 /// Calculates percentage changes in traffic volume between current and previous time periods (day, week, month) to show growth or decline trends.
 ///
 /// ## Arguments
+/// * `filter` - Scope of the data to summarize.
 /// * `conn` - A database connection.
 ///
 /// ## Errors
@@ -401,7 +1083,7 @@ struct TrafficChange {
 ///
 /// ## Returns
 /// `QueryResult<serde_json::Value>` containing percentage changes in traffic volume.
-pub async fn percentages(conn: &DbConn) -> QueryResult<serde_json::Value> {
+pub async fn percentages(filter: StatsFilter, conn: &DbConn) -> QueryResult<serde_json::Value> {
     // Suppress Clippy warning about casting i64 to f64, which is acceptable
     // in this context because we're calculating percentages for website traffic,
     // where the values are unlikely to be so large that precision loss is significant.
@@ -425,12 +1107,19 @@ pub async fn percentages(conn: &DbConn) -> QueryResult<serde_json::Value> {
     for (label, current_interval, previous_interval) in intervals {
         let query = format!(
             "SELECT \
-                (SELECT COUNT(*) FROM event WHERE created_at >= datetime('now', '{current_interval}')) AS current_count, \
-                (SELECT COUNT(*) FROM event WHERE created_at BETWEEN datetime('now', '{previous_interval}') AND datetime('now', '{current_interval}')) AS previous_count",
+                (SELECT COUNT(*) FROM event JOIN collector ON collector.id = event.collector_id WHERE event.created_at >= datetime('now', '{current_interval}') AND (?1 IS NULL OR collector.site_id = ?1) AND (?2 OR collector.is_bot = 0)) AS current_count, \
+                (SELECT COUNT(*) FROM event JOIN collector ON collector.id = event.collector_id WHERE event.created_at BETWEEN datetime('now', '{previous_interval}') AND datetime('now', '{current_interval}') AND (?1 IS NULL OR collector.site_id = ?1) AND (?2 OR collector.is_bot = 0)) AS previous_count",
         );
 
+        let site = filter.site.clone();
+        let include_bots = filter.include_bots();
         let result = conn
-            .run(move |c| sql_query(query).load::<TrafficChange>(c))
+            .run(move |c| {
+                sql_query(query)
+                    .bind::<Nullable<Text>, _>(site)
+                    .bind::<Bool, _>(include_bots)
+                    .load::<TrafficChange>(c)
+            })
             .await?;
 
         let change = if let Some(tc) = result.first() {
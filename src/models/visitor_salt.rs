@@ -0,0 +1,186 @@
+use std::net::IpAddr;
+
+use chrono::{NaiveDate, NaiveDateTime, Utc};
+use diesel::{
+    Connection, ExpressionMethods, OptionalExtension, QueryDsl, QueryResult, RunQueryDsl,
+    prelude::{Insertable, Queryable},
+};
+use rand::{RngCore, rngs::OsRng};
+use sha2::{Digest, Sha256};
+
+use crate::{DbConn, schema::visitor_salt, services::hex};
+
+/// Size of a salt, in bytes
+const SALT_LENGTH: usize = 32;
+
+/// # `VisitorSalt`
+/// Random value mixed into visitor IDs for a single day.
+///
+/// Only today's salt is ever kept: once it is deleted, yesterday's visitor IDs can no
+/// longer be recomputed from an IP address and a user agent, nor linked to today's.
+#[derive(Queryable, Insertable, Debug, Clone)]
+#[diesel(table_name = visitor_salt)]
+pub struct VisitorSalt {
+    pub day: NaiveDate,
+    pub salt: String,
+    pub created_at: Option<NaiveDateTime>,
+}
+
+impl VisitorSalt {
+    /// # `today`
+    /// Returns the salt of the current UTC day, creating it and discarding older ones
+    /// when the day changes.
+    ///
+    /// ## Arguments
+    /// * `conn` - Database connection
+    ///
+    /// ## Errors
+    /// If the salt cannot be read or written.
+    ///
+    /// ## Returns
+    /// * `QueryResult<String>` - Today's salt
+    pub async fn today(conn: &DbConn) -> QueryResult<String> {
+        let today = Utc::now().date_naive();
+
+        conn.run(move |c| {
+            let current = visitor_salt::table
+                .find(today)
+                .select(visitor_salt::salt)
+                .first(c)
+                .optional()?;
+            if let Some(salt) = current {
+                return Ok(salt);
+            }
+
+            c.transaction(|c| {
+                diesel::delete(visitor_salt::table.filter(visitor_salt::day.ne(today)))
+                    .execute(c)?;
+
+                // Another request may have created it in the meantime, theirs wins
+                diesel::insert_or_ignore_into(visitor_salt::table)
+                    .values(&VisitorSalt {
+                        day: today,
+                        salt: Self::generate(),
+                        created_at: None,
+                    })
+                    .execute(c)?;
+
+                visitor_salt::table
+                    .find(today)
+                    .select(visitor_salt::salt)
+                    .first(c)
+            })
+        })
+        .await
+    }
+
+    /// # `generate`
+    /// Generates a new random salt. Salts that are never stored, such as those of past
+    /// days when importing access logs, make visitor IDs that can never be recomputed.
+    ///
+    /// ## Returns
+    /// * `String` - The salt, hex encoded
+    #[must_use]
+    pub fn generate() -> String {
+        let mut salt = [0u8; SALT_LENGTH];
+        OsRng.fill_bytes(&mut salt);
+
+        hex::encode(&salt)
+    }
+
+    /// # `visitor_id`
+    /// Computes the anonymous ID of a visitor, as `sha256(salt, site, ip, user agent)`.
+    /// The same person gets the same ID for the whole day, and a new unrelated one the next.
+    ///
+    /// ## Arguments
+    /// * `salt` - Today's salt
+    /// * `site_id` - The site being visited, if any
+    /// * `ip` - The IP address of the visitor
+    /// * `user_agent` - The raw `User-Agent` header of the visitor
+    ///
+    /// ## Returns
+    /// * `String` - The visitor ID, hex encoded
+    #[must_use]
+    pub fn visitor_id(salt: &str, site_id: Option<&str>, ip: IpAddr, user_agent: &str) -> String {
+        let mut hasher = Sha256::new();
+
+        for part in [
+            salt,
+            site_id.unwrap_or_default(),
+            &ip.to_string(),
+            user_agent,
+        ] {
+            hasher.update(part.as_bytes());
+            hasher.update([0]);
+        }
+
+        hex::encode(&hasher.finalize())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support;
+    use chrono::Duration;
+
+    const USER_AGENT: &str =
+        "Mozilla/5.0 (X11; Linux x86_64; rv:128.0) Gecko/20100101 Firefox/128.0";
+
+    fn id(salt: &str, site_id: Option<&str>, ip: &str, user_agent: &str) -> String {
+        VisitorSalt::visitor_id(salt, site_id, ip.parse().expect("valid IP"), user_agent)
+    }
+
+    #[test]
+    fn visitor_ids_depend_on_every_part() {
+        let salt = VisitorSalt::generate();
+        let visitor = id(&salt, Some("site"), "192.0.2.1", USER_AGENT);
+
+        assert_eq!(visitor.len(), 64);
+        assert_eq!(visitor, id(&salt, Some("site"), "192.0.2.1", USER_AGENT));
+        for other in [
+            id(&salt, Some("other"), "192.0.2.1", USER_AGENT),
+            id(&salt, None, "192.0.2.1", USER_AGENT),
+            id(&salt, Some("site"), "192.0.2.2", USER_AGENT),
+            id(&salt, Some("site"), "192.0.2.1", "curl/8.0"),
+            id(
+                &VisitorSalt::generate(),
+                Some("site"),
+                "192.0.2.1",
+                USER_AGENT,
+            ),
+        ] {
+            assert_ne!(visitor, other);
+        }
+    }
+
+    #[rocket::async_test]
+    async fn salt_is_stable_within_a_day_and_rotates() {
+        let rocket = test_support::rocket().ignite().await.expect("valid rocket");
+        let conn = DbConn::get_one(&rocket).await.expect("database connection");
+
+        let salt = VisitorSalt::today(&conn).await.expect("today's salt");
+        assert_eq!(salt, VisitorSalt::today(&conn).await.expect("today's salt"));
+        let visitor = id(&salt, None, "192.0.2.1", USER_AGENT);
+
+        // Pretend the salt was created yesterday: the next call must replace it
+        let yesterday = Utc::now().date_naive() - Duration::days(1);
+        conn.run(move |c| {
+            diesel::update(visitor_salt::table)
+                .set(visitor_salt::day.eq(yesterday))
+                .execute(c)
+        })
+        .await
+        .expect("salt moved to yesterday");
+
+        let rotated = VisitorSalt::today(&conn).await.expect("today's salt");
+        assert_ne!(salt, rotated);
+        assert_ne!(visitor, id(&rotated, None, "192.0.2.1", USER_AGENT));
+
+        let kept: i64 = conn
+            .run(|c| visitor_salt::table.count().get_result(c))
+            .await
+            .expect("salt count");
+        assert_eq!(kept, 1);
+    }
+}
@@ -0,0 +1,236 @@
+use std::{collections::BTreeMap, fmt::Display, str::FromStr};
+
+use chrono::NaiveDateTime;
+use diesel::{
+    Connection, QueryResult, RunQueryDsl,
+    prelude::{Insertable, Queryable},
+};
+use serde::{Deserialize, Serialize};
+use ulid::Ulid;
+
+use crate::{DbConn, schema::vital};
+
+/// # `VitalName`
+/// Core Web Vitals, and the other page load metrics reported alongside them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum VitalName {
+    /// Largest Contentful Paint, in milliseconds
+    Lcp,
+    /// Cumulative Layout Shift, unitless
+    Cls,
+    /// Interaction to Next Paint, in milliseconds
+    Inp,
+    /// First Contentful Paint, in milliseconds
+    Fcp,
+    /// Time To First Byte, in milliseconds
+    Ttfb,
+}
+
+impl VitalName {
+    #[must_use]
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            VitalName::Lcp => "LCP",
+            VitalName::Cls => "CLS",
+            VitalName::Inp => "INP",
+            VitalName::Fcp => "FCP",
+            VitalName::Ttfb => "TTFB",
+        }
+    }
+
+    /// # `max_value`
+    /// Returns the largest value accepted for the metric, anything above is bogus.
+    #[must_use]
+    pub fn max_value(&self) -> f64 {
+        match self {
+            VitalName::Cls => 100.0,
+            VitalName::Lcp | VitalName::Inp | VitalName::Fcp | VitalName::Ttfb => 600_000.0,
+        }
+    }
+}
+
+impl Display for VitalName {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+impl FromStr for VitalName {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "LCP" => Ok(VitalName::Lcp),
+            "CLS" => Ok(VitalName::Cls),
+            "INP" => Ok(VitalName::Inp),
+            "FCP" => Ok(VitalName::Fcp),
+            "TTFB" => Ok(VitalName::Ttfb),
+            other => Err(format!("Unknown vital: {other}")),
+        }
+    }
+}
+
+#[derive(Queryable, Insertable, Serialize, Deserialize, Debug)]
+#[diesel(table_name = vital)]
+#[serde(crate = "rocket::serde")]
+pub struct Vital {
+    pub id: String,
+    pub collector_id: String,
+    pub url: String,
+    pub name: String,
+    pub value: f64,
+    pub created_at: Option<NaiveDateTime>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct VitalsQuery {
+    pub url: String,
+    pub collector_id: String,
+    /// Signed collector token handed out by `POST /session`
+    #[serde(default)]
+    pub token: Option<String>,
+    #[serde(default)]
+    pub site_id: Option<String>,
+    /// Measured values by metric name, e.g. `{"LCP": 1840, "CLS": 0.02}`
+    pub metrics: BTreeMap<String, f64>,
+}
+
+impl VitalsQuery {
+    /// # `into_vitals`
+    /// Checks the reported metrics and turns them into one `Vital` each.
+    ///
+    /// ## Arguments
+    /// * `url` - The cleaned URL of the page the metrics were measured on
+    ///
+    /// ## Errors
+    /// * `String` - Why the metrics are rejected
+    ///
+    /// ## Returns
+    /// * `Result<Vec<Vital>, String>` - The vitals to store
+    pub fn into_vitals(self, url: &str) -> Result<Vec<Vital>, String> {
+        if self.metrics.is_empty() {
+            return Err("No metrics reported".to_string());
+        }
+
+        self.metrics
+            .into_iter()
+            .map(|(name, value)| {
+                let name = name.parse::<VitalName>()?;
+                if !value.is_finite() || value < 0.0 || value > name.max_value() {
+                    return Err(format!("{name} must be between 0 and {}", name.max_value()));
+                }
+
+                Ok(Vital {
+                    id: Ulid::new().to_string(),
+                    collector_id: self.collector_id.clone(),
+                    url: url.to_string(),
+                    name: name.to_string(),
+                    value,
+                    created_at: None,
+                })
+            })
+            .collect()
+    }
+}
+
+impl Vital {
+    /// # `insert_batch`
+    /// Inserts the vitals of a page in a single transaction.
+    ///
+    /// ## Arguments
+    /// * `vitals` - The vitals to insert
+    /// * `conn` - Database connection
+    ///
+    /// ## Errors
+    /// If any of the vitals cannot be inserted, in which case none is.
+    ///
+    /// ## Returns
+    /// * `QueryResult<usize>` - The number of vitals inserted
+    pub async fn insert_batch(vitals: Vec<Vital>, conn: &DbConn) -> QueryResult<usize> {
+        conn.run(move |c| {
+            c.transaction(|c| {
+                for vital in &vitals {
+                    diesel::insert_into(vital::table).values(vital).execute(c)?;
+                }
+
+                Ok(vitals.len())
+            })
+        })
+        .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn vitals(metrics: &[(&str, f64)]) -> Result<Vec<Vital>, String> {
+        VitalsQuery {
+            url: "https://example.com/".to_string(),
+            collector_id: "01HX".to_string(),
+            token: None,
+            site_id: None,
+            metrics: metrics
+                .iter()
+                .map(|(name, value)| ((*name).to_string(), *value))
+                .collect(),
+        }
+        .into_vitals("/")
+    }
+
+    #[test]
+    fn validates_metric_names() {
+        let cases = [
+            ("LCP", true),
+            ("CLS", true),
+            ("INP", true),
+            ("FCP", true),
+            ("TTFB", true),
+            ("lcp", false),
+            ("FID", false),
+            ("", false),
+        ];
+
+        for (name, valid) in cases {
+            assert_eq!(vitals(&[(name, 1.0)]).is_ok(), valid, "{name}");
+        }
+        assert!(vitals(&[]).is_err());
+        // One bad metric rejects the whole report
+        assert!(vitals(&[("LCP", 1840.0), ("FID", 12.0)]).is_err());
+    }
+
+    #[test]
+    fn validates_metric_values() {
+        let cases = [
+            ("LCP", 0.0, true),
+            ("LCP", 600_000.0, true),
+            ("LCP", 600_001.0, false),
+            ("LCP", -1.0, false),
+            ("TTFB", f64::NAN, false),
+            ("INP", f64::INFINITY, false),
+            ("FCP", f64::NEG_INFINITY, false),
+            // CLS is unitless and small, its bound is not the one of timings
+            ("CLS", 0.0213, true),
+            ("CLS", 100.0, true),
+            ("CLS", 150.0, false),
+            ("LCP", 150.0, true),
+            ("CLS", -0.01, false),
+        ];
+
+        for (name, value, valid) in cases {
+            assert_eq!(vitals(&[(name, value)]).is_ok(), valid, "{name} = {value}");
+        }
+    }
+
+    #[test]
+    fn keeps_reported_values() {
+        let stored = vitals(&[("CLS", 0.0213), ("LCP", 1840.0)]).expect("valid metrics");
+        let stored: Vec<_> = stored
+            .iter()
+            .map(|vital| (vital.name.as_str(), vital.value, vital.url.as_str()))
+            .collect();
+
+        // CLS keeps its fractional value, it is never scaled to an integer like timings
+        assert_eq!(stored, [("CLS", 0.0213, "/"), ("LCP", 1840.0, "/")]);
+    }
+}
@@ -0,0 +1,322 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use regex::Regex;
+use rocket::{
+    Data, Request, Response,
+    fairing::{Fairing, Info, Kind},
+    http::{ContentType, Header, Method, Status, uri::Origin},
+};
+use serde_json::json;
+
+use crate::{AppState, logger::Logger, metrics::Metrics};
+
+/// How many bytes of a body are read to find its collector, the most Rocket can peek.
+/// The tracking script sends `collector_id` first so that long URLs never push it out.
+const BODY_PEEK_BYTES: usize = 512;
+/// Number of buckets above which idle ones are dropped
+const MAX_BUCKETS: usize = 10_000;
+/// How long a bucket must stay untouched before being dropped
+const BUCKET_IDLE: Duration = Duration::from_secs(600);
+/// Path throttled requests are rerouted to, so that no handler runs for them
+const THROTTLED_PATH: &str = "/__rate_limited";
+
+/// # `RateLimits`
+/// Requests allowed per minute on each ingestion route, `0` disabling the limit.
+#[derive(Debug, Clone, Copy)]
+pub struct RateLimits {
+    /// `POST /session`, per client IP
+    pub session: u32,
+    /// `POST /event` and `POST /event/batch`, per client IP and per collector
+    pub event: u32,
+    /// `GET /pixel.gif`, per client IP
+    pub pixel: u32,
+    /// `POST /vitals`, per client IP and per collector
+    pub vitals: u32,
+    /// `POST /errors`, per client IP and per collector
+    pub errors: u32,
+}
+
+struct Bucket {
+    tokens: f64,
+    updated_at: Instant,
+}
+
+/// Retry delay, in seconds, stored on throttled requests
+struct Throttled(Option<u64>);
+
+/// # `RateLimiter`
+/// Throttles ingestion endpoints with token buckets keyed by client IP and collector ID.
+/// Throttled requests never reach their handler and get a `429` with `Retry-After`.
+pub struct RateLimiter {
+    limits: RateLimits,
+    buckets: Mutex<HashMap<String, Bucket>>,
+    collector_regex: Regex,
+}
+
+impl RateLimiter {
+    #[must_use]
+    pub fn new(limits: RateLimits) -> Self {
+        Self {
+            limits,
+            buckets: Mutex::new(HashMap::new()),
+            collector_regex: Regex::new(r#""collector_id"\s*:\s*"([^"]+)""#)
+                .expect("Invalid regex pattern"),
+        }
+    }
+
+    /// # `take`
+    /// Takes a token from a bucket holding up to `per_minute` tokens, refilled continuously.
+    ///
+    /// ## Arguments
+    /// * `key` - The bucket
+    /// * `per_minute` - The limit of the route
+    ///
+    /// ## Returns
+    /// * `Option<u64>` - `None` if the request is allowed, otherwise the seconds until it would be
+    fn take(&self, key: String, per_minute: u32) -> Option<u64> {
+        if per_minute == 0 {
+            return None;
+        }
+
+        let capacity = f64::from(per_minute);
+        let refill_per_second = capacity / 60.0;
+        let now = Instant::now();
+        let mut buckets = self
+            .buckets
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner);
+
+        if buckets.len() > MAX_BUCKETS {
+            buckets.retain(|_, bucket| now.duration_since(bucket.updated_at) < BUCKET_IDLE);
+        }
+
+        let bucket = buckets.entry(key).or_insert(Bucket {
+            tokens: capacity,
+            updated_at: now,
+        });
+        let elapsed = now.duration_since(bucket.updated_at).as_secs_f64();
+        bucket.tokens = (bucket.tokens + elapsed * refill_per_second).min(capacity);
+        bucket.updated_at = now;
+
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            None
+        } else {
+            #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+            Some(((1.0 - bucket.tokens) / refill_per_second).ceil() as u64)
+        }
+    }
+}
+
+#[rocket::async_trait]
+impl Fairing for RateLimiter {
+    fn info(&self) -> Info {
+        Info {
+            name: "Rate Limiter",
+            kind: Kind::Request | Kind::Response,
+        }
+    }
+
+    async fn on_request(&self, request: &mut Request<'_>, data: &mut Data<'_>) {
+        let path = request
+            .uri()
+            .path()
+            .as_str()
+            .trim_end_matches('/')
+            .to_string();
+        let (route, per_minute) = match (request.method(), path.as_str()) {
+            (Method::Post, "/session") => ("session", self.limits.session),
+            (Method::Get, "/pixel.gif") => ("pixel", self.limits.pixel),
+            (Method::Post, "/event" | "/event/batch") => ("event", self.limits.event),
+            (Method::Post, "/vitals") => ("vitals", self.limits.vitals),
+            (Method::Post, "/errors") => ("errors", self.limits.errors),
+            _ => return,
+        };
+        let ip = request
+            .client_ip()
+            .map_or_else(|| "unknown".to_string(), |ip| ip.to_string());
+
+        let mut throttled = self
+            .take(format!("ip:{route}:{ip}"), per_minute)
+            .map(|retry_after| (retry_after, "ip"));
+
+        if throttled.is_none() && matches!(route, "event" | "vitals" | "errors") {
+            let peeked = data.peek(BODY_PEEK_BYTES).await;
+            let collector_id = self
+                .collector_regex
+                .captures(&String::from_utf8_lossy(peeked))
+                .map(|captures| captures[1].to_string());
+
+            if let Some(collector_id) = collector_id {
+                throttled = self
+                    .take(format!("collector:{route}:{collector_id}"), per_minute)
+                    .map(|retry_after| (retry_after, "collector"));
+            }
+        }
+
+        let Some((retry_after, key)) = throttled else {
+            return;
+        };
+
+        Logger::warn(
+            "RateLimiter",
+            &format!("Throttled {route} request from {ip} by {key}"),
+        );
+        if let Some(state) = request.rocket().state::<Arc<AppState>>() {
+            Metrics::increment(match key {
+                "ip" => &state.metrics.rate_limited_ip,
+                _ => &state.metrics.rate_limited_collector,
+            });
+        }
+
+        request.local_cache(|| Throttled(Some(retry_after)));
+        request.set_method(Method::Get);
+        request.set_uri(Origin::path_only(THROTTLED_PATH));
+    }
+
+    async fn on_response<'r>(&self, request: &'r Request<'_>, response: &mut Response<'r>) {
+        let Throttled(Some(retry_after)) = request.local_cache(|| Throttled(None)) else {
+            return;
+        };
+
+        let body = json!({
+            "status": Status::TooManyRequests.code,
+            "success": false,
+            "error": {
+                "message": format!("Too many requests, retry in {retry_after} seconds")
+            }
+        })
+        .to_string();
+
+        response.set_status(Status::TooManyRequests);
+        response.set_header(ContentType::JSON);
+        response.set_header(Header::new("Retry-After", retry_after.to_string()));
+        response.set_sized_body(body.len(), std::io::Cursor::new(body));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rocket::{figment::Figment, local::asynchronous::Client};
+    use std::net::SocketAddr;
+
+    fn limiter() -> RateLimiter {
+        RateLimiter::new(RateLimits {
+            session: 30,
+            event: 120,
+            pixel: 30,
+            vitals: 60,
+            errors: 60,
+        })
+    }
+
+    #[test]
+    fn allows_bursts_up_to_the_limit() {
+        let limiter = limiter();
+
+        for _ in 0..30 {
+            assert_eq!(limiter.take("ip:session:1".to_string(), 30), None);
+        }
+        // 30 per minute refill one token every 2 seconds
+        assert_eq!(limiter.take("ip:session:1".to_string(), 30), Some(2));
+        assert_eq!(limiter.take("ip:session:1".to_string(), 30), Some(2));
+        // Other buckets are untouched
+        assert_eq!(limiter.take("ip:session:2".to_string(), 30), None);
+    }
+
+    #[test]
+    fn refills_over_time() {
+        let limiter = limiter();
+        for _ in 0..3 {
+            limiter.take("ip:event:1".to_string(), 3);
+        }
+        assert_eq!(limiter.take("ip:event:1".to_string(), 3), Some(20));
+
+        // Rewind the bucket by 40 seconds: two tokens are back
+        let rewind = |limiter: &RateLimiter| {
+            let mut buckets = limiter.buckets.lock().unwrap();
+            let bucket = buckets.get_mut("ip:event:1").unwrap();
+            bucket.updated_at -= Duration::from_secs(40);
+        };
+        rewind(&limiter);
+        assert_eq!(limiter.take("ip:event:1".to_string(), 3), None);
+        assert_eq!(limiter.take("ip:event:1".to_string(), 3), None);
+        assert!(limiter.take("ip:event:1".to_string(), 3).is_some());
+
+        // The bucket never holds more than its capacity
+        for _ in 0..10 {
+            rewind(&limiter);
+        }
+        for _ in 0..3 {
+            assert_eq!(limiter.take("ip:event:1".to_string(), 3), None);
+        }
+        assert!(limiter.take("ip:event:1".to_string(), 3).is_some());
+    }
+
+    #[test]
+    fn zero_disables_the_limit() {
+        let limiter = limiter();
+
+        for _ in 0..1000 {
+            assert_eq!(limiter.take("ip:event:1".to_string(), 0), None);
+        }
+    }
+
+    #[test]
+    fn finds_the_collector_of_events() {
+        let limiter = limiter();
+        let collector = |body: &str| {
+            limiter
+                .collector_regex
+                .captures(body)
+                .map(|captures| captures[1].to_string())
+        };
+
+        assert_eq!(
+            collector(r#"{"name":"enter","collector_id" : "01HX","url":"/"}"#).as_deref(),
+            Some("01HX")
+        );
+        assert_eq!(
+            collector(r#"[{"collector_id":"01HX"},{"collector_id":"01HY"}]"#).as_deref(),
+            Some("01HX")
+        );
+        assert_eq!(collector(r#"{"name":"enter"}"#), None);
+    }
+
+    #[rocket::async_test]
+    async fn throttles_collectors_behind_long_urls() {
+        let figment = Figment::from(rocket::Config::debug_default()).merge(("log_level", "off"));
+        let rocket = rocket::custom(figment).attach(RateLimiter::new(RateLimits {
+            session: 0,
+            event: 1,
+            pixel: 0,
+            vitals: 0,
+            errors: 0,
+        }));
+        let client = Client::tracked(rocket).await.expect("valid rocket");
+
+        // Shaped like the batches of the tracking script, the URL alone exceeds the peek
+        let url = format!("https://example.com/{}", "a".repeat(800));
+        let body =
+            format!(r#"[{{"collector_id":"01HX","token":"token","name":"enter","url":"{url}"}}]"#);
+
+        let mut statuses = Vec::new();
+        for remote in ["192.0.2.1:1000", "192.0.2.2:1000"] {
+            let response = client
+                .post("/event/batch")
+                .remote(remote.parse::<SocketAddr>().expect("valid address"))
+                .body(&body)
+                .dispatch()
+                .await;
+            statuses.push(response.status());
+        }
+
+        // Nothing is mounted: allowed requests get a 404, the second visitor shares the collector
+        assert_eq!(statuses, [Status::NotFound, Status::TooManyRequests]);
+    }
+}
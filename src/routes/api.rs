@@ -0,0 +1,208 @@
+use std::{net::IpAddr, sync::Arc};
+
+use chrono::{Duration, Utc};
+use rocket::{State, http::Status, post, serde::json::Json};
+use serde::Deserialize;
+use serde_json::{Map, Value, json};
+
+use crate::{
+    AppState, BearerToken, DbConn, UserAgentInfo,
+    api_response::ApiResponse,
+    models::{ApiKey, Collector, CollectorSource, Event, EventQuery, VisitorSalt},
+    routes::event::{build_event, find_site},
+    services::{session::build_collector, tracker_event::validate_event},
+};
+
+/// Server-side events join the collector the same visitor used within this many minutes
+const VISITOR_SESSION_MINUTES: i64 = 30;
+
+#[derive(Deserialize, Debug)]
+pub struct TrackQuery {
+    /// Defaults to the site of the API key
+    #[serde(default)]
+    pub site_id: Option<String>,
+    pub name: String,
+    pub url: String,
+    #[serde(default)]
+    pub referrer: Option<String>,
+    #[serde(default)]
+    pub props: Option<Map<String, Value>>,
+    /// IP address of the visitor, used to locate them and tell them apart
+    #[serde(default)]
+    pub ip: Option<IpAddr>,
+    /// `User-Agent` of the visitor
+    #[serde(default)]
+    pub user_agent: Option<String>,
+    /// Collector of the visitor's browser session, to attach the event to
+    #[serde(default)]
+    pub collector_id: Option<String>,
+}
+
+/// # `api_track`
+/// Handles POST requests from backends to record events the browser never sees, such as
+/// a confirmed payment or a received webhook.
+///
+/// The event is attached to the given collector, else to the collector the same visitor
+/// (same IP address and user agent) used in the last 30 minutes, else to a new one.
+///
+/// ## Arguments
+/// * `track_data` - Event data from request
+/// * `bearer` - The API key, sent as `Authorization: Bearer <key>`
+/// * `state` - The application state
+/// * `conn` - Database connection
+///
+/// ## Returns
+/// * `Json<Value>` - JSON response containing the ID of the event and its collector
+#[post("/track", format = "application/json", data = "<track_data>")]
+pub async fn api_track(
+    track_data: Json<TrackQuery>,
+    bearer: BearerToken,
+    state: &State<Arc<AppState>>,
+    conn: DbConn,
+) -> Json<Value> {
+    let Some(secret) = bearer.0 else {
+        return ApiResponse::error(Status::Unauthorized, "Missing API key");
+    };
+    let key = match ApiKey::authenticate(secret, &conn).await {
+        Ok(Some(key)) => key,
+        Ok(None) => return ApiResponse::error(Status::Unauthorized, "Invalid API key"),
+        Err(e) => return ApiResponse::internal_error(&format!("Failed to check API key: {e}")),
+    };
+
+    let track_data = track_data.into_inner();
+    if let Some(site_id) = &track_data.site_id
+        && *site_id != key.site_id
+    {
+        return ApiResponse::error(
+            Status::Forbidden,
+            &format!("API key is not valid for site {site_id}"),
+        );
+    }
+
+    let mut event_data = EventQuery {
+        url: track_data.url,
+        referrer: track_data.referrer,
+        name: track_data.name,
+        collector_id: String::new(),
+        token: None,
+        site_id: Some(key.site_id.clone()),
+        webdriver: false,
+        props: track_data.props,
+        engagement_ms: None,
+    };
+    if let Err(message) = event_data.validate_props() {
+        return ApiResponse::bad_request(&message);
+    }
+    if let Err(message) = validate_event(&event_data) {
+        return ApiResponse::bad_request(&message);
+    }
+
+    let site = match find_site(Some(&key.site_id), &conn).await {
+        Ok(site) => site,
+        Err(response) => return response,
+    };
+
+    let (collector_id, attached) = match track_data.collector_id {
+        Some(collector_id) => match Collector::find(collector_id.clone(), &conn).await {
+            Ok(Some(collector)) if collector.site_id.as_ref() == Some(&key.site_id) => {
+                (collector.id, true)
+            }
+            Ok(Some(_)) => {
+                return ApiResponse::error(
+                    Status::Forbidden,
+                    &format!("Collector {collector_id} belongs to another site"),
+                );
+            }
+            Ok(None) => {
+                return ApiResponse::not_found(&format!("Unknown collector {collector_id}"));
+            }
+            Err(e) => {
+                return ApiResponse::internal_error(&format!("Failed to check collector: {e}"));
+            }
+        },
+        None => {
+            let user_agent_info = UserAgentInfo::relayed(
+                track_data.user_agent.as_deref().unwrap_or_default(),
+                &state.user_agent_parser,
+            );
+
+            // Look for the visitor's collector before locating them for a new one
+            let existing = match track_data.ip {
+                Some(ip) => {
+                    let salt = match VisitorSalt::today(&conn).await {
+                        Ok(salt) => salt,
+                        Err(e) => {
+                            return ApiResponse::internal_error(&format!(
+                                "Failed to get visitor salt: {e}"
+                            ));
+                        }
+                    };
+                    let visitor_id = VisitorSalt::visitor_id(
+                        &salt,
+                        Some(&key.site_id),
+                        ip,
+                        &user_agent_info.raw_user_agent,
+                    );
+                    let since = Utc::now().naive_utc() - Duration::minutes(VISITOR_SESSION_MINUTES);
+                    match Collector::latest_of_visitor(
+                        key.site_id.clone(),
+                        visitor_id,
+                        since,
+                        &conn,
+                    )
+                    .await
+                    {
+                        Ok(existing) => existing,
+                        Err(e) => {
+                            return ApiResponse::internal_error(&format!(
+                                "Failed to find collector: {e}"
+                            ));
+                        }
+                    }
+                }
+                None => None,
+            };
+
+            match existing {
+                Some(existing) => (existing.id, true),
+                None => {
+                    let collector = match build_collector(
+                        Some(key.site_id.clone()),
+                        track_data.ip,
+                        user_agent_info,
+                        false,
+                        CollectorSource::Api,
+                        state,
+                        &conn,
+                    )
+                    .await
+                    {
+                        Ok(collector) => collector,
+                        Err(e) => return ApiResponse::internal_error(&e.to_string()),
+                    };
+                    match Collector::insert(collector, &conn).await {
+                        Ok(collector_id) => (collector_id, false),
+                        Err(e) => {
+                            return ApiResponse::internal_error(&format!(
+                                "Failed to record collector: {e}"
+                            ));
+                        }
+                    }
+                }
+            }
+        }
+    };
+
+    event_data.collector_id.clone_from(&collector_id);
+    let event = build_event(event_data, site.as_ref(), state);
+
+    match Event::insert(event, &conn).await {
+        Ok(id) => ApiResponse::created(json!({
+            "message": &format!("Event #{id} recorded successfully"),
+            "id": id,
+            "collector_id": collector_id,
+            "attached": attached
+        })),
+        Err(e) => ApiResponse::internal_error(&format!("Failed to record event: {e}")),
+    }
+}
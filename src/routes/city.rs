@@ -1,12 +1,12 @@
 use crate::api_response::ApiResponse;
 use crate::{
-    DbConn,
-    models::{City, CityQuery},
+    AppState, DbConn,
+    models::{City, CityQuery, GeoCacheEntry},
     paginated::set_pagination_defaults,
 };
-use rocket::{get, post, serde::json::Json};
+use rocket::{State, get, post, serde::json::Json};
 use serde_json::{Value, json};
-use std::net::IpAddr;
+use std::{net::IpAddr, sync::Arc};
 
 /// # `city_insert`
 /// Handles POST requests to insert a new city.
@@ -14,12 +14,18 @@ use std::net::IpAddr;
 /// ## Arguments
 /// * `city_data` - city data from request
 /// * `ip` - IP address of the client
+/// * `state` - The application state
 /// * `conn` - Database connection
 ///
 /// ## Panics
 /// If the regex pattern is invalid.
 #[post("/", format = "application/json", data = "<city_data>")]
-pub async fn city_insert(city_data: Json<CityQuery>, ip: IpAddr, conn: DbConn) -> Json<Value> {
+pub async fn city_insert(
+    city_data: Json<CityQuery>,
+    ip: IpAddr,
+    state: &State<Arc<AppState>>,
+    conn: DbConn,
+) -> Json<Value> {
     let mut city: City = city_data.into_inner().into();
     city.name = city.name.to_lowercase();
     city.country = city.country.to_lowercase();
@@ -29,7 +35,24 @@ pub async fn city_insert(city_data: Json<CityQuery>, ip: IpAddr, conn: DbConn) -
 
     let new_id = match existing_city.unwrap() {
         Some(found) => Ok(found.id.unwrap_or_default()),
-        None => City::insert(city, ip, &conn).await,
+        None => {
+            // Default the coordinates to those of the client
+            if let Ok(location) = GeoCacheEntry::locate(
+                ip,
+                state.geo_locator.as_ref(),
+                state.geo_cache_ttl,
+                &state.metrics,
+                &conn,
+            )
+            .await
+            {
+                city.latitude = location.latitude;
+                city.longitude = location.longitude;
+                city.timezone = location.timezone;
+            }
+
+            City::insert(city, &conn).await
+        }
     };
 
     match new_id {
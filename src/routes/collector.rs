@@ -1,200 +1,188 @@
+use std::sync::Arc;
+
 use rocket::{
-    State, get,
-    http::Status,
-    response::content::{self},
+    Request, Response, State, get,
+    http::{ContentType, Header, Status},
+    request::{FromRequest, Outcome},
+    response::{self, Responder},
 };
-use std::net::{IpAddr, Ipv4Addr};
 
-use crate::{
-    AppState, DbConn, UserAgentInfo,
-    models::{City, Collector, CollectorQuery},
-};
+use crate::AppState;
+
+/// Cache lifetime of `/stats.js` when requested without a version
+const UNVERSIONED_MAX_AGE: u32 = 3600;
+/// Cache lifetime of `/stats.js?v=<version>`, which never changes
+const VERSIONED_MAX_AGE: u32 = 31_536_000;
+
+/// `If-None-Match` header of a request, if any
+pub struct IfNoneMatch(Option<String>);
+
+impl IfNoneMatch {
+    /// # `matches`
+    /// Checks whether the client already has the resource tagged `etag`. The header is a
+    /// comma separated list, compared weakly as RFC 9110 requires: `W/"x"` matches `"x"`,
+    /// and `*` matches anything.
+    ///
+    /// ## Arguments
+    /// * `etag` - The current `ETag` of the resource
+    ///
+    /// ## Returns
+    /// * `bool` - Whether a `304 Not Modified` can be sent
+    #[must_use]
+    pub fn matches(&self, etag: &str) -> bool {
+        let Some(header) = &self.0 else {
+            return false;
+        };
+        let etag = etag.trim_start_matches("W/");
+
+        header
+            .split(',')
+            .map(str::trim)
+            .any(|candidate| candidate == "*" || candidate.trim_start_matches("W/") == etag)
+    }
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for IfNoneMatch {
+    type Error = ();
+
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        Outcome::Success(IfNoneMatch(
+            request
+                .headers()
+                .get_one("If-None-Match")
+                .map(ToString::to_string),
+        ))
+    }
+}
+
+/// The tracking script with its caching headers, or a `304` when the client has it already.
+pub struct ScriptResponse {
+    body: Option<String>,
+    etag: String,
+    cache_control: String,
+}
 
-/// # `stats_js`
-/// Generates the JavaScript code for analytics tracking.
+impl<'r> Responder<'r, 'static> for ScriptResponse {
+    fn respond_to(self, _: &'r Request<'_>) -> response::Result<'static> {
+        let mut response = Response::build();
+        response
+            .header(ContentType::JavaScript)
+            .header(Header::new("ETag", self.etag))
+            .header(Header::new("Cache-Control", self.cache_control));
+
+        match self.body {
+            Some(body) => response.sized_body(body.len(), std::io::Cursor::new(body)),
+            None => response.status(Status::NotModified),
+        };
+
+        response.ok()
+    }
+}
+
+/// # `collector_stats_js`
+/// Serves the tracking script. It is static, so it is cached by browsers: versioned
+/// requests (`?v=<version>`) for a year as immutable, others for an hour. The site is
+/// read by the script itself from its URL, and visitors are registered by `POST /session`.
 ///
 /// ## Arguments
-/// * `ip` - The IP address of the visitor
+/// * `v` - The version of the script the page asks for, if any
+/// * `if_none_match` - The `ETag` of the copy cached by the client, if any
 /// * `state` - The application state
-/// * `user_agent_info` - The user agent information
-/// * `conn` - The database connection
-///
-/// ## Panics
-/// * If there is an error inserting the city into the database.
 ///
 /// ## Returns
-/// * `(Status, content::RawJavaScript<String>)`
-#[get("/")]
-pub async fn collector_stats_js(
-    ip: IpAddr,
-    state: &State<AppState>,
-    user_agent_info: UserAgentInfo,
-    conn: DbConn,
-) -> (Status, content::RawJavaScript<String>) {
-    let ip = if state.dev_mode {
-        IpAddr::V4(Ipv4Addr::new(215, 204, 222, 212))
+/// * `ScriptResponse` - The script, or `304 Not Modified`
+#[get("/?<v>")]
+pub fn collector_stats_js(
+    v: Option<String>,
+    if_none_match: IfNoneMatch,
+    state: &State<Arc<AppState>>,
+) -> ScriptResponse {
+    let script = &state.tracking_script;
+    let etag = script.etag();
+    let cache_control = if v.as_deref() == Some(script.version.as_str()) {
+        format!("public, max-age={VERSIONED_MAX_AGE}, immutable")
     } else {
-        ip
-    };
-
-    let city_to_create = City::from_ip(&ip.to_string()).await.unwrap_or_default();
-
-    let existing_city = City::find_by_name_and_country(
-        city_to_create.name.clone(),
-        city_to_create.country.clone(),
-        &conn,
-    )
-    .await;
-
-    let new_id_res = match existing_city.unwrap() {
-        Some(found) => Ok(found.id.unwrap_or_default()),
-        None => City::insert(city_to_create, ip, &conn).await,
+        format!("public, max-age={UNVERSIONED_MAX_AGE}")
     };
-
-    let new_id = match new_id_res {
-        Ok(id) => id,
-        Err(e) => {
-            panic!("Error inserting city: {e:?}");
-        }
+    let body = if if_none_match.matches(&etag) {
+        None
+    } else {
+        Some(script.body.clone())
     };
 
-    let collector_query = CollectorQuery {
-        origin: ip.to_string(),
-        city_id: new_id,
-        os: Some(user_agent_info.os),
-        browser: Some(user_agent_info.browser),
-    };
-    let collector: Collector = collector_query.into();
+    ScriptResponse {
+        body,
+        etag,
+        cache_control,
+    }
+}
 
-    match Collector::insert(collector, &conn).await {
-        Ok(collector_id) => {
-            let analytics_js = generate_analytics_js(&collector_id.to_string(), &state.address);
-            (Status::Ok, content::RawJavaScript(analytics_js))
-        }
-        Err(e) => {
-            eprintln!("Error inserting collector: {e:?}");
-
-            // Return a minimal fallback script with error details
-            let error_js = format!(
-                r#""use strict";
-                (function() {{
-                    console.error("Analytics initialization failed: {}", {});
-                    window.stats_collect = function() {{
-                        console.warn("Analytics disabled due to initialization error");
-                    }};
-                }})();"#,
-                e,
-                Status::InternalServerError
-            );
-
-            (
-                Status::InternalServerError,
-                content::RawJavaScript(error_js),
-            )
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support;
+    use rocket::{local::asynchronous::Client, routes};
+
+    #[test]
+    fn matches_if_none_match_lists() {
+        let etag = "\"abc\"";
+        let cases = [
+            (None, false),
+            (Some("\"abc\""), true),
+            (Some("W/\"abc\""), true),
+            (Some("\"old\", W/\"abc\""), true),
+            (Some("\"old\",\"abc\""), true),
+            (Some("*"), true),
+            (Some("\"old\""), false),
+            (Some("abc"), false),
+            (Some(""), false),
+        ];
+
+        for (header, expected) in cases {
+            let if_none_match = IfNoneMatch(header.map(ToString::to_string));
+            assert_eq!(if_none_match.matches(etag), expected, "{header:?}");
         }
     }
-}
 
-/// # `generate_analytics_js`
-/// Generates the JavaScript code for analytics tracking.
-///
-/// ## Arguments
-/// * `collector_id` - Collector ID
-/// * `app_url` - Application URL
-///
-/// ## Returns
-/// * `String` - JavaScript code for analytics tracking
-fn generate_analytics_js(collector_id: &str, app_url: &str) -> String {
-    format!(
-        r#""use strict";
-(() => {{
-    {{
-        const collectorId = "{collector_id}";
-        const appUrl = "{app_url}";
-
-        function init() {{
-            {{
-                document.addEventListener("click", (event) => {{
-                    if (event.target.tagName === "A") {{
-                        const target = event.target.getAttribute("target");
-                        const href = event.target.getAttribute("href");
-
-                        if (target === "_blank") {{
-                            stats_collect("leave", href);
-                        }}
-                    }}
-                }});
-
-                window.addEventListener("beforeunload", (event) => {{
-                    stats_collect("exit");
-                }});
-
-                // Listen for history changes
-                function wrapHistoryMethod(method) {{
-                    const original = history[method];
-                    history[method] = function (...args) {{
-                        const [state, title, url] = args;
-                        const fullUrl = new URL(url, window.location.origin)
-                            .href;
-                        console.log("📼 history", method, url, fullUrl);
-                        original.apply(this, args);
-                        stats_collect("visit", fullUrl);
-                    }};
-                }}
-
-                wrapHistoryMethod("pushState");
-                wrapHistoryMethod("replaceState");
-
-                // Listen for popstate event
-                window.addEventListener("popstate", (event) => {{
-                    stats_collect("visit", location.href);
-                }});
-            }}
-        }}
-
-        async function send(
-            type = "pageview",
-            url_override = null,
-            referrer = document.referrer,
-        ) {{
-            const endpoint = `${{appUrl}}/event`;
-
-            const data = {{
-                collector_id: collectorId,
-                name: type,
-                url: url_override || window.location.href,
-                referrer: referrer,
-            }};
-
-            fetch(endpoint, {{
-                method: "POST",
-                headers: {{
-                    "Content-Type": "application/json",
-                }},
-                body: JSON.stringify(data),
-            }})
-                .then((res) => res.json())
-                .then((data) => {{
-                    // console.log("📼", data);
-                }})
-                .catch((rejected) => {{
-                    console.log(`📼 [send]`, "failed to collect");
-                    console.error(rejected);
-                }});
-        }}
-
-        async function stats_collect(type, url = null) {{
-            await send(type, url);
-        }}
-
-        window.stats_collect = stats_collect;
-        stats_collect("enter");
-
-        window.addEventListener("load", () => {{
-            init();
-        }});
-    }}
-}})();
-"#
-    )
+    #[rocket::async_test]
+    async fn revalidates_the_script() {
+        let rocket = test_support::rocket().mount("/stats.js", routes![collector_stats_js]);
+        let client = Client::tracked(rocket).await.expect("valid rocket");
+
+        let response = client.get("/stats.js").dispatch().await;
+        assert_eq!(response.status(), Status::Ok);
+        let etag = response
+            .headers()
+            .get_one("ETag")
+            .expect("ETag header")
+            .to_string();
+        assert!(
+            response
+                .into_string()
+                .await
+                .is_some_and(|body| !body.is_empty())
+        );
+
+        for header in [
+            etag.clone(),
+            format!("W/{etag}"),
+            format!("\"old\", {etag}"),
+        ] {
+            let response = client
+                .get("/stats.js")
+                .header(Header::new("If-None-Match", header))
+                .dispatch()
+                .await;
+            assert_eq!(response.status(), Status::NotModified);
+            assert_eq!(response.headers().get_one("ETag"), Some(etag.as_str()));
+        }
+
+        let response = client
+            .get("/stats.js")
+            .header(Header::new("If-None-Match", "\"old\""))
+            .dispatch()
+            .await;
+        assert_eq!(response.status(), Status::Ok);
+    }
 }
@@ -0,0 +1,166 @@
+use std::sync::Arc;
+
+use chrono::Duration;
+use rocket::{State, get, post, serde::json::Json};
+use serde_json::{Value, json};
+use ulid::Ulid;
+
+use crate::{
+    AppState, DbConn, PageOrigin, UserAgentInfo,
+    api_response::ApiResponse,
+    models::{ErrorGroup, ErrorReport, StatsFilter},
+    paginated::set_pagination_defaults,
+    routes::{
+        event::{authorize_report, find_site, normalize_url},
+        summary::parse_range,
+    },
+};
+
+/// # `error_insert`
+/// Handles POST requests to record a JavaScript error.
+///
+/// Like `/event/batch`, any content type is accepted so that `navigator.sendBeacon`
+/// works without a CORS preflight.
+///
+/// ## Arguments
+/// * `body` - JSON object with the page URL, collector, message, source, position and stack
+/// * `user_agent_info` - The user agent of the client, if any
+/// * `page_origin` - The origin of the page sending the error
+/// * `state` - The application state
+/// * `conn` - Database connection
+#[post("/", data = "<body>")]
+pub async fn error_insert(
+    body: String,
+    user_agent_info: Option<UserAgentInfo>,
+    page_origin: PageOrigin,
+    state: &State<Arc<AppState>>,
+    conn: DbConn,
+) -> Json<Value> {
+    let report: ErrorReport = match serde_json::from_str(&body) {
+        Ok(report) => report,
+        Err(e) => return ApiResponse::bad_request(&format!("Invalid error report: {e}")),
+    };
+
+    if report.message.trim().is_empty() {
+        return ApiResponse::bad_request("Error message cannot be empty");
+    }
+
+    let collector = match authorize_report(
+        &report.url,
+        &report.collector_id,
+        report.token.as_deref(),
+        report.site_id.as_ref(),
+        user_agent_info.as_ref(),
+        page_origin.0.as_deref(),
+        state,
+        &conn,
+    )
+    .await
+    {
+        Ok(collector) => collector,
+        Err(response) => return response,
+    };
+
+    let url = match find_site(collector.site_id.as_ref(), &conn).await {
+        Ok(site) => normalize_url(&report.url, site.as_ref()).url,
+        Err(response) => return response,
+    };
+    let fingerprint = state.error_fingerprinter.fingerprint(
+        collector.site_id.as_deref(),
+        &report.message,
+        report.source.as_deref(),
+        report.stack.as_deref(),
+    );
+    let group_id = fingerprint.id.clone();
+    let group = ErrorGroup::new(fingerprint, collector.site_id);
+    let occurrence = report.into_occurrence(Ulid::new().to_string(), group_id.clone(), url);
+
+    match ErrorGroup::record(group, occurrence, &conn).await {
+        Ok(()) => ApiResponse::created(json!({
+            "message": "Error recorded successfully",
+            "group": group_id
+        })),
+        Err(e) => ApiResponse::internal_error(&format!("Failed to record error: {e}")),
+    }
+}
+
+/// # `error_get`
+/// Handles GET requests to list the error groups seen within a range of days, most
+/// recently seen first.
+///
+/// ## Arguments
+/// * `page` - Page number for pagination
+/// * `per_page` - Number of items per page
+/// * `from` - First day of the range, `YYYY-MM-DD` (default: 6 days before `to`)
+/// * `to` - Last day of the range, included, `YYYY-MM-DD` (default: today, UTC)
+/// * `filter` - Scope of the errors
+/// * `conn` - Database connection
+///
+/// ## Returns
+/// * `Json<Value>` - JSON response containing the error groups
+#[get("/?<page>&<per_page>&<from>&<to>&<filter..>")]
+pub async fn error_get(
+    page: Option<i64>,
+    per_page: Option<i64>,
+    from: Option<String>,
+    to: Option<String>,
+    filter: StatsFilter,
+    conn: DbConn,
+) -> Json<Value> {
+    let (page, per_page) = set_pagination_defaults(page, per_page);
+    let (from, to) = match parse_range(from.as_deref(), to.as_deref()) {
+        Ok(range) => range,
+        Err(message) => return ApiResponse::bad_request(&message),
+    };
+
+    let start_time = from.and_hms_opt(0, 0, 0).unwrap_or_default();
+    let end_time = (to + Duration::days(1))
+        .and_hms_opt(0, 0, 0)
+        .unwrap_or_default();
+
+    match ErrorGroup::all(page, per_page, start_time, end_time, filter, &conn).await {
+        Ok(groups) => ApiResponse::success(json!({
+            "from": from,
+            "to": to,
+            "errors": groups
+        })),
+        Err(e) => ApiResponse::internal_error(&format!("Failed to retrieve errors: {e}")),
+    }
+}
+
+/// # `error_get_one`
+/// Handles GET requests to retrieve an error group, with the pages and sessions it
+/// happened on and its latest occurrences.
+///
+/// ## Arguments
+/// * `id` - Fingerprint of the error group
+/// * `conn` - Database connection
+///
+/// ## Returns
+/// * `Json<Value>` - JSON response containing the error group
+#[get("/<id>")]
+pub async fn error_get_one(id: String, conn: DbConn) -> Json<Value> {
+    let group = match ErrorGroup::find(id.clone(), &conn).await {
+        Ok(Some(group)) => group,
+        Ok(None) => return ApiResponse::not_found(&format!("Error group {id} not found")),
+        Err(e) => return ApiResponse::internal_error(&format!("Failed to retrieve error: {e}")),
+    };
+
+    let details = async {
+        Ok::<_, diesel::result::Error>((
+            ErrorGroup::urls(id.clone(), &conn).await?,
+            ErrorGroup::sessions(id.clone(), &conn).await?,
+            ErrorGroup::occurrences(id.clone(), &conn).await?,
+        ))
+    };
+
+    match details.await {
+        Ok((urls, sessions, occurrences)) => ApiResponse::success(json!({
+            "error": group,
+            "urls": urls,
+            "sessions": sessions,
+            "occurrences": occurrences
+        })),
+        Err(e) => ApiResponse::internal_error(&format!("Failed to retrieve error: {e}")),
+    }
+}
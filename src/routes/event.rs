@@ -1,52 +1,301 @@
-use crate::AppState;
+use std::sync::Arc;
+
 use crate::api_response::ApiResponse;
+use crate::metrics::Metrics;
+use crate::services::{
+    bot_detection::BotReason,
+    campaign::CampaignParams,
+    collector_token::TokenError,
+    privacy::PrivacyPolicy,
+    tracker_event::validate_event,
+    url_rules::{NormalizedUrl, UrlRules},
+    user_agent::DeviceType,
+};
+use crate::{AppState, PageOrigin, UserAgentInfo};
 use crate::{
     DbConn,
-    models::{Event, EventQuery},
+    models::{Collector, Event, EventQuery, Site, StatsFilter},
     paginated::set_pagination_defaults,
 };
 use regex::Regex;
 use rocket::State;
+use rocket::http::Status;
 use rocket::{get, post, serde::json::Json};
 use serde_json::{Value, json};
-use url::Url;
 
-/// # `event_insert`
-/// Handles POST requests to insert a new event.
+/// Largest number of events accepted in a single batch
+const MAX_BATCH_EVENTS: usize = 100;
+
+fn token_error(error: TokenError) -> Json<Value> {
+    ApiResponse::error(error.status(), &error.to_string())
+}
+
+/// # `authorize_report`
+/// Checks that a page may report data for a collector: local pages are refused in
+/// production, visitors who opted out are refused under `PRIVACY_SIGNALS=skip`, and the
+/// collector token must be valid for this collector, site and page origin.
 ///
 /// ## Arguments
-/// * `event_data` - Event data from request
+/// * `url` - The URL of the page reporting
+/// * `collector_id` - The collector the data belongs to
+/// * `token` - The collector token sent with the data, if any
+/// * `site_id` - The site the data claims to belong to, if any
+/// * `user_agent_info` - The user agent of the client, if any
+/// * `page_origin` - The origin of the page sending the data, if known
+/// * `state` - The application state
 /// * `conn` - Database connection
 ///
+/// ## Errors
+/// * `Json<Value>` - The error response to send back if the data is rejected
+///
+/// ## Returns
+/// * `Result<Collector, Json<Value>>` - The collector the data belongs to
+#[allow(clippy::too_many_arguments)]
+pub(crate) async fn authorize_report(
+    url: &str,
+    collector_id: &str,
+    token: Option<&str>,
+    site_id: Option<&String>,
+    user_agent_info: Option<&UserAgentInfo>,
+    page_origin: Option<&str>,
+    state: &AppState,
+    conn: &DbConn,
+) -> Result<Collector, Json<Value>> {
+    // Block local requests in production
+    if !state.dev_mode && is_local_url(url) {
+        return Err(ApiResponse::bad_request(
+            "Local URLs are not allowed in production",
+        ));
+    }
+
+    // Visitors who opted out of tracking entirely don't get their data stored
+    if state.privacy_policy == PrivacyPolicy::Skip
+        && let Some(signal) = user_agent_info.and_then(|info| info.privacy_signal)
+    {
+        if let Some(counter) = state.metrics.privacy(signal, state.privacy_policy) {
+            Metrics::increment(counter);
+        }
+        return Err(ApiResponse::error(
+            Status::Forbidden,
+            &format!("Tracking refused by the visitor ({signal})"),
+        ));
+    }
+
+    // Only collectors issued by `POST /session` may report data, for their own site
+    let claims = match state.token_signer.verify(token, page_origin) {
+        Ok(claims) if claims.collector_id == collector_id => claims,
+        Ok(_) => return Err(token_error(TokenError::Invalid)),
+        Err(e) => return Err(token_error(e)),
+    };
+    if site_id.is_some() && site_id != claims.site_id.as_ref() {
+        return Err(token_error(TokenError::SiteMismatch));
+    }
+    match Collector::find(collector_id.to_string(), conn).await {
+        Ok(Some(collector)) if collector.site_id == claims.site_id => Ok(collector),
+        Ok(Some(_)) => Err(token_error(TokenError::SiteMismatch)),
+        Ok(None) => Err(token_error(TokenError::UnknownCollector)),
+        Err(e) => Err(ApiResponse::internal_error(&format!(
+            "Failed to check collector: {e}"
+        ))),
+    }
+}
+
+/// # `is_local_url`
+/// Whether a URL points to the machine of the developer, e.g. `http://localhost:3000`.
+///
+/// ## Arguments
+/// * `url` - The URL of a page
+///
 /// ## Panics
 /// If the regex pattern is invalid.
-#[post("/", format = "application/json", data = "<event_data>")]
-pub async fn event_insert(
-    event_data: Json<EventQuery>,
-    state: &State<AppState>,
-    conn: DbConn,
-) -> Json<serde_json::Value> {
+pub(crate) fn is_local_url(url: &str) -> bool {
     let localhost_regex = Regex::new(r"http://(127\.0\.0\.1|localhost|0\.0\.0\.0|\[::1\])(:\d+)?")
         .expect("Invalid regex pattern");
 
-    // Block local requests in production
-    if !state.dev_mode && localhost_regex.is_match(&event_data.url) {
-        return ApiResponse::bad_request("Local URLs are not allowed in production");
+    localhost_regex.is_match(url)
+}
+
+/// # `find_site`
+/// Finds the site reported data belongs to, for its domain and settings.
+///
+/// ## Arguments
+/// * `site_id` - The site of the collector, if any
+/// * `conn` - Database connection
+///
+/// ## Errors
+/// * `Json<Value>` - The error response to send back if the site cannot be read
+///
+/// ## Returns
+/// * `Result<Option<Site>, Json<Value>>` - The site, if any
+pub(crate) async fn find_site(
+    site_id: Option<&String>,
+    conn: &DbConn,
+) -> Result<Option<Site>, Json<Value>> {
+    let Some(site_id) = site_id else {
+        return Ok(None);
+    };
+
+    Site::find(site_id.clone(), conn)
+        .await
+        .map_err(|e| ApiResponse::internal_error(&format!("Failed to read site settings: {e}")))
+}
+
+/// # `normalize_url`
+/// Applies the URL rules of a site to a reported URL. Sites whose settings no longer
+/// hold valid rules, and data without a site, get the default rules.
+///
+/// ## Arguments
+/// * `url` - The URL as reported by the page
+/// * `site` - The site the URL belongs to, if any
+///
+/// ## Returns
+/// * `NormalizedUrl` - The URL to store, with its raw URL and path template
+pub(crate) fn normalize_url(url: &str, site: Option<&Site>) -> NormalizedUrl {
+    let rules = site.map_or_else(UrlRules::default, |site| {
+        UrlRules::from_site_settings(&site.settings).unwrap_or_else(|e| {
+            eprintln!("Ignoring the URL rules of site {}: {e}", site.id);
+            UrlRules::default()
+        })
+    });
+
+    rules.normalize(url)
+}
+
+/// # `prepare_event`
+/// Validates an incoming event and turns it into an `Event` ready to be stored.
+/// Collectors sending scripted or automated hits are flagged as bots on the way.
+///
+/// ## Arguments
+/// * `event_data` - Event data from request
+/// * `user_agent_info` - The user agent of the client, if any
+/// * `page_origin` - The origin of the page sending the event, if known
+/// * `state` - The application state
+/// * `conn` - Database connection
+///
+/// ## Errors
+/// * `Json<Value>` - The error response to send back if the event is rejected
+async fn prepare_event(
+    event_data: EventQuery,
+    user_agent_info: Option<&UserAgentInfo>,
+    page_origin: Option<&str>,
+    state: &AppState,
+    conn: &DbConn,
+) -> Result<Event, Json<Value>> {
+    if let Err(message) = event_data.validate_props() {
+        return Err(ApiResponse::bad_request(&message));
+    }
+    if let Err(message) = validate_event(&event_data) {
+        return Err(ApiResponse::bad_request(&message));
     }
 
-    // Clean URL
-    let clean_url = match Url::parse(&event_data.url) {
-        Ok(mut url) => {
-            url.set_query(None);
-            url.to_string().trim_end_matches('/').to_string()
-        }
-        Err(_) => event_data.url.trim_end_matches('/').to_string(),
+    let collector = authorize_report(
+        &event_data.url,
+        &event_data.collector_id,
+        event_data.token.as_deref(),
+        event_data.site_id.as_ref(),
+        user_agent_info,
+        page_origin,
+        state,
+        conn,
+    )
+    .await?;
+
+    // Scripted hits and automated browsers are kept, but their collector is flagged
+    let bot_reason = match user_agent_info {
+        None => Some(BotReason::UserAgent),
+        Some(info) if info.device_type == DeviceType::Bot => Some(BotReason::UserAgent),
+        Some(_) if event_data.webdriver => Some(BotReason::Headless),
+        Some(_) => None,
     };
+    if let Some(reason) = bot_reason
+        && let Err(e) =
+            Collector::flag_bot(event_data.collector_id.clone(), reason.to_string(), conn).await
+    {
+        return Err(ApiResponse::internal_error(&format!(
+            "Failed to record event: {e}"
+        )));
+    }
 
-    let mut new_event = event_data.into_inner(); // from Json<EventQuery> to EventQuery
-    new_event.url = clean_url;
+    let site = find_site(collector.site_id.as_ref(), conn).await?;
 
-    let new_event: Event = new_event.into(); // from EventQuery to Event, `: Event` not needed
+    Ok(build_event(event_data, site.as_ref(), state))
+}
+
+/// # `build_event`
+/// Turns a validated event into an `Event` ready to be stored: extracts its campaign
+/// parameters, classifies its referrer and applies the URL rules of its site.
+///
+/// ## Arguments
+/// * `event_data` - The validated event
+/// * `site` - The site the event belongs to, if any
+/// * `state` - The application state
+///
+/// ## Returns
+/// * `Event` - The event to store
+pub(crate) fn build_event(
+    mut event_data: EventQuery,
+    site: Option<&Site>,
+    state: &AppState,
+) -> Event {
+    // Campaign parameters are lost with the query string, keep them first
+    let campaign = CampaignParams::from_url(&event_data.url);
+    let source = state.referrer_classifier.classify(
+        event_data.referrer.as_deref(),
+        &event_data.url,
+        site.map(|site| site.domain.as_str()),
+        &campaign,
+    );
+    let normalized = normalize_url(&event_data.url, site);
+    event_data.url = normalized.url;
+    let referrer = source.referrer;
+
+    Event {
+        utm_source: campaign.utm_source,
+        utm_medium: campaign.utm_medium,
+        utm_campaign: campaign.utm_campaign,
+        utm_term: campaign.utm_term,
+        utm_content: campaign.utm_content,
+        ref_param: campaign.ref_param,
+        click_id: campaign.click_id,
+        raw_url: Some(normalized.raw_url),
+        path_template: normalized.path_template,
+        referrer_host: referrer.as_ref().map(|referrer| referrer.host.clone()),
+        referrer_domain: referrer.as_ref().map(|referrer| referrer.domain.clone()),
+        referrer_path: referrer.map(|referrer| referrer.path),
+        channel: Some(source.channel.to_string()),
+        ..event_data.into()
+    }
+}
+
+/// # `event_insert`
+/// Handles POST requests to insert a new event.
+///
+/// ## Arguments
+/// * `event_data` - Event data from request
+/// * `user_agent_info` - The user agent of the client, if any
+/// * `page_origin` - The origin of the page sending the event
+/// * `state` - The application state
+/// * `conn` - Database connection
+#[post("/", format = "application/json", data = "<event_data>")]
+pub async fn event_insert(
+    event_data: Json<EventQuery>,
+    user_agent_info: Option<UserAgentInfo>,
+    page_origin: PageOrigin,
+    state: &State<Arc<AppState>>,
+    conn: DbConn,
+) -> Json<serde_json::Value> {
+    let new_event = match prepare_event(
+        event_data.into_inner(),
+        user_agent_info.as_ref(),
+        page_origin.0.as_deref(),
+        state,
+        &conn,
+    )
+    .await
+    {
+        Ok(event) => event,
+        Err(response) => return response,
+    };
 
     // Use connection to insert event
     match Event::insert(new_event, &conn).await {
@@ -57,21 +306,84 @@ pub async fn event_insert(
     }
 }
 
+/// # `event_insert_batch`
+/// Handles POST requests to insert several events at once.
+///
+/// The body is a JSON array of events. Any content type is accepted so that
+/// `navigator.sendBeacon`, which sends strings as `text/plain`, works without
+/// a CORS preflight. Either every event is recorded or none is.
+///
+/// ## Arguments
+/// * `body` - JSON array of events
+/// * `user_agent_info` - The user agent of the client, if any
+/// * `page_origin` - The origin of the page sending the event
+/// * `state` - The application state
+/// * `conn` - Database connection
+#[post("/batch", data = "<body>")]
+pub async fn event_insert_batch(
+    body: String,
+    user_agent_info: Option<UserAgentInfo>,
+    page_origin: PageOrigin,
+    state: &State<Arc<AppState>>,
+    conn: DbConn,
+) -> Json<serde_json::Value> {
+    let events_data: Vec<EventQuery> = match serde_json::from_str(&body) {
+        Ok(events_data) => events_data,
+        Err(e) => return ApiResponse::bad_request(&format!("Invalid batch: {e}")),
+    };
+
+    if events_data.is_empty() {
+        return ApiResponse::bad_request("Batch is empty");
+    }
+    if events_data.len() > MAX_BATCH_EVENTS {
+        return ApiResponse::bad_request(&format!("Batch is limited to {MAX_BATCH_EVENTS} events"));
+    }
+
+    let mut new_events = Vec::with_capacity(events_data.len());
+    for event_data in events_data {
+        match prepare_event(
+            event_data,
+            user_agent_info.as_ref(),
+            page_origin.0.as_deref(),
+            state,
+            &conn,
+        )
+        .await
+        {
+            Ok(event) => new_events.push(event),
+            Err(response) => return response,
+        }
+    }
+
+    match Event::insert_batch(new_events, &conn).await {
+        Ok(count) => ApiResponse::created(json!({
+            "message": &format!("{count} events recorded successfully")
+        })),
+        Err(e) => ApiResponse::internal_error(&format!("Failed to record events: {e}")),
+    }
+}
+
 /// # Retrieve Events
 /// Handles GET requests to fetch all events.
 ///
 /// ## Arguments
 /// * `page` - Page number for pagination
 /// * `per_page` - Number of items per page
+/// * `filter` - Scope of the events to retrieve
 /// * `conn` - Database connection
 ///
 /// ## Returns
 /// * `Json<Value>` - JSON response containing events data
-#[get("/?<page>&<per_page>")]
-pub async fn event_get(page: Option<i64>, per_page: Option<i64>, conn: DbConn) -> Json<Value> {
+#[get("/?<page>&<per_page>&<filter..>")]
+pub async fn event_get(
+    page: Option<i64>,
+    per_page: Option<i64>,
+    filter: StatsFilter,
+    conn: DbConn,
+) -> Json<Value> {
     let (page, per_page) = set_pagination_defaults(page, per_page);
 
-    match Event::all(page, per_page, &conn).await {
+    match Event::all(page, per_page, filter, &conn).await {
         Ok(events_list) => ApiResponse::success(json!({
             "events": events_list
         })),
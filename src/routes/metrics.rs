@@ -0,0 +1,21 @@
+use std::sync::Arc;
+
+use rocket::{State, get, serde::json::Json};
+use serde_json::{Value, json};
+
+use crate::{AppState, api_response::ApiResponse};
+
+/// # `metrics_get`
+/// Handles GET requests to retrieve the operational counters of the server.
+///
+/// ## Arguments
+/// * `state` - The application state
+///
+/// ## Returns
+/// * `Json<Value>` - JSON response containing the counters
+#[get("/")]
+pub fn metrics_get(state: &State<Arc<AppState>>) -> Json<Value> {
+    ApiResponse::success(json!({
+        "metrics": state.metrics.snapshot()
+    }))
+}
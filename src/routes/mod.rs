@@ -1,5 +1,11 @@
+pub mod api;
 pub mod city;
 pub mod collector;
+pub mod error;
 pub mod event;
+pub mod metrics;
+pub mod pixel;
 pub mod session;
+pub mod site;
 pub mod summary;
+pub mod vital;
@@ -0,0 +1,172 @@
+use std::{net::IpAddr, sync::Arc};
+
+use rocket::{
+    FromForm, Request, Response, State, get,
+    http::{ContentType, Header},
+    request::{FromRequest, Outcome},
+    response::{self, Responder},
+};
+
+use crate::{
+    AppState, DbConn, UserAgentInfo,
+    logger::Logger,
+    models::{Event, EventQuery},
+    routes::event::{build_event, find_site, is_local_url},
+    services::{
+        session::{Session, SessionRequest, start_session},
+        tracker_event::{TrackerEvent, validate_event},
+    },
+};
+
+/// A transparent 1x1 GIF
+const TRANSPARENT_GIF: [u8; 43] = [
+    0x47, 0x49, 0x46, 0x38, 0x39, 0x61, 0x01, 0x00, 0x01, 0x00, 0x80, 0x00, 0x00, 0x00, 0x00, 0x00,
+    0xFF, 0xFF, 0xFF, 0x21, 0xF9, 0x04, 0x01, 0x00, 0x00, 0x00, 0x00, 0x2C, 0x00, 0x00, 0x00, 0x00,
+    0x01, 0x00, 0x01, 0x00, 0x00, 0x02, 0x02, 0x44, 0x01, 0x00, 0x3B,
+];
+
+#[derive(FromForm, Debug)]
+pub struct PixelQuery {
+    pub site: Option<String>,
+    /// URL of the page, defaults to the `Referer` of the request
+    pub url: Option<String>,
+    /// Referrer of the page, which only the page itself knows
+    #[field(name = "ref")]
+    pub referrer: Option<String>,
+}
+
+/// `Referer` header of a request, if any
+pub struct Referer(Option<String>);
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for Referer {
+    type Error = ();
+
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        Outcome::Success(Referer(
+            request
+                .headers()
+                .get_one("Referer")
+                .map(ToString::to_string),
+        ))
+    }
+}
+
+/// The tracking pixel, never cached so that every page view reaches the server.
+pub struct PixelResponse;
+
+impl<'r> Responder<'r, 'static> for PixelResponse {
+    fn respond_to(self, _: &'r Request<'_>) -> response::Result<'static> {
+        Response::build()
+            .header(ContentType::GIF)
+            .header(Header::new(
+                "Cache-Control",
+                "no-cache, no-store, must-revalidate, private",
+            ))
+            .header(Header::new("Pragma", "no-cache"))
+            .header(Header::new("Expires", "0"))
+            .sized_body(
+                TRANSPARENT_GIF.len(),
+                std::io::Cursor::new(&TRANSPARENT_GIF[..]),
+            )
+            .ok()
+    }
+}
+
+/// # `collector_pixel`
+/// Serves a transparent GIF for pages that cannot run the tracking script, such as
+/// AMP pages, emails or browsers without JavaScript, and records the visit: a new
+/// collector, through the same geolocation, bot detection and privacy policies as
+/// `POST /session`, and an `enter` event.
+///
+/// The pixel is returned even when nothing is recorded, so that pages never show a
+/// broken image.
+///
+/// ## Arguments
+/// * `query` - The site, page URL and referrer
+/// * `referer` - The `Referer` header, used as the page URL when `url` is missing
+/// * `ip` - The IP address of the visitor
+/// * `user_agent_info` - The user agent of the visitor, if any
+/// * `state` - The application state
+/// * `conn` - Database connection
+///
+/// ## Returns
+/// * `PixelResponse` - The transparent GIF
+#[get("/?<query..>")]
+pub async fn collector_pixel(
+    query: PixelQuery,
+    referer: Referer,
+    ip: IpAddr,
+    user_agent_info: Option<UserAgentInfo>,
+    state: &State<Arc<AppState>>,
+    conn: DbConn,
+) -> PixelResponse {
+    if let Err(message) = record_pixel(query, referer, ip, user_agent_info, state, &conn).await {
+        Logger::debug("Pixel", &format!("Visit not recorded: {message}"));
+    }
+
+    PixelResponse
+}
+
+/// Records the collector and `enter` event of a pixel request.
+async fn record_pixel(
+    query: PixelQuery,
+    referer: Referer,
+    ip: IpAddr,
+    user_agent_info: Option<UserAgentInfo>,
+    state: &AppState,
+    conn: &DbConn,
+) -> Result<(), String> {
+    let Some(url) = query.url.or(referer.0) else {
+        return Err("No page URL".to_string());
+    };
+    let Some(user_agent_info) = user_agent_info else {
+        return Err("No user agent".to_string());
+    };
+    if !state.dev_mode && is_local_url(&url) {
+        return Err("Local URLs are not allowed in production".to_string());
+    }
+
+    let mut event_data = EventQuery {
+        url,
+        referrer: query.referrer,
+        name: TrackerEvent::Enter.to_string(),
+        collector_id: String::new(),
+        token: None,
+        site_id: query.site.clone(),
+        webdriver: false,
+        props: None,
+        engagement_ms: None,
+    };
+    validate_event(&event_data)?;
+
+    let request = SessionRequest {
+        site_id: query.site,
+        resume: None,
+        ip,
+        page_origin: None,
+        user_agent_info,
+    };
+    let (collector_id, site_id) = match start_session(request, state, conn).await {
+        Ok(Session::Tracked {
+            collector_id,
+            site_id,
+            ..
+        }) => (collector_id, site_id),
+        Ok(Session::OptedOut(signal)) => {
+            return Err(format!("Tracking refused by the visitor ({signal})"));
+        }
+        Err(e) => return Err(e.to_string()),
+    };
+
+    let site = find_site(site_id.as_ref(), conn)
+        .await
+        .map_err(|_| "Failed to read site settings".to_string())?;
+    event_data.collector_id = collector_id;
+    let event = build_event(event_data, site.as_ref(), state);
+
+    Event::insert(event, conn)
+        .await
+        .map(|_| ())
+        .map_err(|e| format!("Failed to record event: {e}"))
+}
@@ -1,23 +1,103 @@
-use rocket::{get, serde::json::Json};
+use std::{net::IpAddr, sync::Arc};
+
+use rocket::{State, get, post, serde::json::Json};
+use serde::Deserialize;
 use serde_json::json;
 
 use crate::{
-    DbConn,
+    AppState, DbConn, PageOrigin, UserAgentInfo,
     api_response::ApiResponse,
-    models::{map, retrieve_sessions},
+    models::{StatsFilter, map, retrieve_sessions},
+    services::session::{Session, SessionError, SessionRequest, start_session},
 };
 
+#[derive(Deserialize, Debug)]
+pub struct SessionQuery {
+    #[serde(default)]
+    pub site_id: Option<String>,
+    /// Collector of a previous session in the same tab, resumed if its token is still valid
+    #[serde(default)]
+    pub collector_id: Option<String>,
+    #[serde(default)]
+    pub token: Option<String>,
+}
+
+/// # `session_start`
+/// Handles the handshake of the tracking script: starts a session for the visitor,
+/// or resumes the one of a previous page of the same tab.
+///
+/// The body is JSON, sent as `text/plain` to avoid a CORS preflight.
+///
+/// ## Arguments
+/// * `body` - The `SessionQuery` as JSON
+/// * `ip` - The IP address of the visitor
+/// * `page_origin` - The origin of the page embedding the script
+/// * `user_agent_info` - The user agent information
+/// * `state` - The application state
+/// * `conn` - The database connection
+///
+/// ## Returns
+/// * `Json<serde_json::Value>` - The collector ID and token, or `tracking: false` for
+///   visitors who opted out
+#[post("/", data = "<body>")]
+pub async fn session_start(
+    body: String,
+    ip: IpAddr,
+    page_origin: PageOrigin,
+    user_agent_info: UserAgentInfo,
+    state: &State<Arc<AppState>>,
+    conn: DbConn,
+) -> Json<serde_json::Value> {
+    let query: SessionQuery = match serde_json::from_str(&body) {
+        Ok(query) => query,
+        Err(e) => return ApiResponse::bad_request(&format!("Invalid session request: {e}")),
+    };
+
+    let request = SessionRequest {
+        site_id: query.site_id,
+        resume: query.collector_id.zip(query.token),
+        ip,
+        page_origin: page_origin.0,
+        user_agent_info,
+    };
+
+    match start_session(request, state, &conn).await {
+        Ok(Session::Tracked {
+            collector_id,
+            token,
+            site_id,
+            resumed,
+        }) => ApiResponse::created(json!({
+            "tracking": true,
+            "collector_id": collector_id,
+            "token": token,
+            "site_id": site_id,
+            "resumed": resumed,
+        })),
+        Ok(Session::OptedOut(signal)) => ApiResponse::success(json!({
+            "tracking": false,
+            "reason": signal.as_str(),
+        })),
+        Err(e @ SessionError::UnknownSite(_)) => ApiResponse::not_found(&e.to_string()),
+        Err(e) => {
+            eprintln!("{e}");
+            ApiResponse::internal_error(&e.to_string())
+        }
+    }
+}
+
 /// # `get_sessions`
 /// Handle the request to retrieve the last 30 recent visitor sessions.
 ///
 /// ## Arguments
+/// * `filter` - Scope of the data to retrieve.
 /// * `conn` - The database connection.
 ///
 /// ## Returns
 /// * `Json<serde_json::Value>` - The JSON response containing the sessions.
-#[get("/")]
-pub async fn session_get_sessions(conn: DbConn) -> Json<serde_json::Value> {
-    match retrieve_sessions(&conn).await {
+#[get("/?<filter..>")]
+pub async fn session_get_sessions(filter: StatsFilter, conn: DbConn) -> Json<serde_json::Value> {
+    match retrieve_sessions(filter, &conn).await {
         Ok(sessions) => ApiResponse::success(json!({
             "sessions": sessions
         })),
@@ -29,13 +109,14 @@ pub async fn session_get_sessions(conn: DbConn) -> Json<serde_json::Value> {
 /// Handle the request to retrieve the map data.
 ///
 /// ## Arguments
+/// * `filter` - Scope of the data to retrieve.
 /// * `conn` - The database connection.
 ///
 /// ## Returns
 /// * `Json<serde_json::Value>` - The JSON response containing the map data.
-#[get("/map")]
-pub async fn session_get_map_data(conn: DbConn) -> Json<serde_json::Value> {
-    match map(&conn).await {
+#[get("/map?<filter..>")]
+pub async fn session_get_map_data(filter: StatsFilter, conn: DbConn) -> Json<serde_json::Value> {
+    match map(filter, &conn).await {
         Ok(data) => ApiResponse::success(json!({
             "cities": data
         })),
@@ -0,0 +1,222 @@
+use crate::api_response::ApiResponse;
+use crate::{
+    DbConn,
+    models::{Site, SiteQuery},
+    paginated::set_pagination_defaults,
+};
+use diesel::result::{DatabaseErrorKind, Error};
+use rocket::{get, http::Status, post, put, serde::json::Json};
+use serde_json::{Value, json};
+
+/// # `site_insert`
+/// Handles POST requests to register a new site.
+///
+/// ## Arguments
+/// * `site_data` - Site data from request
+/// * `conn` - Database connection
+///
+/// ## Returns
+/// * `Json<Value>` - JSON response containing the ID of the new site, or a conflict if the
+///   domain is already registered
+#[post("/", format = "application/json", data = "<site_data>")]
+pub async fn site_insert(site_data: Json<SiteQuery>, conn: DbConn) -> Json<Value> {
+    if let Err(message) = site_data.validate() {
+        return ApiResponse::bad_request(&message);
+    }
+
+    let site: Site = site_data.into_inner().into();
+    let domain = site.domain.clone();
+
+    match Site::insert(site, &conn).await {
+        Ok(id) => ApiResponse::created(json!({
+            "message": &format!("Site #{id} recorded successfully"),
+            "id": id
+        })),
+        Err(Error::DatabaseError(DatabaseErrorKind::UniqueViolation, _)) => {
+            ApiResponse::error(Status::Conflict, &format!("Site {domain} already exists"))
+        }
+        Err(e) => ApiResponse::internal_error(&format!("Failed to record site: {e}")),
+    }
+}
+
+/// # `site_update`
+/// Handles PUT requests to replace the domain, name, timezone and settings of a site.
+///
+/// ## Arguments
+/// * `id` - ID of the site
+/// * `site_data` - New site data, validated like on creation
+/// * `conn` - Database connection
+///
+/// ## Returns
+/// * `Json<Value>` - JSON response containing the updated site, or a conflict if the
+///   domain is already registered by another site
+#[put("/<id>", format = "application/json", data = "<site_data>")]
+pub async fn site_update(id: String, site_data: Json<SiteQuery>, conn: DbConn) -> Json<Value> {
+    if let Err(message) = site_data.validate() {
+        return ApiResponse::bad_request(&message);
+    }
+
+    let site = Site {
+        id: id.clone(),
+        ..site_data.into_inner().into()
+    };
+    let domain = site.domain.clone();
+
+    match Site::update(site, &conn).await {
+        Ok(true) => match Site::find(id.clone(), &conn).await {
+            Ok(Some(site)) => ApiResponse::success(json!(site)),
+            Ok(None) => ApiResponse::not_found(&format!("Site {id} not found")),
+            Err(e) => ApiResponse::internal_error(&format!("Failed to retrieve site: {e}")),
+        },
+        Ok(false) => ApiResponse::not_found(&format!("Site {id} not found")),
+        Err(Error::DatabaseError(DatabaseErrorKind::UniqueViolation, _)) => {
+            ApiResponse::error(Status::Conflict, &format!("Site {domain} already exists"))
+        }
+        Err(e) => ApiResponse::internal_error(&format!("Failed to update site: {e}")),
+    }
+}
+
+/// # `site_get`
+/// Handles GET requests to retrieve all sites.
+///
+/// ## Arguments
+/// * `page` - Page number for pagination
+/// * `per_page` - Number of items per page
+/// * `conn` - Database connection
+///
+/// ## Returns
+/// * `Json<Value>` - JSON response containing sites data
+#[get("/?<page>&<per_page>")]
+pub async fn site_get(page: Option<i64>, per_page: Option<i64>, conn: DbConn) -> Json<Value> {
+    let (page, per_page) = set_pagination_defaults(page, per_page);
+
+    match Site::all(page, per_page, &conn).await {
+        Ok(sites) => ApiResponse::success(json!(sites)),
+        Err(e) => ApiResponse::internal_error(&format!("Failed to retrieve sites: {e}")),
+    }
+}
+
+/// # `site_get_one`
+/// Handles GET requests to retrieve a single site.
+///
+/// ## Arguments
+/// * `id` - ID of the site
+/// * `conn` - Database connection
+///
+/// ## Returns
+/// * `Json<Value>` - JSON response containing the site data
+#[get("/<id>")]
+pub async fn site_get_one(id: String, conn: DbConn) -> Json<Value> {
+    match Site::find(id.clone(), &conn).await {
+        Ok(Some(site)) => ApiResponse::success(json!(site)),
+        Ok(None) => ApiResponse::not_found(&format!("Site {id} not found")),
+        Err(e) => ApiResponse::internal_error(&format!("Failed to retrieve site: {e}")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support;
+    use rocket::{http::ContentType, local::asynchronous::Client, routes};
+
+    async fn insert(client: &Client, body: &str) -> Value {
+        client
+            .post("/site")
+            .header(ContentType::JSON)
+            .body(body)
+            .dispatch()
+            .await
+            .into_json()
+            .await
+            .expect("JSON response")
+    }
+
+    async fn update(client: &Client, id: &str, body: &str) -> Value {
+        client
+            .put(format!("/site/{id}"))
+            .header(ContentType::JSON)
+            .body(body)
+            .dispatch()
+            .await
+            .into_json()
+            .await
+            .expect("JSON response")
+    }
+
+    #[rocket::async_test]
+    async fn duplicate_domain_is_a_conflict() {
+        let rocket = test_support::rocket().mount("/site", routes![site_insert]);
+        let client = Client::tracked(rocket).await.expect("valid rocket");
+
+        let created = insert(&client, r#"{"domain": "example.com", "name": "Example"}"#).await;
+        assert_eq!(created["status"], 201);
+
+        let duplicate = insert(&client, r#"{"domain": "Example.com", "name": "Again"}"#).await;
+        assert_eq!(duplicate["status"], 409);
+        assert!(
+            duplicate["error"]["message"]
+                .as_str()
+                .unwrap()
+                .contains("example.com")
+        );
+    }
+
+    #[rocket::async_test]
+    async fn updates_site_settings() {
+        let rocket =
+            test_support::rocket().mount("/site", routes![site_insert, site_update, site_get_one]);
+        let client = Client::tracked(rocket).await.expect("valid rocket");
+
+        let created = insert(&client, r#"{"domain": "example.com", "name": "Example"}"#).await;
+        let id = created["data"]["id"].as_str().expect("site ID");
+        insert(&client, r#"{"domain": "other.com", "name": "Other"}"#).await;
+
+        let updated = update(
+            &client,
+            id,
+            r#"{"domain": "example.com", "name": "Renamed", "timezone": "Europe/Paris",
+                "settings": {"url_rules": {"lowercase": true}}}"#,
+        )
+        .await;
+        assert_eq!(updated["status"], 200);
+        assert_eq!(updated["data"]["name"], "Renamed");
+        assert_eq!(updated["data"]["timezone"], "Europe/Paris");
+        let settings: Value =
+            serde_json::from_str(updated["data"]["settings"].as_str().unwrap()).unwrap();
+        assert_eq!(settings["url_rules"]["lowercase"], true);
+
+        let cases = [
+            (
+                id,
+                r#"{"domain": "example.com", "name": "Bad",
+                    "settings": {"url_rules": {"templates": [{"pattern": "(", "template": "/"}]}}}"#,
+                400,
+            ),
+            (id, r#"{"domain": " ", "name": "Empty"}"#, 400),
+            (id, r#"{"domain": "other.com", "name": "Taken"}"#, 409),
+            (
+                "missing",
+                r#"{"domain": "missing.com", "name": "Missing"}"#,
+                404,
+            ),
+        ];
+        for (site_id, body, status) in cases {
+            assert_eq!(
+                update(&client, site_id, body).await["status"],
+                status,
+                "{body}"
+            );
+        }
+
+        // Refused updates leave the site untouched
+        let site: Value = client
+            .get(format!("/site/{id}"))
+            .dispatch()
+            .await
+            .into_json()
+            .await
+            .expect("JSON response");
+        assert_eq!(site["data"]["name"], "Renamed");
+    }
+}
@@ -1,3 +1,4 @@
+use chrono::{Duration, NaiveDate, Utc};
 use rocket::{get, serde::json::Json};
 use serde_json::{Value, json};
 
@@ -5,7 +6,9 @@ use crate::{
     DbConn,
     api_response::ApiResponse,
     models::{
-        browsers, events, five_minutes, hourly, os_browsers, percentages, referrers, urls, weekly,
+        CampaignDimension, StatsFilter, UrlGrouping, bots, browsers, campaigns, channels, events,
+        five_minutes, hourly, os_browsers, percentages, properties, referrers, session_durations,
+        time_on_page, urls, validate_prop_key, vitals, weekly,
     },
 };
 
@@ -13,13 +16,14 @@ use crate::{
 /// Retrieves the five-minute event summary for a given city.
 ///
 /// ## Arguments
+/// * `filter` - Scope of the data to summarize.
 /// * `conn` - The database connection.
 ///
 /// ## Returns
 /// * `Json<Value>` - The five-minute event summary.
-#[get("/five_minutes")]
-pub async fn summary_get_five_minutes(conn: DbConn) -> Json<Value> {
-    match five_minutes(&conn).await {
+#[get("/five_minutes?<filter..>")]
+pub async fn summary_get_five_minutes(filter: StatsFilter, conn: DbConn) -> Json<Value> {
+    match five_minutes(filter, &conn).await {
         Ok(summary) => ApiResponse::success(json!({
             "summary": summary
         })),
@@ -31,13 +35,14 @@ pub async fn summary_get_five_minutes(conn: DbConn) -> Json<Value> {
 /// Retrieves the event summary for a given city.
 ///
 /// ## Arguments
+/// * `filter` - Scope of the data to summarize.
 /// * `conn` - The database connection.
 ///
 /// ## Returns
 /// * `Json<Value>` - The event summary.
-#[get("/events")]
-pub async fn summary_get_events(conn: DbConn) -> Json<Value> {
-    match events(&conn).await {
+#[get("/events?<filter..>")]
+pub async fn summary_get_events(filter: StatsFilter, conn: DbConn) -> Json<Value> {
+    match events(filter, &conn).await {
         Ok(summary) => ApiResponse::success(json!({
             "summary": summary
         })),
@@ -49,13 +54,14 @@ pub async fn summary_get_events(conn: DbConn) -> Json<Value> {
 /// Retrieves the hourly event summary for a given city.
 ///
 /// ## Arguments
+/// * `filter` - Scope of the data to summarize.
 /// * `conn` - The database connection.
 ///
 /// ## Returns
 /// * `Json<Value>` - The hourly event summary.
-#[get("/hourly")]
-pub async fn summary_get_hourly(conn: DbConn) -> Json<Value> {
-    match hourly(&conn).await {
+#[get("/hourly?<filter..>")]
+pub async fn summary_get_hourly(filter: StatsFilter, conn: DbConn) -> Json<Value> {
+    match hourly(filter, &conn).await {
         Ok(summary) => ApiResponse::success(json!({
             "summary": summary
         })),
@@ -67,13 +73,14 @@ pub async fn summary_get_hourly(conn: DbConn) -> Json<Value> {
 /// Retrieves the top 25 most used browsers.
 ///
 /// ## Arguments
+/// * `filter` - Scope of the data to summarize.
 /// * `conn` - The database connection.
 ///
 /// ## Returns
 /// * `Json<Value>` - The top 25 most used browsers.
-#[get("/browsers")]
-pub async fn summary_get_browsers(conn: DbConn) -> Json<Value> {
-    match browsers(&conn).await {
+#[get("/browsers?<filter..>")]
+pub async fn summary_get_browsers(filter: StatsFilter, conn: DbConn) -> Json<Value> {
+    match browsers(filter, &conn).await {
         Ok(summary) => ApiResponse::success(json!({
             "summary": summary
         })),
@@ -81,17 +88,256 @@ pub async fn summary_get_browsers(conn: DbConn) -> Json<Value> {
     }
 }
 
+/// # `summary_get_bots`
+/// Retrieves the visits flagged as bots over the past 7 days, by reason and browser.
+///
+/// ## Arguments
+/// * `filter` - Scope of the data to summarize.
+/// * `conn` - The database connection.
+///
+/// ## Returns
+/// * `Json<Value>` - The bot visits, most frequent first.
+#[get("/bots?<filter..>")]
+pub async fn summary_get_bots(filter: StatsFilter, conn: DbConn) -> Json<Value> {
+    match bots(filter, &conn).await {
+        Ok(summary) => ApiResponse::success(json!({
+            "summary": summary
+        })),
+        Err(err) => ApiResponse::internal_error(&format!("Failed to retrieve bot data: {err}")),
+    }
+}
+
+/// # `summary_get_properties`
+/// Retrieves the top 25 values of a custom property of an event.
+///
+/// ## Arguments
+/// * `event` - Name of the event.
+/// * `key` - Name of the property.
+/// * `filter` - Scope of the data to summarize.
+/// * `conn` - The database connection.
+///
+/// ## Returns
+/// * `Json<Value>` - The top 25 values of the property, or a bad request if the property
+///   name is invalid.
+#[get("/properties?<event>&<key>&<filter..>")]
+pub async fn summary_get_properties(
+    event: String,
+    key: String,
+    filter: StatsFilter,
+    conn: DbConn,
+) -> Json<Value> {
+    if let Err(message) = validate_prop_key(&key) {
+        return ApiResponse::bad_request(&message);
+    }
+
+    match properties(event, key, filter, &conn).await {
+        Ok(summary) => ApiResponse::success(json!({
+            "summary": summary
+        })),
+        Err(err) => {
+            ApiResponse::internal_error(&format!("Failed to retrieve property data: {err}"))
+        }
+    }
+}
+
+/// # `summary_get_time_on_page`
+/// Retrieves the average active time spent on the 25 most visited URLs.
+///
+/// ## Arguments
+/// * `filter` - Scope of the data to summarize.
+/// * `conn` - The database connection.
+///
+/// ## Returns
+/// * `Json<Value>` - The page views, total and average active time of each URL.
+#[get("/time_on_page?<filter..>")]
+pub async fn summary_get_time_on_page(filter: StatsFilter, conn: DbConn) -> Json<Value> {
+    match time_on_page(filter, &conn).await {
+        Ok(summary) => ApiResponse::success(json!({
+            "summary": summary
+        })),
+        Err(err) => ApiResponse::internal_error(&format!("Failed to retrieve time on page: {err}")),
+    }
+}
+
+/// # `summary_get_session_duration`
+/// Retrieves the average active time of recent sessions.
+///
+/// ## Arguments
+/// * `filter` - Scope of the data to summarize.
+/// * `conn` - The database connection.
+///
+/// ## Returns
+/// * `Json<Value>` - The number of sessions and their average duration and page views.
+#[get("/session_duration?<filter..>")]
+pub async fn summary_get_session_duration(filter: StatsFilter, conn: DbConn) -> Json<Value> {
+    match session_durations(filter, &conn).await {
+        Ok(summary) => ApiResponse::success(json!({
+            "summary": summary
+        })),
+        Err(err) => {
+            ApiResponse::internal_error(&format!("Failed to retrieve session durations: {err}"))
+        }
+    }
+}
+
+/// # `campaign_summary`
+/// Retrieves the sessions, visitors and conversions of the top 25 values of a campaign
+/// dimension.
+async fn campaign_summary(
+    dimension: CampaignDimension,
+    goal: Option<String>,
+    filter: StatsFilter,
+    conn: DbConn,
+) -> Json<Value> {
+    match campaigns(dimension, goal, filter, &conn).await {
+        Ok(summary) => ApiResponse::success(json!({
+            "summary": summary
+        })),
+        Err(err) => {
+            ApiResponse::internal_error(&format!("Failed to retrieve campaign data: {err}"))
+        }
+    }
+}
+
+/// # `summary_get_campaigns`
+/// Retrieves the top 25 campaigns (`utm_campaign`).
+///
+/// ## Arguments
+/// * `goal` - Name of the event counted as a conversion (default: any custom event).
+/// * `filter` - Scope of the data to summarize.
+/// * `conn` - The database connection.
+///
+/// ## Returns
+/// * `Json<Value>` - The sessions, unique visitors and conversions of each campaign.
+#[get("/campaigns?<goal>&<filter..>")]
+pub async fn summary_get_campaigns(
+    goal: Option<String>,
+    filter: StatsFilter,
+    conn: DbConn,
+) -> Json<Value> {
+    campaign_summary(CampaignDimension::Campaign, goal, filter, conn).await
+}
+
+/// # `summary_get_sources`
+/// Retrieves the top 25 traffic sources (`utm_source`, `ref` or click ID).
+///
+/// ## Arguments
+/// * `goal` - Name of the event counted as a conversion (default: any custom event).
+/// * `filter` - Scope of the data to summarize.
+/// * `conn` - The database connection.
+///
+/// ## Returns
+/// * `Json<Value>` - The sessions, unique visitors and conversions of each source.
+#[get("/sources?<goal>&<filter..>")]
+pub async fn summary_get_sources(
+    goal: Option<String>,
+    filter: StatsFilter,
+    conn: DbConn,
+) -> Json<Value> {
+    campaign_summary(CampaignDimension::Source, goal, filter, conn).await
+}
+
+/// # `summary_get_mediums`
+/// Retrieves the top 25 traffic mediums (`utm_medium` or click ID).
+///
+/// ## Arguments
+/// * `goal` - Name of the event counted as a conversion (default: any custom event).
+/// * `filter` - Scope of the data to summarize.
+/// * `conn` - The database connection.
+///
+/// ## Returns
+/// * `Json<Value>` - The sessions, unique visitors and conversions of each medium.
+#[get("/mediums?<goal>&<filter..>")]
+pub async fn summary_get_mediums(
+    goal: Option<String>,
+    filter: StatsFilter,
+    conn: DbConn,
+) -> Json<Value> {
+    campaign_summary(CampaignDimension::Medium, goal, filter, conn).await
+}
+
+/// # `parse_day`
+/// Parses a `YYYY-MM-DD` query parameter.
+fn parse_day(name: &str, value: Option<&str>, default: NaiveDate) -> Result<NaiveDate, String> {
+    value.map_or(Ok(default), |value| {
+        NaiveDate::parse_from_str(value, "%Y-%m-%d")
+            .map_err(|_| format!("Invalid {name} date {value}, expected YYYY-MM-DD"))
+    })
+}
+
+/// # `parse_range`
+/// Parses the `from` and `to` query parameters of a range of days, both included.
+///
+/// ## Arguments
+/// * `from` - First day of the range, `YYYY-MM-DD` (default: 6 days before `to`).
+/// * `to` - Last day of the range, `YYYY-MM-DD` (default: today, UTC).
+///
+/// ## Errors
+/// * `String` - If a day is malformed or the range is reversed.
+pub(crate) fn parse_range(
+    from: Option<&str>,
+    to: Option<&str>,
+) -> Result<(NaiveDate, NaiveDate), String> {
+    let today = Utc::now().date_naive();
+    let to = parse_day("to", to, today)?;
+    let from = parse_day("from", from, to - Duration::days(6))?;
+    if from > to {
+        return Err("from must not be after to".to_string());
+    }
+
+    Ok((from, to))
+}
+
+/// # `summary_get_vitals`
+/// Retrieves the p50, p75 and p95 of each Web Vital per URL and device type.
+///
+/// ## Arguments
+/// * `from` - First day of the range, `YYYY-MM-DD` (default: 6 days before `to`).
+/// * `to` - Last day of the range, included, `YYYY-MM-DD` (default: today, UTC).
+/// * `filter` - Scope of the data to summarize.
+/// * `conn` - The database connection.
+///
+/// ## Returns
+/// * `Json<Value>` - The number of samples and percentiles of each metric.
+#[get("/vitals?<from>&<to>&<filter..>")]
+pub async fn summary_get_vitals(
+    from: Option<String>,
+    to: Option<String>,
+    filter: StatsFilter,
+    conn: DbConn,
+) -> Json<Value> {
+    let (from, to) = match parse_range(from.as_deref(), to.as_deref()) {
+        Ok(range) => range,
+        Err(message) => return ApiResponse::bad_request(&message),
+    };
+
+    let start_time = from.and_hms_opt(0, 0, 0).unwrap_or_default();
+    let end_time = (to + Duration::days(1))
+        .and_hms_opt(0, 0, 0)
+        .unwrap_or_default();
+
+    match vitals(start_time, end_time, filter, &conn).await {
+        Ok(summary) => ApiResponse::success(json!({
+            "from": from,
+            "to": to,
+            "summary": summary
+        })),
+        Err(err) => ApiResponse::internal_error(&format!("Failed to retrieve vitals: {err}")),
+    }
+}
+
 /// # `summary_get_os_browsers`
 /// Retrieves the top 25 most used operating systems and browsers.
 ///
 /// ## Arguments
+/// * `filter` - Scope of the data to summarize.
 /// * `conn` - The database connection.
 ///
 /// ## Returns
 /// * `Json<Value>` - The top 25 most used operating systems and browsers.
-#[get("/os_browsers")]
-pub async fn summary_get_os_browsers(conn: DbConn) -> Json<Value> {
-    match os_browsers(&conn).await {
+#[get("/os_browsers?<filter..>")]
+pub async fn summary_get_os_browsers(filter: StatsFilter, conn: DbConn) -> Json<Value> {
+    match os_browsers(filter, &conn).await {
         Ok(summary) => ApiResponse::success(json!({
             "summary": summary
         })),
@@ -103,13 +349,14 @@ pub async fn summary_get_os_browsers(conn: DbConn) -> Json<Value> {
 /// Retrieves the top 25 most used referrers.
 ///
 /// ## Arguments
+/// * `filter` - Scope of the data to summarize.
 /// * `conn` - The database connection.
 ///
 /// ## Returns
 /// * `Json<Value>` - The top 25 most used referrers.
-#[get("/referrers")]
-pub async fn summary_get_referrers(conn: DbConn) -> Json<Value> {
-    match referrers(&conn).await {
+#[get("/referrers?<filter..>")]
+pub async fn summary_get_referrers(filter: StatsFilter, conn: DbConn) -> Json<Value> {
+    match referrers(filter, &conn).await {
         Ok(summary) => ApiResponse::success(json!({
             "summary": summary
         })),
@@ -117,17 +364,38 @@ pub async fn summary_get_referrers(conn: DbConn) -> Json<Value> {
     }
 }
 
+/// # `summary_get_channels`
+/// Retrieves the sessions and unique visitors of each channel (search, social, email,
+/// paid, referral, direct, internal).
+///
+/// ## Arguments
+/// * `filter` - Scope of the data to summarize.
+/// * `conn` - The database connection.
+///
+/// ## Returns
+/// * `Json<Value>` - The sessions of each channel, most used first.
+#[get("/channels?<filter..>")]
+pub async fn summary_get_channels(filter: StatsFilter, conn: DbConn) -> Json<Value> {
+    match channels(filter, &conn).await {
+        Ok(summary) => ApiResponse::success(json!({
+            "summary": summary
+        })),
+        Err(err) => ApiResponse::internal_error(&format!("Failed to retrieve channels: {err}")),
+    }
+}
+
 /// # `summary_get_weekly_event_counts`
 /// Retrieves the weekly event counts for the last 7 days.
 ///
 /// ## Arguments
+/// * `filter` - Scope of the data to summarize.
 /// * `conn` - The database connection.
 ///
 /// ## Returns
 /// * `Json<Value>` - The weekly event counts for the last 7 days.
-#[get("/weekly")]
-pub async fn summary_get_weekly_event_counts(conn: DbConn) -> Json<Value> {
-    match weekly(&conn).await {
+#[get("/weekly?<filter..>")]
+pub async fn summary_get_weekly_event_counts(filter: StatsFilter, conn: DbConn) -> Json<Value> {
+    match weekly(filter, &conn).await {
         Ok(summary) => ApiResponse::success(json!({
             "summary": summary
         })),
@@ -139,13 +407,14 @@ pub async fn summary_get_weekly_event_counts(conn: DbConn) -> Json<Value> {
 /// Calculates percentage changes in traffic volume between current and previous time periods (day, week, month) to show growth or decline trends.
 ///
 /// ## Arguments
+/// * `filter` - Scope of the data to summarize.
 /// * `conn` - The database connection.
 ///
 /// ## Returns
 /// * `Json<Value>` - The percentage changes in traffic volume between current and previous time periods.
-#[get("/percentages")]
-pub async fn summary_get_percentages(conn: DbConn) -> Json<Value> {
-    match percentages(&conn).await {
+#[get("/percentages?<filter..>")]
+pub async fn summary_get_percentages(filter: StatsFilter, conn: DbConn) -> Json<Value> {
+    match percentages(filter, &conn).await {
         Ok(summary) => ApiResponse::success(json!({
             "summary": summary
         })),
@@ -156,13 +425,30 @@ pub async fn summary_get_percentages(conn: DbConn) -> Json<Value> {
 /// # `summary_get_urls`
 /// Retrieves the top 25 most visited URLs from the past 7 days, ordered by visit count.
 ///
-/// ## Arguments * `conn` - The database connection.
+/// ## Arguments
+/// * `group` - `url` (default) to count each URL, or `template` to count each path template.
+/// * `filter` - Scope of the data to summarize.
+/// * `conn` - The database connection.
 ///
 /// ## Returns
 /// * `Json<Value>` - The top 25 most visited URLs from the past 7 days, ordered by visit count.
-#[get("/urls")]
-pub async fn summary_get_urls(conn: DbConn) -> Json<Value> {
-    match urls(&conn).await {
+#[get("/urls?<group>&<filter..>")]
+pub async fn summary_get_urls(
+    group: Option<String>,
+    filter: StatsFilter,
+    conn: DbConn,
+) -> Json<Value> {
+    let grouping = match group.as_deref() {
+        None | Some("url") => UrlGrouping::Url,
+        Some("template") => UrlGrouping::Template,
+        Some(group) => {
+            return ApiResponse::bad_request(&format!(
+                "Invalid group {group}: expected url or template"
+            ));
+        }
+    };
+
+    match urls(grouping, filter, &conn).await {
         Ok(summary) => ApiResponse::success(json!({
             "summary": summary
         })),
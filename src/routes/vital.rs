@@ -0,0 +1,69 @@
+use std::sync::Arc;
+
+use rocket::{State, post, serde::json::Json};
+use serde_json::{Value, json};
+
+use crate::{
+    AppState, DbConn, PageOrigin, UserAgentInfo,
+    api_response::ApiResponse,
+    models::{Vital, VitalsQuery},
+    routes::event::{authorize_report, find_site, normalize_url},
+};
+
+/// # `vital_insert`
+/// Handles POST requests to record the Web Vitals of a page.
+///
+/// Like `/event/batch`, any content type is accepted so that `navigator.sendBeacon`
+/// works without a CORS preflight.
+///
+/// ## Arguments
+/// * `body` - JSON object with the page URL, collector and metrics
+/// * `user_agent_info` - The user agent of the client, if any
+/// * `page_origin` - The origin of the page sending the vitals
+/// * `state` - The application state
+/// * `conn` - Database connection
+#[post("/", data = "<body>")]
+pub async fn vital_insert(
+    body: String,
+    user_agent_info: Option<UserAgentInfo>,
+    page_origin: PageOrigin,
+    state: &State<Arc<AppState>>,
+    conn: DbConn,
+) -> Json<Value> {
+    let vitals_data: VitalsQuery = match serde_json::from_str(&body) {
+        Ok(vitals_data) => vitals_data,
+        Err(e) => return ApiResponse::bad_request(&format!("Invalid vitals: {e}")),
+    };
+
+    let collector = match authorize_report(
+        &vitals_data.url,
+        &vitals_data.collector_id,
+        vitals_data.token.as_deref(),
+        vitals_data.site_id.as_ref(),
+        user_agent_info.as_ref(),
+        page_origin.0.as_deref(),
+        state,
+        &conn,
+    )
+    .await
+    {
+        Ok(collector) => collector,
+        Err(response) => return response,
+    };
+
+    let url = match find_site(collector.site_id.as_ref(), &conn).await {
+        Ok(site) => normalize_url(&vitals_data.url, site.as_ref()).url,
+        Err(response) => return response,
+    };
+    let vitals = match vitals_data.into_vitals(&url) {
+        Ok(vitals) => vitals,
+        Err(message) => return ApiResponse::bad_request(&message),
+    };
+
+    match Vital::insert_batch(vitals, &conn).await {
+        Ok(count) => ApiResponse::created(json!({
+            "message": &format!("{count} vitals recorded successfully")
+        })),
+        Err(e) => ApiResponse::internal_error(&format!("Failed to record vitals: {e}")),
+    }
+}
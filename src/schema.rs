@@ -1,5 +1,16 @@
 // @generated automatically by Diesel CLI.
 
+diesel::table! {
+    api_key (id) {
+        id -> Text,
+        site_id -> Text,
+        name -> Text,
+        key_hash -> Text,
+        created_at -> Nullable<Timestamp>,
+        last_used_at -> Nullable<Timestamp>,
+    }
+}
+
 diesel::table! {
     city (id) {
         id -> Nullable<Integer>,
@@ -8,6 +19,7 @@ diesel::table! {
         latitude -> Nullable<Float>,
         longitude -> Nullable<Float>,
         created_at -> Nullable<Timestamp>,
+        timezone -> Nullable<Text>,
     }
 }
 
@@ -19,6 +31,42 @@ diesel::table! {
         os -> Nullable<Text>,
         browser -> Nullable<Text>,
         created_at -> Nullable<Timestamp>,
+        site_id -> Nullable<Text>,
+        os_version -> Nullable<Text>,
+        browser_version -> Nullable<Text>,
+        device_type -> Nullable<Text>,
+        engine -> Nullable<Text>,
+        is_bot -> Bool,
+        bot_reason -> Nullable<Text>,
+        visitor_id -> Nullable<Text>,
+        source -> Text,
+    }
+}
+
+diesel::table! {
+    error_group (id) {
+        id -> Text,
+        site_id -> Nullable<Text>,
+        message -> Text,
+        source -> Nullable<Text>,
+        count -> Integer,
+        first_seen -> Timestamp,
+        last_seen -> Timestamp,
+    }
+}
+
+diesel::table! {
+    error_occurrence (id) {
+        id -> Text,
+        error_group_id -> Text,
+        collector_id -> Text,
+        url -> Text,
+        message -> Text,
+        source -> Nullable<Text>,
+        line_number -> Nullable<Integer>,
+        column_number -> Nullable<Integer>,
+        stack -> Nullable<Text>,
+        created_at -> Nullable<Timestamp>,
     }
 }
 
@@ -30,10 +78,102 @@ diesel::table! {
         name -> Text,
         collector_id -> Text,
         created_at -> Nullable<Timestamp>,
+        props -> Nullable<Text>,
+        engagement_ms -> Nullable<Integer>,
+        utm_source -> Nullable<Text>,
+        utm_medium -> Nullable<Text>,
+        utm_campaign -> Nullable<Text>,
+        utm_term -> Nullable<Text>,
+        utm_content -> Nullable<Text>,
+        ref_param -> Nullable<Text>,
+        click_id -> Nullable<Text>,
+        raw_url -> Nullable<Text>,
+        path_template -> Nullable<Text>,
+        referrer_host -> Nullable<Text>,
+        referrer_domain -> Nullable<Text>,
+        referrer_path -> Nullable<Text>,
+        channel -> Nullable<Text>,
+    }
+}
+
+diesel::table! {
+    geo_cache (network) {
+        network -> Text,
+        city -> Text,
+        country -> Text,
+        latitude -> Nullable<Float>,
+        longitude -> Nullable<Float>,
+        timezone -> Nullable<Text>,
+        provider -> Text,
+        expires_at -> Timestamp,
+        created_at -> Nullable<Timestamp>,
+    }
+}
+
+diesel::table! {
+    log_import (id) {
+        id -> Text,
+        site_id -> Text,
+        path -> Text,
+        fingerprint -> Text,
+        lines -> Integer,
+        collectors -> Integer,
+        events -> Integer,
+        created_at -> Nullable<Timestamp>,
+        updated_at -> Nullable<Timestamp>,
+    }
+}
+
+diesel::table! {
+    site (id) {
+        id -> Text,
+        domain -> Text,
+        name -> Text,
+        timezone -> Text,
+        settings -> Text,
+        created_at -> Nullable<Timestamp>,
+    }
+}
+
+diesel::table! {
+    visitor_salt (day) {
+        day -> Date,
+        salt -> Text,
+        created_at -> Nullable<Timestamp>,
+    }
+}
+
+diesel::table! {
+    vital (id) {
+        id -> Text,
+        collector_id -> Text,
+        url -> Text,
+        name -> Text,
+        value -> Double,
+        created_at -> Nullable<Timestamp>,
     }
 }
 
+diesel::joinable!(api_key -> site (site_id));
 diesel::joinable!(collector -> city (city_id));
+diesel::joinable!(collector -> site (site_id));
+diesel::joinable!(error_group -> site (site_id));
+diesel::joinable!(error_occurrence -> collector (collector_id));
+diesel::joinable!(error_occurrence -> error_group (error_group_id));
 diesel::joinable!(event -> collector (collector_id));
+diesel::joinable!(log_import -> site (site_id));
+diesel::joinable!(vital -> collector (collector_id));
 
-diesel::allow_tables_to_appear_in_same_query!(city, collector, event,);
+diesel::allow_tables_to_appear_in_same_query!(
+    api_key,
+    city,
+    collector,
+    error_group,
+    error_occurrence,
+    event,
+    geo_cache,
+    log_import,
+    site,
+    visitor_salt,
+    vital,
+);
@@ -0,0 +1,379 @@
+use std::{net::IpAddr, str::FromStr};
+
+use chrono::{DateTime, NaiveDateTime};
+use regex::Regex;
+
+/// nginx `log_format` of the Common Log Format, also Apache's `common`
+const COMMON_FORMAT: &str =
+    r#"$remote_addr - $remote_user [$time_local] "$request" $status $body_bytes_sent"#;
+/// nginx `log_format` of the Combined Log Format, also Apache's `combined` and nginx's default
+const COMBINED_FORMAT: &str = r#"$remote_addr - $remote_user [$time_local] "$request" $status $body_bytes_sent "$http_referer" "$http_user_agent""#;
+
+/// Extensions of files that are not pages: styles, scripts, images, fonts, media, data
+const ASSET_EXTENSIONS: [&str; 29] = [
+    "css", "js", "mjs", "map", "json", "xml", "txt", "png", "jpg", "jpeg", "gif", "svg", "ico",
+    "webp", "avif", "bmp", "woff", "woff2", "ttf", "otf", "eot", "mp4", "webm", "mp3", "ogg",
+    "wav", "pdf", "zip", "gz",
+];
+
+/// # `LogEntry`
+/// A request read from an access log.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LogEntry {
+    pub ip: IpAddr,
+    /// When the request was received, in UTC
+    pub time: NaiveDateTime,
+    pub method: String,
+    /// Path and query string of the request, e.g. `/blog?page=2`
+    pub target: String,
+    pub status: u16,
+    pub referrer: Option<String>,
+    /// Empty when the client sent none
+    pub user_agent: String,
+    /// `Host` of the request, when the format logs it
+    pub host: Option<String>,
+    /// `http` or `https`, when the format logs it
+    pub scheme: Option<String>,
+}
+
+impl LogEntry {
+    /// # `is_page_view`
+    /// Whether the request loaded a page: a successful (or not modified) `GET` of a path
+    /// that is not an asset such as a stylesheet, an image or a font.
+    ///
+    /// ## Returns
+    /// * `bool` - Whether the request counts as a page view
+    #[must_use]
+    pub fn is_page_view(&self) -> bool {
+        if self.method != "GET" || !((200..300).contains(&self.status) || self.status == 304) {
+            return false;
+        }
+
+        let path = self.target.split(['?', '#']).next().unwrap_or_default();
+        if path.starts_with("/.well-known/") {
+            return false;
+        }
+        let file = path.rsplit('/').next().unwrap_or_default();
+
+        !file.rsplit_once('.').is_some_and(|(_, extension)| {
+            ASSET_EXTENSIONS.contains(&extension.to_lowercase().as_str())
+        })
+    }
+
+    /// # `url`
+    /// Rebuilds the URL of the page that was requested.
+    ///
+    /// ## Arguments
+    /// * `default_host` - Host used when the format does not log it, e.g. the site domain
+    ///
+    /// ## Returns
+    /// * `String` - The URL, `https` unless the format logs the scheme
+    #[must_use]
+    pub fn url(&self, default_host: &str) -> String {
+        format!(
+            "{}://{}{}",
+            self.scheme.as_deref().unwrap_or("https"),
+            self.host.as_deref().unwrap_or(default_host),
+            self.target
+        )
+    }
+}
+
+/// # `LogFormat`
+/// Layout of the lines of an access log, written as an nginx `log_format` string:
+/// `$remote_addr - $remote_user [$time_local] "$request" $status ...`.
+///
+/// `combined` and `common` name the Combined and Common Log Formats, shared by nginx and
+/// Apache. Other formats must log `$remote_addr`, `$time_local` or `$time_iso8601`,
+/// `$status`, and `$request` or both `$request_method` and `$request_uri`. `$http_referer`,
+/// `$http_user_agent`, `$host` (or `$http_host`, `$server_name`) and `$scheme` are read
+/// when present, other variables are ignored.
+#[derive(Debug, Clone)]
+pub struct LogFormat {
+    pattern: Regex,
+}
+
+impl FromStr for LogFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let format = match s.trim() {
+            "combined" => COMBINED_FORMAT,
+            "common" => COMMON_FORMAT,
+            format => format,
+        };
+
+        let mut pattern = String::from("^");
+        let mut variables: Vec<String> = Vec::new();
+        let mut chars = format.chars().peekable();
+        while let Some(c) = chars.next() {
+            if c != '$' {
+                pattern.push_str(&regex::escape(&c.to_string()));
+                continue;
+            }
+
+            let mut name = String::new();
+            while let Some(next) =
+                chars.next_if(|next| next.is_ascii_alphanumeric() || *next == '_')
+            {
+                name.push(next);
+            }
+            if name.is_empty() {
+                pattern.push_str(&regex::escape("$"));
+            } else if variables.contains(&name) {
+                pattern.push_str("(?:.*?)");
+            } else {
+                pattern.push_str(&format!("(?P<{name}>.*?)"));
+                variables.push(name);
+            }
+        }
+        pattern.push('$');
+
+        let has = |name: &str| variables.iter().any(|variable| variable == name);
+        if !has("remote_addr") {
+            return Err("Log format must include $remote_addr".to_string());
+        }
+        if !has("time_local") && !has("time_iso8601") {
+            return Err("Log format must include $time_local or $time_iso8601".to_string());
+        }
+        if !has("status") {
+            return Err("Log format must include $status".to_string());
+        }
+        if !(has("request") || has("request_method") && has("request_uri")) {
+            return Err(
+                "Log format must include $request, or $request_method and $request_uri".to_string(),
+            );
+        }
+
+        Regex::new(&pattern)
+            .map(|pattern| LogFormat { pattern })
+            .map_err(|e| format!("Invalid log format: {e}"))
+    }
+}
+
+impl LogFormat {
+    /// # `parse_line`
+    /// Reads a line of the log.
+    ///
+    /// ## Arguments
+    /// * `line` - The line, without its line break
+    ///
+    /// ## Returns
+    /// * `Option<LogEntry>` - The request, or None if the line does not match the format
+    #[must_use]
+    pub fn parse_line(&self, line: &str) -> Option<LogEntry> {
+        let captures = self.pattern.captures(line)?;
+        let field = |name: &str| {
+            captures
+                .name(name)
+                .map(|value| value.as_str())
+                .filter(|value| !value.is_empty() && *value != "-")
+        };
+
+        let time = match field("time_local") {
+            Some(time) => DateTime::parse_from_str(time, "%d/%b/%Y:%H:%M:%S %z").ok()?,
+            None => DateTime::parse_from_rfc3339(field("time_iso8601")?).ok()?,
+        }
+        .naive_utc();
+
+        let (method, target) = match field("request") {
+            Some(request) => {
+                let mut parts = request.split_whitespace();
+                (parts.next()?.to_string(), parts.next()?.to_string())
+            }
+            None => (
+                field("request_method")?.to_string(),
+                field("request_uri")?.to_string(),
+            ),
+        };
+        // Requests through a proxy name the whole URL
+        let target = match url::Url::parse(&target) {
+            Ok(url) if url.has_host() => match url.query() {
+                Some(query) => format!("{}?{query}", url.path()),
+                None => url.path().to_string(),
+            },
+            _ if target.starts_with('/') => target,
+            _ => return None,
+        };
+
+        Some(LogEntry {
+            ip: field("remote_addr")?.parse().ok()?,
+            time,
+            method,
+            target,
+            status: field("status")?.parse().ok()?,
+            referrer: field("http_referer").map(ToString::to_string),
+            user_agent: field("http_user_agent").unwrap_or_default().to_string(),
+            host: field("host")
+                .or_else(|| field("http_host"))
+                .or_else(|| field("server_name"))
+                .map(str::to_lowercase),
+            scheme: field("scheme").map(ToString::to_string),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const USER_AGENT: &str =
+        "Mozilla/5.0 (X11; Linux x86_64; rv:125.0) Gecko/20100101 Firefox/125.0";
+
+    fn time(time: &str) -> NaiveDateTime {
+        NaiveDateTime::parse_from_str(time, "%Y-%m-%d %H:%M:%S").unwrap()
+    }
+
+    fn entry(target: &str, method: &str, status: u16) -> LogEntry {
+        LogEntry {
+            ip: "203.0.113.7".parse().unwrap(),
+            time: time("2026-10-10 13:55:36"),
+            method: method.to_string(),
+            target: target.to_string(),
+            status,
+            referrer: None,
+            user_agent: USER_AGENT.to_string(),
+            host: None,
+            scheme: None,
+        }
+    }
+
+    #[test]
+    fn parses_combined_lines() {
+        let format: LogFormat = "combined".parse().unwrap();
+        let line = format!(
+            r#"203.0.113.7 - - [10/Oct/2026:13:55:36 +0000] "GET /blog?page=2 HTTP/1.1" 200 5120 "https://www.google.com/" "{USER_AGENT}""#
+        );
+
+        let entry = format.parse_line(&line).unwrap();
+        assert_eq!(entry.ip, "203.0.113.7".parse::<IpAddr>().unwrap());
+        assert_eq!(entry.time, time("2026-10-10 13:55:36"));
+        assert_eq!(entry.method, "GET");
+        assert_eq!(entry.target, "/blog?page=2");
+        assert_eq!(entry.status, 200);
+        assert_eq!(entry.referrer.as_deref(), Some("https://www.google.com/"));
+        assert_eq!(entry.user_agent, USER_AGENT);
+        assert_eq!(entry.host, None);
+        assert_eq!(entry.url("example.com"), "https://example.com/blog?page=2");
+    }
+
+    #[test]
+    fn parses_common_lines() {
+        let format: LogFormat = "common".parse().unwrap();
+        let line =
+            r#"2001:db8::1 - frank [10/Oct/2026:13:55:36 +0000] "GET /about HTTP/1.0" 304 -"#;
+
+        let entry = format.parse_line(line).unwrap();
+        assert_eq!(entry.ip, "2001:db8::1".parse::<IpAddr>().unwrap());
+        assert_eq!(entry.target, "/about");
+        assert_eq!(entry.status, 304);
+        assert_eq!(entry.referrer, None);
+        assert_eq!(entry.user_agent, "");
+    }
+
+    #[test]
+    fn parses_custom_formats() {
+        let format: LogFormat =
+            r#"$remote_addr [$time_iso8601] $scheme://$host "$request" $status "$http_referer" "$http_user_agent""#
+                .parse()
+                .unwrap();
+        let line = format!(
+            r#"203.0.113.7 [2026-10-10T13:55:36+00:00] http://Blog.Example.com "GET / HTTP/2.0" 200 "-" "{USER_AGENT}""#
+        );
+
+        let entry = format.parse_line(&line).unwrap();
+        assert_eq!(entry.time, time("2026-10-10 13:55:36"));
+        assert_eq!(entry.host.as_deref(), Some("blog.example.com"));
+        assert_eq!(entry.scheme.as_deref(), Some("http"));
+        assert_eq!(entry.referrer, None);
+        assert_eq!(entry.url("example.com"), "http://blog.example.com/");
+
+        let format: LogFormat = "$remote_addr [$time_local] $request_method $request_uri $status"
+            .parse()
+            .unwrap();
+        let entry = format
+            .parse_line("203.0.113.7 [10/Oct/2026:13:55:36 +0000] GET /pricing 200")
+            .unwrap();
+        assert_eq!(entry.method, "GET");
+        assert_eq!(entry.target, "/pricing");
+    }
+
+    #[test]
+    fn strips_the_origin_of_proxied_requests() {
+        let format: LogFormat = "common".parse().unwrap();
+
+        let entry = format
+            .parse_line(r#"203.0.113.7 - - [10/Oct/2026:13:55:36 +0000] "GET http://example.com/docs?q=1 HTTP/1.1" 200 10"#)
+            .unwrap();
+        assert_eq!(entry.target, "/docs?q=1");
+
+        assert!(format
+            .parse_line(r#"203.0.113.7 - - [10/Oct/2026:13:55:36 +0000] "CONNECT example.com:443 HTTP/1.1" 200 10"#)
+            .is_none());
+    }
+
+    #[test]
+    fn converts_times_to_utc() {
+        let format: LogFormat = "common".parse().unwrap();
+
+        let entry = format
+            .parse_line(r#"203.0.113.7 - - [10/Oct/2026:01:30:00 +0200] "GET / HTTP/1.1" 200 10"#)
+            .unwrap();
+        assert_eq!(entry.time, time("2026-10-09 23:30:00"));
+    }
+
+    #[test]
+    fn skips_lines_that_do_not_match() {
+        let format: LogFormat = "combined".parse().unwrap();
+
+        for line in [
+            "",
+            "garbage",
+            r#"not-an-ip - - [10/Oct/2026:13:55:36 +0000] "GET / HTTP/1.1" 200 10 "-" "-""#,
+            r#"203.0.113.7 - - [yesterday] "GET / HTTP/1.1" 200 10 "-" "-""#,
+        ] {
+            assert!(format.parse_line(line).is_none(), "{line}");
+        }
+    }
+
+    #[test]
+    fn rejects_incomplete_formats() {
+        for format in [
+            r#"[$time_local] "$request" $status"#,
+            r#"$remote_addr [$time_local] "$request""#,
+            r#"$remote_addr "$request" $status"#,
+            r#"$remote_addr [$time_local] $request_method $status"#,
+        ] {
+            assert!(format.parse::<LogFormat>().is_err(), "{format}");
+        }
+    }
+
+    #[test]
+    fn recognizes_page_views() {
+        let cases = [
+            ("/", "GET", 200, true),
+            ("/blog/post?page=2", "GET", 200, true),
+            ("/about", "GET", 304, true),
+            ("/v1.2/release-notes", "GET", 200, true),
+            ("/", "POST", 200, false),
+            ("/", "HEAD", 200, false),
+            ("/missing", "GET", 404, false),
+            ("/old", "GET", 301, false),
+            ("/styles/main.css", "GET", 200, false),
+            ("/app.js?v=3", "GET", 200, false),
+            ("/images/Logo.PNG", "GET", 200, false),
+            ("/fonts/inter.woff2", "GET", 200, false),
+            ("/favicon.ico", "GET", 200, false),
+            ("/.well-known/security.txt", "GET", 200, false),
+            ("/.well-known/change-password", "GET", 200, false),
+        ];
+
+        for (target, method, status, page_view) in cases {
+            assert_eq!(
+                entry(target, method, status).is_page_view(),
+                page_view,
+                "{method} {target} {status}"
+            );
+        }
+    }
+}
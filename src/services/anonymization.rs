@@ -0,0 +1,209 @@
+use std::{fmt::Display, net::IpAddr, str::FromStr};
+
+use hmac::{Hmac, Mac};
+use ipnet::IpNet;
+use sha2::Sha256;
+
+use crate::services::hex;
+
+/// Prefix length IPv4 addresses are truncated to
+const IPV4_PREFIX: u8 = 24;
+/// Prefix length IPv6 addresses are truncated to
+const IPV6_PREFIX: u8 = 48;
+
+/// # `IpAnonymization`
+/// How visitor IP addresses are stored.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum IpAnonymization {
+    /// The full address, as received
+    Full,
+    /// The network of the address: /24 for IPv4, /48 for IPv6
+    Truncate,
+    /// A keyed hash of the address, which can be compared but not reversed
+    Hash,
+    /// Nothing at all
+    None,
+}
+
+impl Display for IpAnonymization {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mode = match self {
+            IpAnonymization::Full => "full",
+            IpAnonymization::Truncate => "truncate",
+            IpAnonymization::Hash => "hash",
+            IpAnonymization::None => "none",
+        };
+        write!(f, "{mode}")
+    }
+}
+
+impl FromStr for IpAnonymization {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "full" => Ok(IpAnonymization::Full),
+            "truncate" => Ok(IpAnonymization::Truncate),
+            "hash" => Ok(IpAnonymization::Hash),
+            "none" => Ok(IpAnonymization::None),
+            other => Err(format!("Unknown IP anonymization mode: {other}")),
+        }
+    }
+}
+
+/// # `IpAnonymizer`
+/// Turns visitor IP addresses into what may be stored, according to the configured mode.
+#[derive(Debug, Clone)]
+pub struct IpAnonymizer {
+    mode: IpAnonymization,
+    key: Vec<u8>,
+}
+
+impl IpAnonymizer {
+    /// # `new`
+    /// Builds an anonymizer.
+    ///
+    /// ## Arguments
+    /// * `mode` - How addresses are stored
+    /// * `key` - Secret key of the `hash` mode
+    ///
+    /// ## Errors
+    /// If the `hash` mode is used without a key.
+    ///
+    /// ## Returns
+    /// * `Result<IpAnonymizer, String>` - The anonymizer
+    pub fn new(mode: IpAnonymization, key: Option<&str>) -> Result<Self, String> {
+        let key = key.unwrap_or_default().as_bytes().to_vec();
+
+        if mode == IpAnonymization::Hash && key.is_empty() {
+            return Err("IP_HASH_KEY is required to hash IP addresses".to_string());
+        }
+
+        Ok(Self { mode, key })
+    }
+
+    #[must_use]
+    pub fn mode(&self) -> IpAnonymization {
+        self.mode
+    }
+
+    /// # `anonymize`
+    /// Returns what should be stored for an IP address.
+    ///
+    /// ## Arguments
+    /// * `ip` - The IP address of the visitor
+    ///
+    /// ## Returns
+    /// * `String` - The address, its network, its hash, or an empty string
+    #[must_use]
+    pub fn anonymize(&self, ip: IpAddr) -> String {
+        match self.mode {
+            IpAnonymization::Full => ip.to_string(),
+            IpAnonymization::Truncate => {
+                let prefix = match ip {
+                    IpAddr::V4(_) => IPV4_PREFIX,
+                    IpAddr::V6(_) => IPV6_PREFIX,
+                };
+
+                IpNet::new(ip, prefix)
+                    .map(|network| network.trunc().addr().to_string())
+                    .unwrap_or_default()
+            }
+            IpAnonymization::Hash => {
+                let mut mac = Hmac::<Sha256>::new_from_slice(&self.key)
+                    .expect("HMAC accepts keys of any length");
+                mac.update(ip.to_string().as_bytes());
+
+                hex::encode(&mac.finalize().into_bytes())
+            }
+            IpAnonymization::None => String::new(),
+        }
+    }
+
+    /// # `anonymize_stored`
+    /// Returns what should replace an already stored origin.
+    /// Values that are not IP addresses, like hashes, were anonymized before and are kept.
+    ///
+    /// ## Arguments
+    /// * `origin` - The stored origin
+    ///
+    /// ## Returns
+    /// * `String` - The new origin
+    #[must_use]
+    pub fn anonymize_stored(&self, origin: &str) -> String {
+        match origin.parse::<IpAddr>() {
+            Ok(ip) => self.anonymize(ip),
+            Err(_) if self.mode == IpAnonymization::None => String::new(),
+            Err(_) => origin.to_string(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn anonymize(mode: IpAnonymization, ip: &str) -> String {
+        IpAnonymizer::new(mode, Some("secret"))
+            .unwrap()
+            .anonymize(ip.parse().unwrap())
+    }
+
+    #[test]
+    fn parses_modes() {
+        assert_eq!("Truncate ".parse(), Ok(IpAnonymization::Truncate));
+        assert_eq!("none".parse(), Ok(IpAnonymization::None));
+        assert!("mask".parse::<IpAnonymization>().is_err());
+    }
+
+    #[test]
+    fn anonymizes_addresses() {
+        let cases = [
+            (IpAnonymization::Full, "81.2.69.142", "81.2.69.142"),
+            (
+                IpAnonymization::Full,
+                "2001:db8:85a3::8a2e:370:7334",
+                "2001:db8:85a3::8a2e:370:7334",
+            ),
+            (IpAnonymization::Truncate, "81.2.69.142", "81.2.69.0"),
+            (
+                IpAnonymization::Truncate,
+                "2001:db8:85a3:8d3:1319:8a2e:370:7348",
+                "2001:db8:85a3::",
+            ),
+            (IpAnonymization::Truncate, "::1", "::"),
+            (
+                IpAnonymization::Hash,
+                "81.2.69.142",
+                "848d05046e90d95fe078242418188736ad8d0b93cd9d3ce3fec539556b73f8ae",
+            ),
+            (IpAnonymization::None, "81.2.69.142", ""),
+        ];
+
+        for (mode, ip, expected) in cases {
+            assert_eq!(anonymize(mode, ip), expected, "{mode} {ip}");
+        }
+    }
+
+    #[test]
+    fn hashes_depend_on_the_key() {
+        let other = IpAnonymizer::new(IpAnonymization::Hash, Some("other"))
+            .unwrap()
+            .anonymize("81.2.69.142".parse().unwrap());
+
+        assert_ne!(other, anonymize(IpAnonymization::Hash, "81.2.69.142"));
+        assert!(IpAnonymizer::new(IpAnonymization::Hash, None).is_err());
+        assert!(IpAnonymizer::new(IpAnonymization::Hash, Some("")).is_err());
+    }
+
+    #[test]
+    fn anonymizes_stored_origins() {
+        let truncate = IpAnonymizer::new(IpAnonymization::Truncate, None).unwrap();
+        assert_eq!(truncate.anonymize_stored("81.2.69.142"), "81.2.69.0");
+        assert_eq!(truncate.anonymize_stored("81.2.69.0"), "81.2.69.0");
+        assert_eq!(truncate.anonymize_stored("848d0504"), "848d0504");
+
+        let none = IpAnonymizer::new(IpAnonymization::None, None).unwrap();
+        assert_eq!(none.anonymize_stored("848d0504"), "");
+    }
+}
@@ -0,0 +1,257 @@
+use std::{collections::HashSet, fmt::Display, fs, net::IpAddr, path::Path};
+
+use ipnet::IpNet;
+use maxminddb::{Reader, geoip2};
+use serde::Deserialize;
+
+use crate::services::user_agent::DeviceType;
+
+/// Networks shipped with the application, used unless `BOT_NETWORKS` points to another file
+const BUNDLED_NETWORKS: &str = include_str!("../../resources/bot_networks.json");
+
+/// # `BotReason`
+/// Why a visitor was flagged as a bot.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum BotReason {
+    /// The user agent belongs to a crawler or an HTTP library
+    UserAgent,
+    /// The request comes from a known crawler IP range
+    CrawlerNetwork,
+    /// The browser is automated (`navigator.webdriver`) or looks headless
+    Headless,
+    /// The request comes from a hosting provider rather than an ISP
+    Datacenter,
+}
+
+impl BotReason {
+    #[must_use]
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            BotReason::UserAgent => "user_agent",
+            BotReason::CrawlerNetwork => "crawler_network",
+            BotReason::Headless => "headless",
+            BotReason::Datacenter => "datacenter",
+        }
+    }
+}
+
+impl Display for BotReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+#[derive(Deserialize)]
+struct RawBotNetworks {
+    crawler_networks: Vec<String>,
+    datacenter_asns: Vec<u32>,
+}
+
+/// # `BotDetector`
+/// Combines user agent, IP range, headless and ASN signals to tell bots from humans.
+pub struct BotDetector {
+    crawler_networks: Vec<IpNet>,
+    datacenter_asns: HashSet<u32>,
+    asn_database: Option<Reader<Vec<u8>>>,
+}
+
+impl BotDetector {
+    /// # `from_json`
+    /// Builds a detector from the content of a bot networks file.
+    ///
+    /// ## Arguments
+    /// * `json` - The networks, see `resources/bot_networks.json`
+    /// * `asn_database` - Path to a MaxMind / DB-IP ASN `.mmdb` database, if any
+    ///
+    /// ## Errors
+    /// If the JSON is malformed, a network is invalid or the ASN database cannot be read.
+    ///
+    /// ## Returns
+    /// * `Result<BotDetector, String>` - The detector
+    pub fn from_json(json: &str, asn_database: Option<&str>) -> Result<Self, String> {
+        let raw: RawBotNetworks =
+            serde_json::from_str(json).map_err(|e| format!("Invalid bot networks file: {e}"))?;
+        let crawler_networks = raw
+            .crawler_networks
+            .iter()
+            .map(|network| {
+                network
+                    .parse::<IpNet>()
+                    .map_err(|e| format!("Invalid network {network}: {e}"))
+            })
+            .collect::<Result<Vec<_>, String>>()?;
+        let asn_database = asn_database
+            .map(|path| {
+                Reader::open_readfile(path).map_err(|e| format!("Failed to open {path}: {e}"))
+            })
+            .transpose()?;
+
+        Ok(Self {
+            crawler_networks,
+            datacenter_asns: raw.datacenter_asns.into_iter().collect(),
+            asn_database,
+        })
+    }
+
+    /// # `new`
+    /// Builds a detector from an optional networks file, falling back to the bundled one.
+    ///
+    /// ## Arguments
+    /// * `networks` - Path to a bot networks file, if any
+    /// * `asn_database` - Path to a MaxMind / DB-IP ASN `.mmdb` database, if any
+    ///
+    /// ## Errors
+    /// If a file cannot be read or is invalid.
+    ///
+    /// ## Returns
+    /// * `Result<BotDetector, String>` - The detector
+    pub fn new(networks: Option<&Path>, asn_database: Option<&str>) -> Result<Self, String> {
+        match networks {
+            Some(path) => {
+                let content = fs::read_to_string(path)
+                    .map_err(|e| format!("Failed to read {}: {e}", path.display()))?;
+                Self::from_json(&content, asn_database)
+            }
+            None => Self::from_json(BUNDLED_NETWORKS, asn_database),
+        }
+    }
+
+    /// # `asn`
+    /// Returns the autonomous system number of an IP address, when an ASN database is configured.
+    fn asn(&self, ip: IpAddr) -> Option<u32> {
+        self.asn_database
+            .as_ref()?
+            .lookup::<geoip2::Asn>(ip)
+            .ok()?
+            .autonomous_system_number
+    }
+
+    /// # `detect`
+    /// Tells whether a visitor is a bot, and why.
+    ///
+    /// ## Arguments
+    /// * `ip` - The IP address of the visitor
+    /// * `device_type` - The device type parsed from its user agent
+    /// * `headless` - Whether the request shows headless-browser signals
+    ///
+    /// ## Returns
+    /// * `Option<BotReason>` - The first matching reason, `None` for humans
+    #[must_use]
+    pub fn detect(&self, ip: IpAddr, device_type: DeviceType, headless: bool) -> Option<BotReason> {
+        if device_type == DeviceType::Bot {
+            return Some(BotReason::UserAgent);
+        }
+
+        if self
+            .crawler_networks
+            .iter()
+            .any(|network| network.contains(&ip))
+        {
+            return Some(BotReason::CrawlerNetwork);
+        }
+
+        if headless {
+            return Some(BotReason::Headless);
+        }
+
+        if self
+            .asn(ip)
+            .is_some_and(|asn| self.datacenter_asns.contains(&asn))
+        {
+            return Some(BotReason::Datacenter);
+        }
+
+        None
+    }
+}
+
+impl Default for BotDetector {
+    fn default() -> Self {
+        Self::from_json(BUNDLED_NETWORKS, None).expect("Bundled bot networks are invalid")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn detects_bots() {
+        let detector = BotDetector::default();
+        let cases = [
+            ("81.2.69.142", DeviceType::Desktop, false, None),
+            ("2a01:e0a::1", DeviceType::Mobile, false, None),
+            (
+                "81.2.69.142",
+                DeviceType::Bot,
+                false,
+                Some(BotReason::UserAgent),
+            ),
+            (
+                "66.249.66.1",
+                DeviceType::Desktop,
+                false,
+                Some(BotReason::CrawlerNetwork),
+            ),
+            (
+                "2001:4860:4801:10::1",
+                DeviceType::Desktop,
+                false,
+                Some(BotReason::CrawlerNetwork),
+            ),
+            (
+                "81.2.69.142",
+                DeviceType::Desktop,
+                true,
+                Some(BotReason::Headless),
+            ),
+            // The user agent is the strongest signal
+            (
+                "66.249.66.1",
+                DeviceType::Bot,
+                true,
+                Some(BotReason::UserAgent),
+            ),
+            (
+                "66.249.66.1",
+                DeviceType::Desktop,
+                true,
+                Some(BotReason::CrawlerNetwork),
+            ),
+        ];
+
+        for (ip, device_type, headless, reason) in cases {
+            assert_eq!(
+                detector.detect(ip.parse().unwrap(), device_type, headless),
+                reason,
+                "{ip} {device_type} {headless}"
+            );
+        }
+    }
+
+    #[test]
+    fn reads_networks_files() {
+        let detector = BotDetector::from_json(
+            r#"{"crawler_networks": ["10.0.0.0/8"], "datacenter_asns": []}"#,
+            None,
+        )
+        .unwrap();
+        assert_eq!(
+            detector.detect("10.1.2.3".parse().unwrap(), DeviceType::Desktop, false),
+            Some(BotReason::CrawlerNetwork)
+        );
+        assert_eq!(
+            detector.detect("66.249.66.1".parse().unwrap(), DeviceType::Desktop, false),
+            None
+        );
+
+        assert!(
+            BotDetector::from_json(
+                r#"{"crawler_networks": ["10.0.0.0/33"], "datacenter_asns": []}"#,
+                None
+            )
+            .is_err()
+        );
+        assert!(BotDetector::from_json(r#"{"crawler_networks": []}"#, None).is_err());
+    }
+}
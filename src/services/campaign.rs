@@ -0,0 +1,129 @@
+use url::Url;
+
+/// Query parameters holding the ID of an ad click
+pub const CLICK_ID_PARAMS: [&str; 2] = ["gclid", "fbclid"];
+
+/// Longest campaign parameter value stored
+const MAX_CAMPAIGN_VALUE_LENGTH: usize = 256;
+
+/// # `CampaignParams`
+/// Marketing parameters found in the query string of a landing page.
+///
+/// Click IDs (`gclid`, `fbclid`) identify a single ad click, so only which one was
+/// present is kept, never its value.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CampaignParams {
+    pub utm_source: Option<String>,
+    pub utm_medium: Option<String>,
+    pub utm_campaign: Option<String>,
+    pub utm_term: Option<String>,
+    pub utm_content: Option<String>,
+    /// `?ref=`, used by directories and newsletters instead of `utm_source`
+    pub ref_param: Option<String>,
+    /// `gclid` or `fbclid`, when the visit comes from a Google or Facebook ad
+    pub click_id: Option<String>,
+}
+
+impl CampaignParams {
+    /// # `from_url`
+    /// Extracts the campaign parameters of a URL, before its query string is dropped.
+    ///
+    /// Sources and mediums are lowercased, as `Newsletter` and `newsletter` are the same
+    /// source to everyone. Empty values are ignored and long ones truncated.
+    ///
+    /// ## Arguments
+    /// * `url` - The URL of the page, as reported by the tracking script
+    ///
+    /// ## Returns
+    /// * `CampaignParams` - The parameters found, all `None` for a URL without any
+    #[must_use]
+    pub fn from_url(url: &str) -> Self {
+        let Ok(url) = Url::parse(url) else {
+            return Self::default();
+        };
+
+        let mut params = Self::default();
+        for (key, value) in url.query_pairs() {
+            let value = value.trim();
+            if value.is_empty() {
+                continue;
+            }
+            let value: String = value.chars().take(MAX_CAMPAIGN_VALUE_LENGTH).collect();
+
+            match key.as_ref() {
+                "utm_source" => params.utm_source = Some(value.to_lowercase()),
+                "utm_medium" => params.utm_medium = Some(value.to_lowercase()),
+                "utm_campaign" => params.utm_campaign = Some(value),
+                "utm_term" => params.utm_term = Some(value),
+                "utm_content" => params.utm_content = Some(value),
+                "ref" => params.ref_param = Some(value.to_lowercase()),
+                key if CLICK_ID_PARAMS.contains(&key) && params.click_id.is_none() => {
+                    params.click_id = Some(key.to_string());
+                }
+                _ => {}
+            }
+        }
+
+        params
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn extracts_utm_parameters() {
+        let params = CampaignParams::from_url(
+            "https://example.com/pricing?utm_source=Newsletter&utm_medium=Email&utm_campaign=Spring%20Sale&utm_term=analytics&utm_content=Header&plan=pro",
+        );
+
+        assert_eq!(
+            params,
+            CampaignParams {
+                utm_source: Some("newsletter".to_string()),
+                utm_medium: Some("email".to_string()),
+                utm_campaign: Some("Spring Sale".to_string()),
+                utm_term: Some("analytics".to_string()),
+                utm_content: Some("Header".to_string()),
+                ref_param: None,
+                click_id: None,
+            }
+        );
+    }
+
+    #[test]
+    fn keeps_which_click_id_was_present() {
+        let params = CampaignParams::from_url("https://example.com/?gclid=Cj0KCQiA&fbclid=IwAR2");
+        assert_eq!(params.click_id.as_deref(), Some("gclid"));
+
+        let params = CampaignParams::from_url("https://example.com/?fbclid=IwAR2&ref=ProductHunt");
+        assert_eq!(params.click_id.as_deref(), Some("fbclid"));
+        assert_eq!(params.ref_param.as_deref(), Some("producthunt"));
+    }
+
+    #[test]
+    fn ignores_empty_and_missing_values() {
+        for url in [
+            "https://example.com/",
+            "https://example.com/?utm_source=&utm_medium=%20",
+            "https://example.com/#utm_source=fragment",
+            "/relative?utm_source=newsletter",
+            "not a url",
+        ] {
+            assert_eq!(
+                CampaignParams::from_url(url),
+                CampaignParams::default(),
+                "{url}"
+            );
+        }
+    }
+
+    #[test]
+    fn truncates_long_values() {
+        let url = format!("https://example.com/?utm_campaign={}", "é".repeat(300));
+        let campaign = CampaignParams::from_url(&url).utm_campaign.unwrap();
+
+        assert_eq!(campaign.chars().count(), MAX_CAMPAIGN_VALUE_LENGTH);
+    }
+}
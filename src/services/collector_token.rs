@@ -0,0 +1,266 @@
+use std::fmt::Display;
+
+use base64::{Engine, engine::general_purpose::URL_SAFE_NO_PAD};
+use chrono::Utc;
+use hmac::{Hmac, Mac};
+use rand::{RngCore, rngs::OsRng};
+use rocket::http::Status;
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+
+/// # `CollectorClaims`
+/// What a collector token vouches for.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CollectorClaims {
+    /// The collector the token was issued for
+    pub collector_id: String,
+    /// The site the collector belongs to, if any
+    pub site_id: Option<String>,
+    /// The origin of the page that loaded the script, if known
+    pub origin: Option<String>,
+    /// Unix timestamp after which the token is refused
+    pub expires_at: i64,
+}
+
+/// # `TokenError`
+/// Why an event was refused by collector token verification.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TokenError {
+    /// No token was sent
+    Missing,
+    /// The token is malformed, forged or issued for another collector
+    Invalid,
+    /// The token was valid but is too old
+    Expired,
+    /// The token is valid but its collector does not exist
+    UnknownCollector,
+    /// The event is reported for another site than the token's
+    SiteMismatch,
+    /// The event is sent from another origin than the token's
+    OriginMismatch,
+}
+
+impl TokenError {
+    #[must_use]
+    pub fn status(&self) -> Status {
+        match self {
+            TokenError::Missing | TokenError::Invalid | TokenError::Expired => Status::Unauthorized,
+            TokenError::UnknownCollector => Status::NotFound,
+            TokenError::SiteMismatch | TokenError::OriginMismatch => Status::Forbidden,
+        }
+    }
+}
+
+impl Display for TokenError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let message = match self {
+            TokenError::Missing => "Missing collector token",
+            TokenError::Invalid => "Invalid collector token",
+            TokenError::Expired => "Expired collector token",
+            TokenError::UnknownCollector => "Unknown collector",
+            TokenError::SiteMismatch => "Collector does not belong to this site",
+            TokenError::OriginMismatch => "Collector token was issued for another origin",
+        };
+        write!(f, "{message}")
+    }
+}
+
+/// # `CollectorTokenSigner`
+/// Issues and verifies the tokens `/stats.js` hands to the tracking script, so that
+/// events can only be sent for collectors the server created.
+///
+/// Tokens are `base64url(claims).base64url(hmac_sha256(claims))`.
+pub struct CollectorTokenSigner {
+    key: Vec<u8>,
+    ttl_seconds: i64,
+}
+
+impl CollectorTokenSigner {
+    /// # `new`
+    /// Builds a signer.
+    ///
+    /// ## Arguments
+    /// * `secret` - Signing key, a random one is generated when absent
+    /// * `ttl_seconds` - How long a token stays valid
+    ///
+    /// ## Returns
+    /// * `CollectorTokenSigner` - The signer
+    #[must_use]
+    pub fn new(secret: Option<&str>, ttl_seconds: i64) -> Self {
+        let key = match secret {
+            Some(secret) => secret.as_bytes().to_vec(),
+            None => {
+                let mut key = vec![0u8; 32];
+                OsRng.fill_bytes(&mut key);
+                key
+            }
+        };
+
+        Self { key, ttl_seconds }
+    }
+
+    fn mac(&self, payload: &[u8]) -> Hmac<Sha256> {
+        let mut mac =
+            Hmac::<Sha256>::new_from_slice(&self.key).expect("HMAC accepts keys of any length");
+        mac.update(payload);
+        mac
+    }
+
+    /// # `issue`
+    /// Issues a token for a collector.
+    ///
+    /// ## Arguments
+    /// * `collector_id` - The collector
+    /// * `site_id` - The site of the collector, if any
+    /// * `origin` - The origin of the page that loaded the script, if known
+    ///
+    /// ## Returns
+    /// * `String` - The signed token
+    #[must_use]
+    pub fn issue(&self, collector_id: &str, site_id: Option<&str>, origin: Option<&str>) -> String {
+        let claims = CollectorClaims {
+            collector_id: collector_id.to_string(),
+            site_id: site_id.map(ToString::to_string),
+            origin: origin.map(ToString::to_string),
+            expires_at: Utc::now().timestamp() + self.ttl_seconds,
+        };
+        let payload = serde_json::to_vec(&claims).expect("Claims are serializable");
+        let signature = self.mac(&payload).finalize().into_bytes();
+
+        format!(
+            "{}.{}",
+            URL_SAFE_NO_PAD.encode(&payload),
+            URL_SAFE_NO_PAD.encode(signature)
+        )
+    }
+
+    /// # `verify`
+    /// Checks a token's signature, expiry and origin.
+    ///
+    /// ## Arguments
+    /// * `token` - The token sent with the event, if any
+    /// * `origin` - The origin the event is sent from, if known
+    ///
+    /// ## Errors
+    /// * `TokenError` - Why the token is refused
+    ///
+    /// ## Returns
+    /// * `Result<CollectorClaims, TokenError>` - What the token vouches for
+    pub fn verify(
+        &self,
+        token: Option<&str>,
+        origin: Option<&str>,
+    ) -> Result<CollectorClaims, TokenError> {
+        let token = token.ok_or(TokenError::Missing)?;
+        let (payload, signature) = token.split_once('.').ok_or(TokenError::Invalid)?;
+        let payload = URL_SAFE_NO_PAD
+            .decode(payload)
+            .map_err(|_| TokenError::Invalid)?;
+        let signature = URL_SAFE_NO_PAD
+            .decode(signature)
+            .map_err(|_| TokenError::Invalid)?;
+
+        self.mac(&payload)
+            .verify_slice(&signature)
+            .map_err(|_| TokenError::Invalid)?;
+
+        let claims: CollectorClaims =
+            serde_json::from_slice(&payload).map_err(|_| TokenError::Invalid)?;
+
+        if claims.expires_at < Utc::now().timestamp() {
+            return Err(TokenError::Expired);
+        }
+        if claims.origin.is_some() && claims.origin.as_deref() != origin {
+            return Err(TokenError::OriginMismatch);
+        }
+
+        Ok(claims)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ORIGIN: &str = "https://example.com";
+
+    fn signer() -> CollectorTokenSigner {
+        CollectorTokenSigner::new(Some("secret"), 3600)
+    }
+
+    #[test]
+    fn verifies_issued_tokens() {
+        let signer = signer();
+        let token = signer.issue("col_1", Some("site_1"), Some(ORIGIN));
+
+        let claims = signer.verify(Some(&token), Some(ORIGIN)).unwrap();
+        assert_eq!(claims.collector_id, "col_1");
+        assert_eq!(claims.site_id.as_deref(), Some("site_1"));
+        assert_eq!(claims.origin.as_deref(), Some(ORIGIN));
+        assert!(claims.expires_at > Utc::now().timestamp());
+
+        // Tokens issued without an origin are accepted from any
+        let token = signer.issue("col_2", None, None);
+        assert!(signer.verify(Some(&token), Some(ORIGIN)).is_ok());
+        assert!(signer.verify(Some(&token), None).is_ok());
+    }
+
+    #[test]
+    fn refuses_missing_and_malformed_tokens() {
+        let signer = signer();
+
+        assert_eq!(signer.verify(None, None), Err(TokenError::Missing));
+        for token in ["", "no-dot", "a.b", "!!!.???", "e30.e30"] {
+            assert_eq!(
+                signer.verify(Some(token), None),
+                Err(TokenError::Invalid),
+                "{token}"
+            );
+        }
+    }
+
+    #[test]
+    fn refuses_tampered_tokens() {
+        let signer = signer();
+        let token = signer.issue("col_1", Some("site_1"), None);
+        let (_, signature) = token.split_once('.').unwrap();
+
+        let mut claims = signer.verify(Some(&token), None).unwrap();
+        claims.collector_id = "col_2".to_string();
+        let forged = format!(
+            "{}.{signature}",
+            URL_SAFE_NO_PAD.encode(serde_json::to_vec(&claims).unwrap())
+        );
+        assert_eq!(signer.verify(Some(&forged), None), Err(TokenError::Invalid));
+
+        let other = CollectorTokenSigner::new(Some("other"), 3600);
+        assert_eq!(other.verify(Some(&token), None), Err(TokenError::Invalid));
+    }
+
+    #[test]
+    fn refuses_expired_tokens() {
+        let signer = CollectorTokenSigner::new(Some("secret"), -1);
+        let token = signer.issue("col_1", None, None);
+
+        assert_eq!(signer.verify(Some(&token), None), Err(TokenError::Expired));
+    }
+
+    #[test]
+    fn refuses_tokens_from_other_origins() {
+        let signer = signer();
+        let token = signer.issue("col_1", None, Some(ORIGIN));
+
+        assert_eq!(
+            signer.verify(Some(&token), Some("https://evil.example")),
+            Err(TokenError::OriginMismatch)
+        );
+        assert_eq!(
+            signer.verify(Some(&token), Some("http://example.com")),
+            Err(TokenError::OriginMismatch)
+        );
+        assert_eq!(
+            signer.verify(Some(&token), None),
+            Err(TokenError::OriginMismatch)
+        );
+    }
+}
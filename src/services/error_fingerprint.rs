@@ -0,0 +1,292 @@
+use regex::Regex;
+use sha2::{Digest, Sha256};
+
+use crate::services::hex;
+
+/// Number of stack frames that identify an error
+const FINGERPRINT_FRAMES: usize = 3;
+
+/// # `ErrorFingerprint`
+/// Identity of a group of similar errors.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ErrorFingerprint {
+    /// Hash of the site, normalized message, source and top stack frames
+    pub id: String,
+    pub message: String,
+    pub source: Option<String>,
+}
+
+/// # `ErrorFingerprinter`
+/// Groups JavaScript errors that only differ by their variable parts.
+///
+/// Messages lose their numbers, hexadecimal IDs and UUIDs, and scripts and stack frames
+/// their query strings and line and column numbers, so that the same error thrown with
+/// other values or by another build of the same script ends up in the same group.
+pub struct ErrorFingerprinter {
+    uuid: Regex,
+    hex: Regex,
+    number: Regex,
+    query: Regex,
+    position: Regex,
+}
+
+impl ErrorFingerprinter {
+    /// # `new`
+    /// Builds a fingerprinter.
+    ///
+    /// ## Panics
+    /// If one of the regex patterns is invalid.
+    ///
+    /// ## Returns
+    /// * `ErrorFingerprinter` - The fingerprinter
+    #[must_use]
+    pub fn new() -> Self {
+        Self {
+            uuid: Regex::new(
+                r"(?i)\b[0-9a-f]{8}-[0-9a-f]{4}-[0-9a-f]{4}-[0-9a-f]{4}-[0-9a-f]{12}\b",
+            )
+            .expect("Invalid regex pattern"),
+            hex: Regex::new(r"(?i)\b0x[0-9a-f]+\b|\b[0-9a-f]{8,}\b")
+                .expect("Invalid regex pattern"),
+            number: Regex::new(r"\d+").expect("Invalid regex pattern"),
+            query: Regex::new(r"[?#][^\s:)]*").expect("Invalid regex pattern"),
+            position: Regex::new(r"(:\d+)+(\)?)\s*$").expect("Invalid regex pattern"),
+        }
+    }
+
+    /// # `normalize_message`
+    /// Strips the variable parts of an error message.
+    ///
+    /// ## Arguments
+    /// * `message` - The message as reported by the browser
+    ///
+    /// ## Returns
+    /// * `String` - The message shared by the whole group
+    #[must_use]
+    pub fn normalize_message(&self, message: &str) -> String {
+        let message = message.trim();
+        let message = message.strip_prefix("Uncaught ").unwrap_or(message);
+        let message = self.uuid.replace_all(message, "<uuid>");
+        let message = self.hex.replace_all(&message, "<hex>");
+
+        self.number.replace_all(&message, "<n>").to_string()
+    }
+
+    /// # `normalize_source`
+    /// Drops the query string and fragment of a script URL, often a cache-busting version.
+    ///
+    /// ## Arguments
+    /// * `source` - The URL of the script that threw
+    ///
+    /// ## Returns
+    /// * `String` - The URL shared by every version of the script
+    #[must_use]
+    pub fn normalize_source(&self, source: &str) -> String {
+        self.query.replace_all(source.trim(), "").to_string()
+    }
+
+    /// # `top_frames`
+    /// Returns the first frames of a stack trace, without positions nor query strings.
+    ///
+    /// Both V8 (`at fn (file:1:2)`) and Gecko / WebKit (`fn@file:1:2`) formats are understood;
+    /// the message line V8 puts first is skipped.
+    ///
+    /// ## Arguments
+    /// * `stack` - The stack trace as reported by the browser
+    ///
+    /// ## Returns
+    /// * `Vec<String>` - Up to three normalized frames
+    #[must_use]
+    pub fn top_frames(&self, stack: &str) -> Vec<String> {
+        stack
+            .lines()
+            .map(str::trim)
+            .filter(|line| line.starts_with("at ") || line.contains('@'))
+            .take(FINGERPRINT_FRAMES)
+            .map(|line| {
+                let line = self.position.replace(line, "$2");
+                self.query.replace_all(&line, "").to_string()
+            })
+            .collect()
+    }
+
+    /// # `fingerprint`
+    /// Computes the group an error belongs to.
+    ///
+    /// ## Arguments
+    /// * `site_id` - The site the error happened on, groups are never shared between sites
+    /// * `message` - The error message
+    /// * `source` - The URL of the script that threw, if known
+    /// * `stack` - The stack trace, if any
+    ///
+    /// ## Returns
+    /// * `ErrorFingerprint` - The group ID, with the normalized message and source
+    #[must_use]
+    pub fn fingerprint(
+        &self,
+        site_id: Option<&str>,
+        message: &str,
+        source: Option<&str>,
+        stack: Option<&str>,
+    ) -> ErrorFingerprint {
+        let message = self.normalize_message(message);
+        let source = source
+            .map(|source| self.normalize_source(source))
+            .filter(|source| !source.is_empty());
+        let frames = stack
+            .map(|stack| self.top_frames(stack))
+            .unwrap_or_default();
+
+        let mut hasher = Sha256::new();
+        for part in [
+            site_id.unwrap_or_default(),
+            &message,
+            source.as_deref().unwrap_or_default(),
+            &frames.join("\n"),
+        ] {
+            hasher.update(part.as_bytes());
+            hasher.update([0]);
+        }
+        let id = hex::encode(&hasher.finalize()[..16]);
+
+        ErrorFingerprint {
+            id,
+            message,
+            source,
+        }
+    }
+}
+
+impl Default for ErrorFingerprinter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const V8_STACK: &str = "TypeError: Cannot read properties of undefined (reading 'id')
+    at renderCart (https://example.com/app.js?v=42:120:15)
+    at HTMLButtonElement.<anonymous> (https://example.com/app.js?v=42:88:9)
+    at dispatch (https://example.com/vendor.js:2:3301)
+    at handle (https://example.com/vendor.js:2:1200)";
+
+    #[test]
+    fn normalizes_messages() {
+        let fingerprinter = ErrorFingerprinter::new();
+        let cases = [
+            (
+                "Uncaught TypeError: Cannot read properties of undefined (reading 'id')",
+                "TypeError: Cannot read properties of undefined (reading 'id')",
+            ),
+            ("Item 42 not found", "Item <n> not found"),
+            (
+                "Order 3f2a9c1d-0b4e-4f6a-9c2d-7e8f9a0b1c2d failed",
+                "Order <uuid> failed",
+            ),
+            ("Bad pointer 0x7ffd1234", "Bad pointer <hex>"),
+            ("Session deadbeefcafe expired", "Session <hex> expired"),
+            ("  Script error.  ", "Script error."),
+        ];
+
+        for (message, expected) in cases {
+            assert_eq!(fingerprinter.normalize_message(message), expected);
+        }
+    }
+
+    #[test]
+    fn normalizes_sources() {
+        let fingerprinter = ErrorFingerprinter::new();
+
+        assert_eq!(
+            fingerprinter.normalize_source("https://example.com/app.js?v=42#x"),
+            "https://example.com/app.js"
+        );
+        assert_eq!(
+            fingerprinter.normalize_source("https://example.com/app.js"),
+            "https://example.com/app.js"
+        );
+    }
+
+    #[test]
+    fn keeps_the_top_frames() {
+        let fingerprinter = ErrorFingerprinter::new();
+
+        assert_eq!(
+            fingerprinter.top_frames(V8_STACK),
+            [
+                "at renderCart (https://example.com/app.js)",
+                "at HTMLButtonElement.<anonymous> (https://example.com/app.js)",
+                "at dispatch (https://example.com/vendor.js)",
+            ]
+        );
+        assert_eq!(
+            fingerprinter.top_frames(
+                "renderCart@https://example.com/app.js?v=42:120:15\n@https://example.com/app.js:88:9"
+            ),
+            [
+                "renderCart@https://example.com/app.js",
+                "@https://example.com/app.js"
+            ]
+        );
+        assert!(fingerprinter.top_frames("TypeError: oops").is_empty());
+    }
+
+    #[test]
+    fn groups_similar_errors() {
+        let fingerprinter = ErrorFingerprinter::new();
+        let fingerprint = |site: &str, message: &str, source: &str, stack: &str| {
+            fingerprinter.fingerprint(Some(site), message, Some(source), Some(stack))
+        };
+
+        let first = fingerprint(
+            "site_1",
+            "Item 42 not found",
+            "https://example.com/app.js?v=1",
+            V8_STACK,
+        );
+        assert_eq!(first.id.len(), 32);
+        assert_eq!(first.message, "Item <n> not found");
+        assert_eq!(first.source.as_deref(), Some("https://example.com/app.js"));
+
+        // Other values, another build
+        let same = fingerprint(
+            "site_1",
+            "Uncaught Item 7 not found",
+            "https://example.com/app.js?v=2",
+            &V8_STACK.replace(":120:15", ":131:2"),
+        );
+        assert_eq!(same.id, first.id);
+
+        for other in [
+            fingerprint(
+                "site_2",
+                "Item 42 not found",
+                "https://example.com/app.js",
+                V8_STACK,
+            ),
+            fingerprint(
+                "site_1",
+                "Cart 42 not found",
+                "https://example.com/app.js",
+                V8_STACK,
+            ),
+            fingerprint(
+                "site_1",
+                "Item 42 not found",
+                "https://example.com/cart.js",
+                V8_STACK,
+            ),
+            fingerprint(
+                "site_1",
+                "Item 42 not found",
+                "https://example.com/app.js",
+                &V8_STACK.replace("renderCart", "renderList"),
+            ),
+        ] {
+            assert_ne!(other.id, first.id);
+        }
+    }
+}
@@ -0,0 +1,153 @@
+use std::{net::IpAddr, path::Path};
+
+use maxminddb::{MaxMindDBError, Reader, geoip2};
+
+use crate::services::location::{GeoLocator, Location, LocationError};
+
+/// # `GeoIpDatabase`
+/// Offline IP geolocation backed by a MaxMind / DB-IP `.mmdb` city database.
+///
+/// The whole file is loaded in memory once, lookups never touch the network.
+pub struct GeoIpDatabase {
+    reader: Reader<Vec<u8>>,
+}
+
+impl GeoIpDatabase {
+    /// # `open`
+    /// Loads a `.mmdb` database from disk.
+    ///
+    /// ## Arguments
+    /// * `path` - Path to the `.mmdb` file
+    ///
+    /// ## Errors
+    /// If the file cannot be read or is not a valid MaxMind database.
+    ///
+    /// ## Returns
+    /// * `Result<GeoIpDatabase, MaxMindDBError>` - The loaded database
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, MaxMindDBError> {
+        Ok(Self {
+            reader: Reader::open_readfile(path)?,
+        })
+    }
+
+    /// # `from_bytes`
+    /// Loads a `.mmdb` database from memory, mostly useful for fixtures.
+    ///
+    /// ## Arguments
+    /// * `bytes` - Raw content of a `.mmdb` file
+    ///
+    /// ## Errors
+    /// If the bytes are not a valid MaxMind database.
+    ///
+    /// ## Returns
+    /// * `Result<GeoIpDatabase, MaxMindDBError>` - The loaded database
+    pub fn from_bytes(bytes: Vec<u8>) -> Result<Self, MaxMindDBError> {
+        Ok(Self {
+            reader: Reader::from_source(bytes)?,
+        })
+    }
+
+    /// # `lookup`
+    /// Resolves the city, country, coordinates and timezone of an IP address.
+    ///
+    /// ## Arguments
+    /// * `ip` - The IP address to look up
+    ///
+    /// ## Errors
+    /// * `LocationError::NotFound` - If the address is not in the database
+    /// * `LocationError::Database` - If the database record cannot be decoded
+    ///
+    /// ## Returns
+    /// * `Result<Location, LocationError>` - The location of the IP address
+    pub fn lookup(&self, ip: IpAddr) -> Result<Location, LocationError> {
+        let record: geoip2::City = self.reader.lookup(ip)?;
+
+        let city = record
+            .city
+            .and_then(|city| city.names)
+            .and_then(|names| names.get("en").map(ToString::to_string))
+            .unwrap_or_default();
+        let country = record
+            .country
+            .and_then(|country| country.iso_code)
+            .map(ToString::to_string)
+            .unwrap_or_default();
+        let location = record.location;
+
+        #[allow(clippy::cast_possible_truncation)]
+        Ok(Location {
+            city,
+            country,
+            latitude: location
+                .as_ref()
+                .and_then(|l| l.latitude)
+                .map(|lat| lat as f32),
+            longitude: location
+                .as_ref()
+                .and_then(|l| l.longitude)
+                .map(|lng| lng as f32),
+            timezone: location.and_then(|l| l.time_zone).map(ToString::to_string),
+        })
+    }
+}
+
+#[rocket::async_trait]
+impl GeoLocator for GeoIpDatabase {
+    fn name(&self) -> &'static str {
+        "mmdb"
+    }
+
+    async fn locate(&self, ip: IpAddr) -> Result<Location, LocationError> {
+        self.lookup(ip)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A two-record city database: `81.2.69.0/24` in London, `90.0.0.0/24` in Paris
+    const FIXTURE: &[u8] = include_bytes!("../../tests/fixtures/city.mmdb");
+
+    fn fixture() -> GeoIpDatabase {
+        GeoIpDatabase::from_bytes(FIXTURE.to_vec()).expect("Fixture database is invalid")
+    }
+
+    #[test]
+    fn resolves_known_addresses() {
+        let database = fixture();
+
+        let location = database.lookup("81.2.69.142".parse().unwrap()).unwrap();
+        assert_eq!(location.city, "London");
+        assert_eq!(location.country, "GB");
+        assert_eq!(location.latitude, Some(51.5142));
+        assert_eq!(location.longitude, Some(-0.0931));
+        assert_eq!(location.timezone.as_deref(), Some("Europe/London"));
+
+        let location = database.lookup("90.0.0.1".parse().unwrap()).unwrap();
+        assert_eq!(location.city, "Paris");
+        assert_eq!(location.country, "FR");
+        assert_eq!(location.latitude, Some(48.8566));
+        assert_eq!(location.longitude, Some(2.3522));
+    }
+
+    #[test]
+    fn reports_unknown_addresses() {
+        let database = fixture();
+
+        for ip in ["81.2.70.1", "10.0.0.1", "203.0.113.7"] {
+            assert!(
+                matches!(
+                    database.lookup(ip.parse().unwrap()),
+                    Err(LocationError::NotFound)
+                ),
+                "{ip}"
+            );
+        }
+    }
+
+    #[test]
+    fn rejects_invalid_databases() {
+        assert!(GeoIpDatabase::from_bytes(b"not a database".to_vec()).is_err());
+    }
+}
@@ -0,0 +1,12 @@
+/// # `encode`
+/// Writes bytes as lowercase hexadecimal, two digits per byte.
+///
+/// ## Arguments
+/// * `bytes` - The bytes, e.g. a hash or a random secret
+///
+/// ## Returns
+/// * `String` - The hexadecimal string, twice as long as `bytes`
+#[must_use]
+pub fn encode(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{byte:02x}")).collect()
+}
@@ -1,5 +1,7 @@
 use serde::Deserialize;
-use std::env;
+use std::net::IpAddr;
+
+use crate::services::location::{GeoLocator, Location, LocationError};
 
 #[derive(Debug, Deserialize)]
 pub struct IpInfoResponse {
@@ -34,23 +36,35 @@ impl IpInfoResponse {
     }
 }
 
+impl From<IpInfoResponse> for Location {
+    fn from(info: IpInfoResponse) -> Self {
+        let (latitude, longitude) = info.coordinates().unwrap_or((0.0, 0.0));
+
+        Location {
+            city: info.city,
+            country: info.country,
+            latitude: Some(latitude),
+            longitude: Some(longitude),
+            timezone: info.timezone,
+        }
+    }
+}
+
 /// # `get_city_info`
 /// Get city information for a given IP address.
 ///
 /// This function sends a request to the `IPInfo` API to retrieve information about the city associated with the provided IP address.
-/// It requires an API token which should be set as the environment variable `IPINFO_TOKEN`.
 ///
 /// ## Arguments
 /// * `ip` - The IP address for which to retrieve city information.
+/// * `token` - The `IPInfo` API token.
 ///
 /// ## Errors
 /// Returns an error if the request to the `IPInfo` API fails.
 ///
 /// ## Returns
 /// * `IpInfoResponse` result
-pub async fn get_city_info(ip: &str) -> Result<IpInfoResponse, reqwest::Error> {
-    let token = env::var("IPINFO_TOKEN").unwrap_or_default();
-
+pub async fn get_city_info(ip: &str, token: &str) -> Result<IpInfoResponse, reqwest::Error> {
     let base_url = if ip.contains(':') {
         "https://v6.ipinfo.io"
     } else {
@@ -68,3 +82,27 @@ pub async fn get_city_info(ip: &str) -> Result<IpInfoResponse, reqwest::Error> {
         .json::<IpInfoResponse>()
         .await
 }
+
+/// # `IpInfoLocator`
+/// `GeoLocator` backed by the `IPInfo` HTTP API.
+pub struct IpInfoLocator {
+    token: String,
+}
+
+impl IpInfoLocator {
+    #[must_use]
+    pub fn new(token: String) -> Self {
+        Self { token }
+    }
+}
+
+#[rocket::async_trait]
+impl GeoLocator for IpInfoLocator {
+    fn name(&self) -> &'static str {
+        "ipinfo"
+    }
+
+    async fn locate(&self, ip: IpAddr) -> Result<Location, LocationError> {
+        Ok(get_city_info(&ip.to_string(), &self.token).await?.into())
+    }
+}
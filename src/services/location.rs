@@ -0,0 +1,112 @@
+use std::{fmt::Display, net::IpAddr};
+
+use crate::{
+    config::{AppConfig, GeoProvider},
+    services::{geoip::GeoIpDatabase, ip_location::IpInfoLocator, static_locator::StaticLocator},
+};
+
+/// # `Location`
+/// Geographic information resolved for an IP address, independently of the provider.
+#[derive(Debug, Clone, Default)]
+pub struct Location {
+    pub city: String,
+    pub country: String,
+    pub latitude: Option<f32>,
+    pub longitude: Option<f32>,
+    pub timezone: Option<String>,
+}
+
+/// # `LocationError`
+/// Errors that can happen while resolving the location of an IP address.
+#[derive(Debug)]
+pub enum LocationError {
+    /// The remote geolocation API could not be reached or answered with garbage
+    Request(reqwest::Error),
+    /// The local geolocation database could not be read
+    Database(maxminddb::MaxMindDBError),
+    /// The IP address is not known by the provider
+    NotFound,
+}
+
+impl Display for LocationError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LocationError::Request(e) => write!(f, "geolocation request failed: {e}"),
+            LocationError::Database(e) => write!(f, "geolocation database error: {e}"),
+            LocationError::NotFound => write!(f, "IP address not found"),
+        }
+    }
+}
+
+impl std::error::Error for LocationError {}
+
+impl From<reqwest::Error> for LocationError {
+    fn from(error: reqwest::Error) -> Self {
+        LocationError::Request(error)
+    }
+}
+
+impl From<maxminddb::MaxMindDBError> for LocationError {
+    fn from(error: maxminddb::MaxMindDBError) -> Self {
+        match error {
+            maxminddb::MaxMindDBError::AddressNotFoundError(_) => LocationError::NotFound,
+            error => LocationError::Database(error),
+        }
+    }
+}
+
+/// # `GeoLocator`
+/// A source able to resolve the location of an IP address.
+///
+/// Implemented by the `IPInfo` client, the local `.mmdb` database and the static table,
+/// the one in use is picked from the configuration by `build_locator`.
+#[rocket::async_trait]
+pub trait GeoLocator: Send + Sync {
+    /// Short name of the provider, used in logs
+    fn name(&self) -> &'static str;
+
+    /// # `locate`
+    /// Resolves the location of an IP address.
+    ///
+    /// ## Arguments
+    /// * `ip` - The IP address to look up
+    ///
+    /// ## Errors
+    /// If the provider fails or does not know the address.
+    ///
+    /// ## Returns
+    /// * `Result<Location, LocationError>` - The location of the IP address
+    async fn locate(&self, ip: IpAddr) -> Result<Location, LocationError>;
+}
+
+/// # `build_locator`
+/// Creates the `GeoLocator` selected by the configuration.
+///
+/// ## Arguments
+/// * `config` - The application configuration
+///
+/// ## Errors
+/// If the selected provider cannot be initialized (missing or invalid database/table file).
+///
+/// ## Returns
+/// * `Result<Box<dyn GeoLocator>, String>` - The configured locator
+pub fn build_locator(config: &AppConfig) -> Result<Box<dyn GeoLocator>, String> {
+    match config.geo_provider {
+        GeoProvider::IpInfo => Ok(Box::new(IpInfoLocator::new(config.ipinfo_token.clone()))),
+        GeoProvider::Mmdb => {
+            let path = config
+                .geoip_database
+                .as_ref()
+                .ok_or("GEOIP_DATABASE must be set to use the mmdb provider")?;
+
+            GeoIpDatabase::open(path)
+                .map(|database| Box::new(database) as Box<dyn GeoLocator>)
+                .map_err(|e| format!("Failed to open {path}: {e}"))
+        }
+        GeoProvider::Static => match &config.geo_static_table {
+            Some(path) => StaticLocator::from_file(path)
+                .map(|locator| Box::new(locator) as Box<dyn GeoLocator>),
+            None => Ok(Box::new(StaticLocator::development())),
+        },
+    }
+}
@@ -0,0 +1,406 @@
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::{BufRead, BufReader},
+    net::IpAddr,
+    path::Path,
+};
+
+use chrono::{Duration, NaiveDate, NaiveDateTime};
+use sha2::{Digest, Sha256};
+
+use crate::{
+    AppState, DbConn, UserAgentInfo,
+    models::{Collector, CollectorSource, Event, EventQuery, LogImport, Site, VisitorSalt},
+    routes::event::build_event,
+    services::{
+        access_log::{LogEntry, LogFormat},
+        hex,
+        session::build_collector,
+        tracker_event::TrackerEvent,
+    },
+};
+
+/// Lines read between two commits of an import
+const CHUNK_LINES: usize = 10_000;
+
+/// # `ImportProgress`
+/// How far an import went.
+#[derive(Debug, Clone, Default)]
+pub struct ImportProgress {
+    /// Lines read, including those imported by previous runs
+    pub lines: usize,
+    /// Lines skipped because previous runs imported them
+    pub resumed_lines: usize,
+    pub bytes: u64,
+    pub total_bytes: u64,
+    pub page_views: usize,
+    pub sessions: usize,
+    /// Page views of bots, which are not imported
+    pub bot_hits: usize,
+    /// Requests that are not page views: assets, errors, redirects, other methods
+    pub other_requests: usize,
+    /// Lines that do not match the log format
+    pub invalid_lines: usize,
+}
+
+impl ImportProgress {
+    /// # `percent`
+    /// Share of the file read so far.
+    ///
+    /// ## Returns
+    /// * `f64` - Between 0 and 100
+    #[must_use]
+    #[allow(clippy::cast_precision_loss)]
+    pub fn percent(&self) -> f64 {
+        if self.total_bytes == 0 {
+            100.0
+        } else {
+            self.bytes as f64 * 100.0 / self.total_bytes as f64
+        }
+    }
+}
+
+/// # `LogSessions`
+/// Groups the page views of an access log into collectors: hits of the same IP address
+/// and user agent belong to the same session until it is inactive for longer than the
+/// timeout.
+pub struct LogSessions {
+    site: Site,
+    timeout: Duration,
+    open: HashMap<(IpAddr, String), OpenSession>,
+    /// Random salts of past days, kept in memory only; `None` for live logs, which use
+    /// today's salt like the tracking script
+    salts: Option<HashMap<NaiveDate, String>>,
+}
+
+struct OpenSession {
+    /// `None` for bots, whose hits are skipped
+    collector_id: Option<String>,
+    last_seen: NaiveDateTime,
+}
+
+/// # `LogHit`
+/// What became of a request read from an access log.
+#[allow(clippy::large_enum_variant)]
+pub enum LogHit {
+    /// A page view, with the collector of the session when it was just started
+    PageView {
+        collector: Option<Collector>,
+        event: Event,
+    },
+    Bot,
+    Other,
+}
+
+impl LogSessions {
+    /// # `live`
+    /// Groups the hits of a log as they are written.
+    ///
+    /// ## Arguments
+    /// * `site` - The site the log belongs to
+    /// * `timeout` - Inactivity after which a visitor starts a new session
+    #[must_use]
+    pub fn live(site: Site, timeout: Duration) -> Self {
+        Self {
+            site,
+            timeout,
+            open: HashMap::new(),
+            salts: None,
+        }
+    }
+
+    /// # `historical`
+    /// Groups the hits of an old log. Visitor IDs are computed with a random salt per
+    /// day of the log, as today's salt would link visitors across days.
+    ///
+    /// ## Arguments
+    /// * `site` - The site the log belongs to
+    /// * `timeout` - Inactivity after which a visitor starts a new session
+    #[must_use]
+    pub fn historical(site: Site, timeout: Duration) -> Self {
+        Self {
+            site,
+            timeout,
+            open: HashMap::new(),
+            salts: Some(HashMap::new()),
+        }
+    }
+
+    /// # `site`
+    /// The site the log belongs to.
+    ///
+    /// ## Returns
+    /// * `&Site` - The site
+    #[must_use]
+    pub fn site(&self) -> &Site {
+        &self.site
+    }
+
+    /// # `record`
+    /// Turns a request into a page view of its session, starting a new session when the
+    /// visitor has none open: the visitor is then located and checked for bots like those
+    /// of `POST /session`.
+    ///
+    /// ## Arguments
+    /// * `entry` - The request
+    /// * `state` - The application state
+    /// * `conn` - Database connection
+    ///
+    /// ## Errors
+    /// If the database fails.
+    ///
+    /// ## Returns
+    /// * `Result<LogHit, String>` - The page view, or why the request is not one
+    pub async fn record(
+        &mut self,
+        entry: &LogEntry,
+        state: &AppState,
+        conn: &DbConn,
+    ) -> Result<LogHit, String> {
+        if !entry.is_page_view() {
+            return Ok(LogHit::Other);
+        }
+
+        let key = (entry.ip, entry.user_agent.clone());
+        let current = self
+            .open
+            .get_mut(&key)
+            .filter(|session| entry.time - session.last_seen <= self.timeout);
+
+        let (collector_id, collector) = match current {
+            Some(session) => {
+                session.last_seen = entry.time;
+                match &session.collector_id {
+                    Some(collector_id) => (collector_id.clone(), None),
+                    None => return Ok(LogHit::Bot),
+                }
+            }
+            None => {
+                let collector = self.start(entry, state, conn).await?;
+                self.open.insert(
+                    key,
+                    OpenSession {
+                        collector_id: collector.as_ref().map(|collector| collector.id.clone()),
+                        last_seen: entry.time,
+                    },
+                );
+                match collector {
+                    Some(collector) => (collector.id.clone(), Some(collector)),
+                    None => return Ok(LogHit::Bot),
+                }
+            }
+        };
+
+        let event_data = EventQuery {
+            url: entry.url(&self.site.domain),
+            referrer: entry.referrer.clone(),
+            // Every request is a full page load
+            name: TrackerEvent::Enter.to_string(),
+            collector_id,
+            token: None,
+            site_id: Some(self.site.id.clone()),
+            webdriver: false,
+            props: None,
+            engagement_ms: None,
+        };
+        let mut event = build_event(event_data, Some(&self.site), state);
+        event.created_at = Some(entry.time);
+
+        Ok(LogHit::PageView { collector, event })
+    }
+
+    /// Builds the collector of a new session, `None` for bots.
+    async fn start(
+        &mut self,
+        entry: &LogEntry,
+        state: &AppState,
+        conn: &DbConn,
+    ) -> Result<Option<Collector>, String> {
+        // Browsers always send a user agent, scripts sometimes don't
+        if entry.user_agent.is_empty() {
+            return Ok(None);
+        }
+
+        let user_agent_info = UserAgentInfo::relayed(&entry.user_agent, &state.user_agent_parser);
+        let mut collector = build_collector(
+            Some(self.site.id.clone()),
+            Some(entry.ip),
+            user_agent_info,
+            false,
+            CollectorSource::Log,
+            state,
+            conn,
+        )
+        .await
+        .map_err(|e| e.to_string())?;
+        if collector.is_bot {
+            return Ok(None);
+        }
+
+        if let Some(salts) = &mut self.salts {
+            let salt = salts
+                .entry(entry.time.date())
+                .or_insert_with(VisitorSalt::generate);
+            collector.visitor_id = collector.visitor_id.map(|_| {
+                VisitorSalt::visitor_id(salt, Some(&self.site.id), entry.ip, &entry.user_agent)
+            });
+        }
+        collector.created_at = Some(entry.time);
+
+        Ok(Some(collector))
+    }
+
+    /// # `close_inactive`
+    /// Forgets the sessions inactive for longer than the timeout, to bound memory use.
+    ///
+    /// ## Arguments
+    /// * `now` - Time of the latest request read
+    pub fn close_inactive(&mut self, now: NaiveDateTime) {
+        let timeout = self.timeout;
+        self.open
+            .retain(|_, session| now - session.last_seen <= timeout);
+    }
+}
+
+/// # `import_logs`
+/// Imports the page views of an access log into a site, with their original times.
+///
+/// Lines are committed in chunks, each with the number of lines imported so far: running
+/// the import again, after an interruption or once more lines were appended, only reads
+/// the lines that were not imported yet.
+///
+/// Sessions carry over from the logs read before with the same `sessions`, so rotated
+/// logs imported oldest first keep the visits that span them. Lines skipped because a
+/// previous run imported them are not read into sessions: their collectors and visitor
+/// IDs cannot be matched again, so a visit open where that run stopped is split in two.
+///
+/// ## Arguments
+/// * `path` - Path of the log file
+/// * `format` - Layout of the lines
+/// * `sessions` - Sessions of the site, shared by the logs of one import
+/// * `on_progress` - Called after each chunk
+/// * `state` - The application state
+/// * `conn` - Database connection
+///
+/// ## Errors
+/// If the file cannot be read, or the database fails. Chunks
+/// committed before the error are kept.
+///
+/// ## Returns
+/// * `Result<ImportProgress, String>` - What was read and imported
+pub async fn import_logs(
+    path: &Path,
+    format: &LogFormat,
+    sessions: &mut LogSessions,
+    mut on_progress: impl FnMut(&ImportProgress),
+    state: &AppState,
+    conn: &DbConn,
+) -> Result<ImportProgress, String> {
+    let open = || File::open(path).map_err(|e| format!("Failed to open {}: {e}", path.display()));
+    let mut first_line = Vec::new();
+    BufReader::new(open()?)
+        .read_until(b'\n', &mut first_line)
+        .map_err(|e| format!("Failed to read {}: {e}", path.display()))?;
+    if first_line.is_empty() {
+        return Err(format!("{} is empty", path.display()));
+    }
+    let fingerprint = hex::encode(&Sha256::digest(&first_line));
+
+    let import = LogImport::start(
+        sessions.site().id.clone(),
+        path.display().to_string(),
+        fingerprint,
+        conn,
+    )
+    .await
+    .map_err(|e| format!("Failed to start import: {e}"))?;
+
+    let file = open()?;
+    let mut progress = ImportProgress {
+        total_bytes: file
+            .metadata()
+            .map(|metadata| metadata.len())
+            .unwrap_or_default(),
+        ..ImportProgress::default()
+    };
+    let mut reader = BufReader::new(file);
+    let mut collectors = Vec::new();
+    let mut events = Vec::new();
+    let mut latest = None;
+    let mut line = Vec::new();
+
+    loop {
+        line.clear();
+        let read = reader
+            .read_until(b'\n', &mut line)
+            .map_err(|e| format!("Failed to read {}: {e}", path.display()))?;
+        if read == 0 {
+            break;
+        }
+        progress.lines += 1;
+        progress.bytes += read as u64;
+
+        if progress.lines <= usize::try_from(import.lines).unwrap_or_default() {
+            progress.resumed_lines += 1;
+            continue;
+        }
+
+        let text = String::from_utf8_lossy(&line);
+        match format.parse_line(text.trim_end_matches(['\r', '\n'])) {
+            Some(entry) => {
+                match sessions.record(&entry, state, conn).await? {
+                    LogHit::PageView { collector, event } => {
+                        if let Some(collector) = collector {
+                            progress.sessions += 1;
+                            collectors.push(collector);
+                        }
+                        progress.page_views += 1;
+                        events.push(event);
+                    }
+                    LogHit::Bot => progress.bot_hits += 1,
+                    LogHit::Other => progress.other_requests += 1,
+                }
+                latest = latest.max(Some(entry.time));
+            }
+            None => progress.invalid_lines += 1,
+        }
+
+        if progress.lines.is_multiple_of(CHUNK_LINES) {
+            commit(&import, path, &progress, &mut collectors, &mut events, conn).await?;
+            if let Some(latest) = latest {
+                sessions.close_inactive(latest);
+            }
+            on_progress(&progress);
+        }
+    }
+
+    commit(&import, path, &progress, &mut collectors, &mut events, conn).await?;
+    on_progress(&progress);
+
+    Ok(progress)
+}
+
+/// Stores the rows read since the previous commit.
+async fn commit(
+    import: &LogImport,
+    path: &Path,
+    progress: &ImportProgress,
+    collectors: &mut Vec<Collector>,
+    events: &mut Vec<Event>,
+    conn: &DbConn,
+) -> Result<(), String> {
+    let lines = i32::try_from(progress.lines)
+        .map_err(|_| "Logs are limited to 2147483647 lines".to_string())?;
+
+    LogImport::commit(
+        import.id.clone(),
+        path.display().to_string(),
+        lines,
+        std::mem::take(collectors),
+        std::mem::take(events),
+        conn,
+    )
+    .await
+    .map_err(|e| format!("Failed to store imported rows: {e}"))
+}
@@ -1 +1,19 @@
+pub mod access_log;
+pub mod anonymization;
+pub mod bot_detection;
+pub mod campaign;
+pub mod collector_token;
+pub mod error_fingerprint;
+pub mod geoip;
+pub mod hex;
 pub mod ip_location;
+pub mod location;
+pub mod log_import;
+pub mod privacy;
+pub mod referrer;
+pub mod session;
+pub mod static_locator;
+pub mod tracker_event;
+pub mod tracking_script;
+pub mod url_rules;
+pub mod user_agent;
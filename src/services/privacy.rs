@@ -0,0 +1,79 @@
+use std::{fmt::Display, str::FromStr};
+
+use rocket::http::HeaderMap;
+
+/// # `PrivacyPolicy`
+/// What to do with visitors asking not to be tracked.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PrivacyPolicy {
+    /// Track them like everyone else
+    Ignore,
+    /// Count their visits and events, without anything that identifies them
+    Aggregate,
+    /// Don't track them at all
+    Skip,
+}
+
+impl FromStr for PrivacyPolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "ignore" => Ok(PrivacyPolicy::Ignore),
+            "aggregate" => Ok(PrivacyPolicy::Aggregate),
+            "skip" => Ok(PrivacyPolicy::Skip),
+            other => Err(format!("Unknown privacy signals policy: {other}")),
+        }
+    }
+}
+
+/// # `PrivacySignal`
+/// Header a browser sends when its user opts out of tracking.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PrivacySignal {
+    /// `DNT: 1`
+    DoNotTrack,
+    /// `Sec-GPC: 1`
+    GlobalPrivacyControl,
+}
+
+impl PrivacySignal {
+    /// # `from_headers`
+    /// Returns the opt-out signal sent with a request, Global Privacy Control first.
+    ///
+    /// ## Arguments
+    /// * `headers` - The request headers
+    ///
+    /// ## Returns
+    /// * `Option<PrivacySignal>` - The signal, `None` when the visitor did not opt out
+    #[must_use]
+    pub fn from_headers(headers: &HeaderMap<'_>) -> Option<Self> {
+        let enabled = |name: &str| {
+            headers
+                .get_one(name)
+                .is_some_and(|value| value.trim() == "1")
+        };
+
+        if enabled("Sec-GPC") {
+            Some(PrivacySignal::GlobalPrivacyControl)
+        } else if enabled("DNT") {
+            Some(PrivacySignal::DoNotTrack)
+        } else {
+            None
+        }
+    }
+
+    #[must_use]
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            PrivacySignal::DoNotTrack => "do_not_track",
+            PrivacySignal::GlobalPrivacyControl => "global_privacy_control",
+        }
+    }
+}
+
+impl Display for PrivacySignal {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
@@ -0,0 +1,485 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fmt::Display,
+    fs,
+    net::IpAddr,
+    path::Path,
+    str::FromStr,
+};
+
+use serde::Deserialize;
+use url::Url;
+
+use crate::services::campaign::CampaignParams;
+
+/// Sources shipped with the application, used unless `REFERRER_SOURCES` points to another file
+const BUNDLED_SOURCES: &str = include_str!("../../resources/referrer_sources.json");
+
+/// `utm_medium` values of paid traffic
+const PAID_MEDIUMS: [&str; 9] = [
+    "cpc",
+    "ppc",
+    "cpm",
+    "cpv",
+    "paid",
+    "paidsearch",
+    "paid_search",
+    "paid_social",
+    "display",
+];
+/// `utm_medium` values of email traffic
+const EMAIL_MEDIUMS: [&str; 3] = ["email", "e-mail", "newsletter"];
+
+/// Longest referrer path stored
+const MAX_REFERRER_PATH_LENGTH: usize = 512;
+
+/// # `Channel`
+/// Broad kind of traffic an event comes from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Channel {
+    /// A search engine
+    Search,
+    /// A social network or community site
+    Social,
+    /// A webmail or newsletter
+    Email,
+    /// An ad, known from its click ID, its `utm_medium` or the ad network referring it
+    Paid,
+    /// Any other website
+    Referral,
+    /// No referrer: bookmarks, typed URLs, apps
+    Direct,
+    /// Another page of the same site
+    Internal,
+}
+
+impl Channel {
+    #[must_use]
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Channel::Search => "search",
+            Channel::Social => "social",
+            Channel::Email => "email",
+            Channel::Paid => "paid",
+            Channel::Referral => "referral",
+            Channel::Direct => "direct",
+            Channel::Internal => "internal",
+        }
+    }
+}
+
+impl Display for Channel {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+impl FromStr for Channel {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "search" => Ok(Channel::Search),
+            "social" => Ok(Channel::Social),
+            "email" => Ok(Channel::Email),
+            "paid" => Ok(Channel::Paid),
+            "referral" => Ok(Channel::Referral),
+            "direct" => Ok(Channel::Direct),
+            "internal" => Ok(Channel::Internal),
+            other => Err(format!("Unknown channel: {other}")),
+        }
+    }
+}
+
+/// # `ParsedReferrer`
+/// The parts of an external referrer kept with an event.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParsedReferrer {
+    /// e.g. `www.google.co.uk`
+    pub host: String,
+    /// Registrable domain of the host, e.g. `google.co.uk`
+    pub domain: String,
+    /// Path without its query string, which often holds search terms
+    pub path: String,
+}
+
+/// # `ReferrerClassification`
+/// Where an event comes from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReferrerClassification {
+    /// The referrer, unless there is none or it is internal
+    pub referrer: Option<ParsedReferrer>,
+    pub channel: Channel,
+}
+
+#[derive(Deserialize)]
+struct RawSources {
+    suffixes: Vec<String>,
+    channels: HashMap<String, Vec<String>>,
+}
+
+/// # `ReferrerClassifier`
+/// Parses referrers and sorts them into channels, driven by a JSON sources file listing
+/// the domains of each channel and the public suffixes with two labels (`co.uk`).
+///
+/// A domain matches its subdomains too, and `google.*` matches Google under any suffix.
+/// The most specific domain wins, so `mail.google.com` is email while `google.com` is search.
+pub struct ReferrerClassifier {
+    suffixes: HashSet<String>,
+    domains: HashMap<String, Channel>,
+    /// Channels of `name.*` entries, by name
+    wildcards: HashMap<String, Channel>,
+}
+
+impl ReferrerClassifier {
+    /// # `from_json`
+    /// Builds a classifier from the content of a sources file.
+    ///
+    /// ## Arguments
+    /// * `json` - The sources, see `resources/referrer_sources.json`
+    ///
+    /// ## Errors
+    /// If the JSON is malformed or a channel cannot be given to domains.
+    ///
+    /// ## Returns
+    /// * `Result<ReferrerClassifier, String>` - The classifier
+    pub fn from_json(json: &str) -> Result<Self, String> {
+        let raw: RawSources = serde_json::from_str(json)
+            .map_err(|e| format!("Invalid referrer sources file: {e}"))?;
+
+        let mut domains = HashMap::new();
+        let mut wildcards = HashMap::new();
+        for (channel, entries) in raw.channels {
+            let channel = match channel.parse::<Channel>()? {
+                Channel::Direct | Channel::Internal | Channel::Referral => {
+                    return Err(format!("Domains cannot be listed as {channel} traffic"));
+                }
+                channel => channel,
+            };
+            for entry in entries {
+                let entry = entry.trim().to_lowercase();
+                match entry.strip_suffix(".*") {
+                    Some(name) => wildcards.insert(name.to_string(), channel),
+                    None => domains.insert(entry, channel),
+                };
+            }
+        }
+
+        Ok(Self {
+            suffixes: raw
+                .suffixes
+                .into_iter()
+                .map(|suffix| suffix.to_lowercase())
+                .collect(),
+            domains,
+            wildcards,
+        })
+    }
+
+    /// # `from_file`
+    /// Builds a classifier from a sources file on disk.
+    ///
+    /// ## Arguments
+    /// * `path` - Path to the sources file
+    ///
+    /// ## Errors
+    /// If the file cannot be read or is not a valid sources file.
+    ///
+    /// ## Returns
+    /// * `Result<ReferrerClassifier, String>` - The classifier
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self, String> {
+        let content = fs::read_to_string(path.as_ref())
+            .map_err(|e| format!("Failed to read {}: {e}", path.as_ref().display()))?;
+
+        Self::from_json(&content)
+    }
+
+    /// # `bundled`
+    /// Builds a classifier from the sources shipped with the application.
+    ///
+    /// ## Panics
+    /// If the bundled sources are invalid, which is a bug.
+    ///
+    /// ## Returns
+    /// * `ReferrerClassifier` - The classifier
+    #[must_use]
+    pub fn bundled() -> Self {
+        Self::from_json(BUNDLED_SOURCES).expect("Bundled referrer sources are invalid")
+    }
+
+    /// # `registrable_domain`
+    /// Returns the part of a host its owner registered, e.g. `bbc.co.uk` for `www.bbc.co.uk`.
+    ///
+    /// ## Arguments
+    /// * `host` - A lowercase host name
+    ///
+    /// ## Returns
+    /// * `String` - The registrable domain, or the host itself for IP addresses
+    #[must_use]
+    pub fn registrable_domain(&self, host: &str) -> String {
+        let host = host.trim_end_matches('.');
+        if host.trim_matches(['[', ']']).parse::<IpAddr>().is_ok() {
+            return host.to_string();
+        }
+
+        let labels: Vec<&str> = host.split('.').collect();
+        let keep = match labels.len() {
+            0..=2 => labels.len(),
+            count if self.suffixes.contains(&labels[count - 2..].join(".")) => 3,
+            _ => 2,
+        };
+
+        labels[labels.len() - keep..].join(".")
+    }
+
+    /// Channel of a host, from its most specific listed domain.
+    fn lookup(&self, host: &str, domain: &str) -> Option<Channel> {
+        let mut candidate = host;
+        loop {
+            if let Some(channel) = self.domains.get(candidate) {
+                return Some(*channel);
+            }
+            if candidate == domain {
+                break;
+            }
+            match candidate.split_once('.') {
+                Some((_, parent)) => candidate = parent,
+                None => break,
+            }
+        }
+
+        domain
+            .split('.')
+            .next()
+            .and_then(|name| self.wildcards.get(name))
+            .copied()
+    }
+
+    /// # `classify`
+    /// Parses the referrer of an event and finds its channel. Campaign parameters come
+    /// first, as an ad or a newsletter often opens pages without any referrer.
+    ///
+    /// ## Arguments
+    /// * `referrer` - The referrer reported with the event, if any
+    /// * `page_url` - The URL of the page the event happened on
+    /// * `site_domain` - The domain of the site the event belongs to, if known
+    /// * `campaign` - The campaign parameters of the page URL
+    ///
+    /// ## Returns
+    /// * `ReferrerClassification` - The external referrer, if any, and the channel
+    #[must_use]
+    pub fn classify(
+        &self,
+        referrer: Option<&str>,
+        page_url: &str,
+        site_domain: Option<&str>,
+        campaign: &CampaignParams,
+    ) -> ReferrerClassification {
+        let parsed = referrer
+            .and_then(|referrer| Url::parse(referrer.trim()).ok())
+            .filter(|url| matches!(url.scheme(), "http" | "https"))
+            .and_then(|url| {
+                let host = url.host_str()?.to_lowercase();
+                let path: String = url.path().chars().take(MAX_REFERRER_PATH_LENGTH).collect();
+                Some((host, path))
+            });
+
+        let medium = campaign.utm_medium.as_deref().unwrap_or_default();
+        let campaign_channel = if campaign.click_id.is_some() || PAID_MEDIUMS.contains(&medium) {
+            Some(Channel::Paid)
+        } else if EMAIL_MEDIUMS.contains(&medium) {
+            Some(Channel::Email)
+        } else {
+            None
+        };
+
+        let Some((host, path)) = parsed else {
+            return ReferrerClassification {
+                referrer: None,
+                channel: campaign_channel.unwrap_or(Channel::Direct),
+            };
+        };
+
+        if is_internal(&host, page_url, site_domain) {
+            return ReferrerClassification {
+                referrer: None,
+                channel: campaign_channel.unwrap_or(Channel::Internal),
+            };
+        }
+
+        let domain = self.registrable_domain(&host);
+        let channel = campaign_channel
+            .or_else(|| self.lookup(&host, &domain))
+            .unwrap_or(Channel::Referral);
+
+        ReferrerClassification {
+            referrer: Some(ParsedReferrer { host, domain, path }),
+            channel,
+        }
+    }
+}
+
+impl Default for ReferrerClassifier {
+    fn default() -> Self {
+        Self::bundled()
+    }
+}
+
+/// Whether a referrer host is the page itself or its site, `www.` aside.
+fn is_internal(host: &str, page_url: &str, site_domain: Option<&str>) -> bool {
+    let host = host.strip_prefix("www.").unwrap_or(host);
+    let page_host = Url::parse(page_url)
+        .ok()
+        .and_then(|url| url.host_str().map(str::to_lowercase));
+
+    page_host
+        .as_deref()
+        .is_some_and(|page_host| page_host.strip_prefix("www.").unwrap_or(page_host) == host)
+        || site_domain.is_some_and(|site_domain| {
+            let site_domain = site_domain.strip_prefix("www.").unwrap_or(site_domain);
+            host == site_domain || host.ends_with(&format!(".{site_domain}"))
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PAGE: &str = "https://example.com/pricing";
+
+    #[test]
+    fn finds_registrable_domains() {
+        let classifier = ReferrerClassifier::bundled();
+        let cases = [
+            ("example.com", "example.com"),
+            ("www.example.com", "example.com"),
+            ("a.b.example.com", "example.com"),
+            ("www.bbc.co.uk", "bbc.co.uk"),
+            ("bbc.co.uk", "bbc.co.uk"),
+            ("news.bbc.co.uk", "bbc.co.uk"),
+            ("www.google.com.au", "google.com.au"),
+            ("example.com.", "example.com"),
+            ("localhost", "localhost"),
+            ("192.168.1.10", "192.168.1.10"),
+            ("[2001:db8::1]", "[2001:db8::1]"),
+        ];
+
+        for (host, domain) in cases {
+            assert_eq!(classifier.registrable_domain(host), domain, "{host}");
+        }
+    }
+
+    #[test]
+    fn classifies_referrers() {
+        let classifier = ReferrerClassifier::bundled();
+        let none = CampaignParams::default();
+        let cases = [
+            (None, Channel::Direct),
+            (Some(""), Channel::Direct),
+            (Some("android-app://com.slack"), Channel::Direct),
+            (Some("https://www.google.com/"), Channel::Search),
+            (
+                Some("https://www.google.co.uk/search?q=stats"),
+                Channel::Search,
+            ),
+            (Some("https://duckduckgo.com/"), Channel::Search),
+            (Some("https://mail.google.com/mail/u/0/"), Channel::Email),
+            (Some("https://t.co/abc"), Channel::Social),
+            (Some("https://old.reddit.com/r/rust/"), Channel::Social),
+            (
+                Some("https://news.ycombinator.com/item?id=1"),
+                Channel::Social,
+            ),
+            (
+                Some("https://www.googleadservices.com/pagead/aclk"),
+                Channel::Paid,
+            ),
+            (Some("https://blog.rust-lang.org/2024/"), Channel::Referral),
+            // `google.*` only matches Google itself
+            (Some("https://googleblog.example.org/"), Channel::Referral),
+            (Some("https://example.com/"), Channel::Internal),
+            (Some("https://www.example.com/blog"), Channel::Internal),
+            (Some("https://docs.example.com/"), Channel::Internal),
+        ];
+
+        for (referrer, channel) in cases {
+            let classification = classifier.classify(referrer, PAGE, Some("example.com"), &none);
+            assert_eq!(classification.channel, channel, "{referrer:?}");
+        }
+    }
+
+    #[test]
+    fn keeps_external_referrers_without_their_query() {
+        let classifier = ReferrerClassifier::bundled();
+        let none = CampaignParams::default();
+
+        let classification = classifier.classify(
+            Some("https://WWW.Google.co.uk/search?q=private"),
+            PAGE,
+            None,
+            &none,
+        );
+        assert_eq!(
+            classification.referrer,
+            Some(ParsedReferrer {
+                host: "www.google.co.uk".to_string(),
+                domain: "google.co.uk".to_string(),
+                path: "/search".to_string(),
+            })
+        );
+
+        let classification = classifier.classify(Some("https://example.com/"), PAGE, None, &none);
+        assert_eq!(classification.referrer, None);
+        assert_eq!(classification.channel, Channel::Internal);
+    }
+
+    #[test]
+    fn campaigns_come_first() {
+        let classifier = ReferrerClassifier::bundled();
+        let campaign = |url: &str| CampaignParams::from_url(url);
+        let cases = [
+            ("https://example.com/?gclid=abc", None, Channel::Paid),
+            (
+                "https://example.com/?utm_medium=CPC",
+                Some("https://www.google.com/"),
+                Channel::Paid,
+            ),
+            (
+                "https://example.com/?utm_medium=newsletter",
+                None,
+                Channel::Email,
+            ),
+            (
+                "https://example.com/?utm_medium=email",
+                Some("https://example.com/"),
+                Channel::Email,
+            ),
+            (
+                "https://example.com/?utm_medium=social",
+                Some("https://www.google.com/"),
+                Channel::Search,
+            ),
+        ];
+
+        for (page, referrer, channel) in cases {
+            let classification =
+                classifier.classify(referrer, page, Some("example.com"), &campaign(page));
+            assert_eq!(classification.channel, channel, "{page} {referrer:?}");
+        }
+    }
+
+    #[test]
+    fn rejects_invalid_sources() {
+        assert!(
+            ReferrerClassifier::from_json(r#"{"suffixes": [], "channels": {"search": ["a.com"]}}"#)
+                .is_ok()
+        );
+        for json in [
+            r#"{"suffixes": [], "channels": {"direct": ["a.com"]}}"#,
+            r#"{"suffixes": [], "channels": {"video": ["a.com"]}}"#,
+            r#"{"channels": {}}"#,
+        ] {
+            assert!(ReferrerClassifier::from_json(json).is_err(), "{json}");
+        }
+    }
+}
@@ -0,0 +1,283 @@
+use std::{fmt::Display, net::IpAddr};
+
+use crate::{
+    AppState, DbConn, UserAgentInfo,
+    logger::Logger,
+    metrics::Metrics,
+    models::{City, Collector, CollectorQuery, CollectorSource, GeoCacheEntry, Site, VisitorSalt},
+    services::{
+        bot_detection::BotReason,
+        privacy::{PrivacyPolicy, PrivacySignal},
+        user_agent::DeviceType,
+    },
+};
+
+/// # `SessionRequest`
+/// Everything known about a visitor asking to start or resume a session.
+pub struct SessionRequest {
+    /// The site the script is embedded in, if any
+    pub site_id: Option<String>,
+    /// The collector and token of a previous session in the same tab, if any
+    pub resume: Option<(String, String)>,
+    /// The IP address of the visitor
+    pub ip: IpAddr,
+    /// The origin of the page embedding the script, if known
+    pub page_origin: Option<String>,
+    /// The user agent of the visitor
+    pub user_agent_info: UserAgentInfo,
+}
+
+/// # `Session`
+/// Outcome of a session handshake.
+#[derive(Debug)]
+pub enum Session {
+    /// The visitor is tracked under this collector
+    Tracked {
+        collector_id: String,
+        token: String,
+        site_id: Option<String>,
+        resumed: bool,
+    },
+    /// The visitor opted out and the policy is to not track them at all
+    OptedOut(PrivacySignal),
+}
+
+/// # `SessionError`
+/// Why a session could not be started.
+#[derive(Debug)]
+pub enum SessionError {
+    UnknownSite(String),
+    Database(diesel::result::Error),
+}
+
+impl Display for SessionError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SessionError::UnknownSite(site_id) => write!(f, "Unknown site {site_id}"),
+            SessionError::Database(e) => write!(f, "Failed to start session: {e}"),
+        }
+    }
+}
+
+impl From<diesel::result::Error> for SessionError {
+    fn from(error: diesel::result::Error) -> Self {
+        SessionError::Database(error)
+    }
+}
+
+/// # `start_session`
+/// Resumes the collector of a previous session when its token is still valid, or creates
+/// a new one: geolocates the visitor, detects bots, computes the visitor ID and applies
+/// the anonymization and privacy signal policies.
+///
+/// ## Arguments
+/// * `request` - The visitor
+/// * `state` - The application state
+/// * `conn` - The database connection
+///
+/// ## Errors
+/// If the site is unknown or the database fails.
+///
+/// ## Returns
+/// * `Result<Session, SessionError>` - The session
+pub async fn start_session(
+    request: SessionRequest,
+    state: &AppState,
+    conn: &DbConn,
+) -> Result<Session, SessionError> {
+    let SessionRequest {
+        site_id,
+        resume,
+        ip,
+        page_origin,
+        user_agent_info,
+    } = request;
+
+    let site_id = match site_id {
+        Some(site_id) => match Site::find(site_id.clone(), conn).await? {
+            Some(site) => Some(site.id),
+            None => return Err(SessionError::UnknownSite(site_id)),
+        },
+        None => None,
+    };
+
+    // Visitors who opted out are either not tracked, or only counted
+    let aggregate_only = match user_agent_info.privacy_signal {
+        Some(signal) if state.privacy_policy != PrivacyPolicy::Ignore => {
+            if let Some(counter) = state.metrics.privacy(signal, state.privacy_policy) {
+                Metrics::increment(counter);
+            }
+            if state.privacy_policy == PrivacyPolicy::Skip {
+                return Ok(Session::OptedOut(signal));
+            }
+            true
+        }
+        _ => false,
+    };
+
+    // Navigating to another page of the same site keeps the same collector
+    if let Some((collector_id, token)) = resume
+        && let Ok(claims) = state
+            .token_signer
+            .verify(Some(&token), page_origin.as_deref())
+        && claims.collector_id == collector_id
+        && claims.site_id == site_id
+        && Collector::find(collector_id.clone(), conn).await?.is_some()
+    {
+        let token =
+            state
+                .token_signer
+                .issue(&collector_id, site_id.as_deref(), page_origin.as_deref());
+
+        return Ok(Session::Tracked {
+            collector_id,
+            token,
+            site_id,
+            resumed: true,
+        });
+    }
+
+    let collector = build_collector(
+        site_id.clone(),
+        Some(ip),
+        user_agent_info,
+        aggregate_only,
+        CollectorSource::Tracker,
+        state,
+        conn,
+    )
+    .await?;
+    let collector_id = Collector::insert(collector, conn).await?;
+    let token = state
+        .token_signer
+        .issue(&collector_id, site_id.as_deref(), page_origin.as_deref());
+
+    Ok(Session::Tracked {
+        collector_id,
+        token,
+        site_id,
+        resumed: false,
+    })
+}
+
+/// # `build_collector`
+/// Describes a new visitor: geolocates them, detects bots, computes their visitor ID and
+/// applies the anonymization policy. The collector is not stored.
+///
+/// ## Arguments
+/// * `site_id` - The site being visited, if any
+/// * `ip` - The IP address of the visitor, if known
+/// * `user_agent_info` - The user agent of the visitor
+/// * `aggregate_only` - Whether the visitor opted out and must only be counted
+/// * `source` - What is starting the session
+/// * `state` - The application state
+/// * `conn` - The database connection
+///
+/// ## Errors
+/// If the database fails.
+///
+/// ## Returns
+/// * `Result<Collector, SessionError>` - The collector to insert
+pub async fn build_collector(
+    site_id: Option<String>,
+    ip: Option<IpAddr>,
+    user_agent_info: UserAgentInfo,
+    aggregate_only: bool,
+    source: CollectorSource,
+    state: &AppState,
+    conn: &DbConn,
+) -> Result<Collector, SessionError> {
+    // Without an IP address, the visitor can't be located nor told apart
+    let Some(ip) = ip else {
+        let bot_reason = (user_agent_info.device_type == DeviceType::Bot)
+            .then(|| BotReason::UserAgent.to_string());
+
+        let collector_query = CollectorQuery {
+            origin: String::new(),
+            city_id: City::unknown(conn).await?,
+            os: Some(user_agent_info.os),
+            browser: Some(user_agent_info.browser),
+            site_id,
+            os_version: user_agent_info.os_version.filter(|_| !aggregate_only),
+            browser_version: user_agent_info.browser_version.filter(|_| !aggregate_only),
+            device_type: Some(user_agent_info.device_type.to_string()),
+            engine: Some(user_agent_info.engine),
+            bot_reason,
+            visitor_id: None,
+        };
+
+        return Ok(Collector {
+            source: source.to_string(),
+            ..collector_query.into()
+        });
+    };
+
+    let city_to_create: City = GeoCacheEntry::locate(
+        ip,
+        state.geo_locator.as_ref(),
+        state.geo_cache_ttl,
+        &state.metrics,
+        conn,
+    )
+    .await
+    .map(Into::into)
+    .unwrap_or_default();
+
+    let existing_city = City::find_by_name_and_country(
+        city_to_create.name.clone(),
+        city_to_create.country.clone(),
+        conn,
+    )
+    .await?;
+
+    let city_id = match existing_city {
+        Some(found) => found.id.unwrap_or_default(),
+        None => City::insert(city_to_create, conn).await?,
+    };
+
+    let bot_reason = state
+        .bot_detector
+        .detect(ip, user_agent_info.device_type, user_agent_info.headless)
+        .map(|reason| reason.to_string());
+
+    // Without a salt, visitors can't be told apart but are still counted as sessions
+    let visitor_id = if aggregate_only {
+        None
+    } else {
+        match VisitorSalt::today(conn).await {
+            Ok(salt) => Some(VisitorSalt::visitor_id(
+                &salt,
+                site_id.as_deref(),
+                ip,
+                &user_agent_info.raw_user_agent,
+            )),
+            Err(e) => {
+                Logger::error("Collector", &format!("Failed to get visitor salt: {e}"));
+                None
+            }
+        }
+    };
+
+    let collector_query = CollectorQuery {
+        origin: if aggregate_only {
+            String::new()
+        } else {
+            state.ip_anonymizer.anonymize(ip)
+        },
+        city_id,
+        os: Some(user_agent_info.os),
+        browser: Some(user_agent_info.browser),
+        site_id: site_id.clone(),
+        os_version: user_agent_info.os_version.filter(|_| !aggregate_only),
+        browser_version: user_agent_info.browser_version.filter(|_| !aggregate_only),
+        device_type: Some(user_agent_info.device_type.to_string()),
+        engine: Some(user_agent_info.engine),
+        bot_reason,
+        visitor_id,
+    };
+
+    Ok(Collector {
+        source: source.to_string(),
+        ..collector_query.into()
+    })
+}
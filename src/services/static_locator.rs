@@ -0,0 +1,227 @@
+use std::{fs, net::IpAddr, path::Path};
+
+use ipnet::IpNet;
+use serde::Deserialize;
+
+use crate::services::location::{GeoLocator, Location, LocationError};
+
+/// # `StaticEntry`
+/// One row of a static geolocation table, as written in its JSON file.
+#[derive(Deserialize, Debug)]
+pub struct StaticEntry {
+    pub network: String,
+    pub city: String,
+    pub country: String,
+    pub latitude: Option<f32>,
+    pub longitude: Option<f32>,
+    pub timezone: Option<String>,
+}
+
+/// # `StaticLocator`
+/// `GeoLocator` resolving IP addresses from a fixed CIDR-to-city table.
+///
+/// Meant for development and tests: no network, no database file, fully predictable.
+/// When several networks match, the most specific one wins.
+pub struct StaticLocator {
+    entries: Vec<(IpNet, Location)>,
+}
+
+impl StaticLocator {
+    /// # `new`
+    /// Builds a locator from table entries.
+    ///
+    /// ## Arguments
+    /// * `entries` - The CIDR-to-city table
+    ///
+    /// ## Errors
+    /// If one of the networks is not valid CIDR notation.
+    ///
+    /// ## Returns
+    /// * `Result<StaticLocator, String>` - The locator
+    pub fn new(entries: Vec<StaticEntry>) -> Result<Self, String> {
+        let entries = entries
+            .into_iter()
+            .map(|entry| {
+                let network = entry
+                    .network
+                    .parse::<IpNet>()
+                    .map_err(|e| format!("Invalid network {}: {e}", entry.network))?;
+
+                Ok((
+                    network,
+                    Location {
+                        city: entry.city,
+                        country: entry.country,
+                        latitude: entry.latitude,
+                        longitude: entry.longitude,
+                        timezone: entry.timezone,
+                    },
+                ))
+            })
+            .collect::<Result<Vec<_>, String>>()?;
+
+        Ok(Self { entries })
+    }
+
+    /// # `from_file`
+    /// Loads the table from a JSON file containing an array of `StaticEntry`.
+    ///
+    /// ## Arguments
+    /// * `path` - Path to the JSON file
+    ///
+    /// ## Errors
+    /// If the file cannot be read or parsed.
+    ///
+    /// ## Returns
+    /// * `Result<StaticLocator, String>` - The locator
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self, String> {
+        let content = fs::read_to_string(path.as_ref())
+            .map_err(|e| format!("Failed to read {}: {e}", path.as_ref().display()))?;
+        let entries: Vec<StaticEntry> = serde_json::from_str(&content)
+            .map_err(|e| format!("Failed to parse {}: {e}", path.as_ref().display()))?;
+
+        Self::new(entries)
+    }
+
+    /// # `development`
+    /// Default table used in development: every visitor comes from Paris.
+    ///
+    /// ## Returns
+    /// * `StaticLocator` - The locator
+    #[must_use]
+    pub fn development() -> Self {
+        let paris = |network: &str| StaticEntry {
+            network: network.to_string(),
+            city: "Paris".to_string(),
+            country: "FR".to_string(),
+            latitude: Some(48.8566),
+            longitude: Some(2.3522),
+            timezone: Some("Europe/Paris".to_string()),
+        };
+
+        Self::new(vec![paris("0.0.0.0/0"), paris("::/0")]).unwrap_or(Self {
+            entries: Vec::new(),
+        })
+    }
+
+    /// # `lookup`
+    /// Finds the most specific network containing the IP address.
+    ///
+    /// ## Arguments
+    /// * `ip` - The IP address to look up
+    ///
+    /// ## Returns
+    /// * `Option<&Location>` - The location of the IP address, if any
+    #[must_use]
+    pub fn lookup(&self, ip: IpAddr) -> Option<&Location> {
+        self.entries
+            .iter()
+            .filter(|(network, _)| network.contains(&ip))
+            .max_by_key(|(network, _)| network.prefix_len())
+            .map(|(_, location)| location)
+    }
+}
+
+#[rocket::async_trait]
+impl GeoLocator for StaticLocator {
+    fn name(&self) -> &'static str {
+        "static"
+    }
+
+    async fn locate(&self, ip: IpAddr) -> Result<Location, LocationError> {
+        self.lookup(ip).cloned().ok_or(LocationError::NotFound)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+
+    fn entry(network: &str, city: &str) -> StaticEntry {
+        StaticEntry {
+            network: network.to_string(),
+            city: city.to_string(),
+            country: "FR".to_string(),
+            latitude: None,
+            longitude: None,
+            timezone: None,
+        }
+    }
+
+    fn city(locator: &StaticLocator, ip: &str) -> Option<String> {
+        locator
+            .lookup(ip.parse().expect("valid IP"))
+            .map(|location| location.city.clone())
+    }
+
+    #[test]
+    fn matches_networks() {
+        let locator = StaticLocator::new(vec![
+            entry("192.0.2.0/24", "Lyon"),
+            entry("2001:db8::/32", "Nantes"),
+        ])
+        .expect("valid table");
+        let cases = [
+            ("192.0.2.0", Some("Lyon")),
+            ("192.0.2.255", Some("Lyon")),
+            ("192.0.3.1", None),
+            ("2001:db8::1", Some("Nantes")),
+            ("2001:db9::1", None),
+        ];
+
+        for (ip, expected) in cases {
+            assert_eq!(city(&locator, ip).as_deref(), expected, "{ip}");
+        }
+    }
+
+    #[test]
+    fn most_specific_network_wins() {
+        let locator = StaticLocator::new(vec![
+            entry("10.0.0.0/8", "Paris"),
+            entry("10.1.2.0/24", "Lille"),
+            entry("10.1.0.0/16", "Rennes"),
+        ])
+        .expect("valid table");
+        let cases = [
+            ("10.9.9.9", "Paris"),
+            ("10.1.9.9", "Rennes"),
+            ("10.1.2.3", "Lille"),
+        ];
+
+        for (ip, expected) in cases {
+            assert_eq!(city(&locator, ip).as_deref(), Some(expected), "{ip}");
+        }
+    }
+
+    #[test]
+    fn rejects_malformed_tables() {
+        assert!(StaticLocator::new(vec![entry("10.0.0.0/33", "Paris")]).is_err());
+        assert!(StaticLocator::new(vec![entry("not a network", "Paris")]).is_err());
+
+        let path = env::temp_dir().join(format!("static_locator_{}.json", ulid::Ulid::new()));
+        let cases = [
+            r#"[{"network": "10.0.0.0/8", "city": "Paris"}]"#,
+            r#"{"network": "10.0.0.0/8", "city": "Paris", "country": "FR"}"#,
+            r#"[{"network": "10.0.0/8", "city": "Paris", "country": "FR"}]"#,
+            "not json",
+        ];
+
+        for content in cases {
+            fs::write(&path, content).expect("writable temp dir");
+            assert!(StaticLocator::from_file(&path).is_err(), "{content}");
+        }
+
+        fs::write(
+            &path,
+            r#"[{"network": "10.0.0.0/8", "city": "Paris", "country": "FR"}]"#,
+        )
+        .expect("writable temp dir");
+        let locator = StaticLocator::from_file(&path);
+        fs::remove_file(&path).ok();
+        assert_eq!(
+            city(&locator.expect("valid file"), "10.0.0.1").as_deref(),
+            Some("Paris")
+        );
+    }
+}
@@ -0,0 +1,222 @@
+use std::{fmt::Display, str::FromStr};
+
+use serde_json::Value;
+use url::Url;
+
+use crate::models::EventQuery;
+
+/// Longest event name
+const MAX_EVENT_NAME_LENGTH: usize = 64;
+/// Longest active time a single `engagement` event may report, one hour
+const MAX_ENGAGEMENT_MS: i32 = 3_600_000;
+
+/// # `TrackerEvent`
+/// Events emitted by the modules of the tracking script.
+///
+/// Any other valid name is a custom event sent with `stats_collect`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TrackerEvent {
+    /// First page view of a page load
+    Enter,
+    /// Following page views, through the History API or the hash
+    Visit,
+    /// The page is being hidden or closed
+    Exit,
+    /// Active time spent on the page since the previous one, in `engagement_ms`
+    Engagement,
+    /// Click on a link to another host, `url` is the link
+    Outbound,
+    /// Click on a link to a file, `url` is the file and `props.extension` its extension
+    Download,
+    /// Click on a `mailto:` link, the address is not sent
+    Mailto,
+    /// Click on a `tel:` link, the number is not sent
+    Tel,
+}
+
+impl TrackerEvent {
+    /// Every event emitted by the tracking script
+    pub const ALL: [TrackerEvent; 8] = [
+        TrackerEvent::Enter,
+        TrackerEvent::Visit,
+        TrackerEvent::Exit,
+        TrackerEvent::Engagement,
+        TrackerEvent::Outbound,
+        TrackerEvent::Download,
+        TrackerEvent::Mailto,
+        TrackerEvent::Tel,
+    ];
+
+    #[must_use]
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TrackerEvent::Enter => "enter",
+            TrackerEvent::Visit => "visit",
+            TrackerEvent::Exit => "exit",
+            TrackerEvent::Engagement => "engagement",
+            TrackerEvent::Outbound => "outbound",
+            TrackerEvent::Download => "download",
+            TrackerEvent::Mailto => "mailto",
+            TrackerEvent::Tel => "tel",
+        }
+    }
+}
+
+impl Display for TrackerEvent {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+impl FromStr for TrackerEvent {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "enter" => Ok(TrackerEvent::Enter),
+            "visit" => Ok(TrackerEvent::Visit),
+            "exit" => Ok(TrackerEvent::Exit),
+            "engagement" => Ok(TrackerEvent::Engagement),
+            "outbound" => Ok(TrackerEvent::Outbound),
+            "download" => Ok(TrackerEvent::Download),
+            "mailto" => Ok(TrackerEvent::Mailto),
+            "tel" => Ok(TrackerEvent::Tel),
+            other => Err(format!("Unknown tracker event: {other}")),
+        }
+    }
+}
+
+/// # `validate_event`
+/// Checks the name of an event and, for tracker events, the URL, properties and
+/// engagement time they carry.
+///
+/// ## Arguments
+/// * `event` - The incoming event
+///
+/// ## Errors
+/// * `String` - Why the event is rejected
+pub fn validate_event(event: &EventQuery) -> Result<(), String> {
+    let name = event.name.as_str();
+    if name.is_empty() || name.len() > MAX_EVENT_NAME_LENGTH {
+        return Err(format!(
+            "Event names must be 1 to {MAX_EVENT_NAME_LENGTH} characters long"
+        ));
+    }
+    if !name
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '.' | ':'))
+    {
+        return Err(format!(
+            "Invalid event name {name}: only letters, digits, _, -, . and : are allowed"
+        ));
+    }
+
+    let tracker_event = name.parse::<TrackerEvent>().ok();
+    match (tracker_event, event.engagement_ms) {
+        (Some(TrackerEvent::Engagement), Some(1..=MAX_ENGAGEMENT_MS)) => {}
+        (Some(TrackerEvent::Engagement), _) => {
+            return Err(format!(
+                "engagement events must have an engagement_ms of 1 to {MAX_ENGAGEMENT_MS}"
+            ));
+        }
+        (_, Some(_)) => return Err("Only engagement events can have an engagement_ms".to_string()),
+        (_, None) => {}
+    }
+
+    let Some(tracker_event) = tracker_event else {
+        return Ok(());
+    };
+
+    // Contact links are reported with the page they were clicked on, never the address or number
+    let web_url = Url::parse(&event.url)
+        .ok()
+        .filter(|url| matches!(url.scheme(), "http" | "https"));
+    if web_url.is_none() {
+        return Err(format!("{tracker_event} events must have an http(s) URL"));
+    }
+
+    let extension = event
+        .props
+        .as_ref()
+        .and_then(|props| props.get("extension"));
+    match (tracker_event, extension) {
+        (TrackerEvent::Download, Some(Value::String(extension))) if !extension.is_empty() => Ok(()),
+        (TrackerEvent::Download, _) => {
+            Err("download events must have an extension property".to_string())
+        }
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn event(event: Value) -> EventQuery {
+        let mut fields =
+            json!({"url": "https://example.com/", "referrer": null, "collector_id": "col_1"});
+        fields
+            .as_object_mut()
+            .unwrap()
+            .extend(event.as_object().unwrap().clone());
+        serde_json::from_value(fields).unwrap()
+    }
+
+    #[test]
+    fn names_round_trip() {
+        for tracker_event in TrackerEvent::ALL {
+            assert_eq!(tracker_event.as_str().parse(), Ok(tracker_event));
+        }
+        assert!("signup".parse::<TrackerEvent>().is_err());
+    }
+
+    #[test]
+    fn accepts_valid_events() {
+        let events = [
+            json!({"name": "enter"}),
+            json!({"name": "visit", "url": "http://localhost:8000/blog#intro"}),
+            json!({"name": "engagement", "engagement_ms": 1}),
+            json!({"name": "engagement", "engagement_ms": 3_600_000}),
+            json!({"name": "download", "url": "https://example.com/a.pdf", "props": {"extension": "pdf"}}),
+            json!({"name": "signup"}),
+            json!({"name": "checkout:step-2.done_ok"}),
+            // Custom events may carry any URL
+            json!({"name": "signup", "url": "app://settings"}),
+            json!({"name": "a".repeat(64)}),
+        ];
+
+        for data in events {
+            assert_eq!(validate_event(&event(data.clone())), Ok(()), "{data}");
+        }
+    }
+
+    #[test]
+    fn rejects_invalid_events() {
+        let events = [
+            json!({"name": ""}),
+            json!({"name": "a".repeat(65)}),
+            json!({"name": "sign up"}),
+            json!({"name": "signup!"}),
+            json!({"name": "<script>"}),
+            json!({"name": "inscription-réussie"}),
+            json!({"name": "engagement"}),
+            json!({"name": "engagement", "engagement_ms": 0}),
+            json!({"name": "engagement", "engagement_ms": -5}),
+            json!({"name": "engagement", "engagement_ms": 3_600_001}),
+            json!({"name": "enter", "engagement_ms": 100}),
+            json!({"name": "signup", "engagement_ms": 100}),
+            json!({"name": "mailto", "url": "mailto:someone@example.com"}),
+            json!({"name": "tel", "url": "tel:+15555550100"}),
+            json!({"name": "enter", "url": "not a url"}),
+            json!({"name": "download", "url": "https://example.com/a.pdf"}),
+            json!({"name": "download", "url": "https://example.com/a.pdf", "props": {"extension": ""}}),
+            json!({"name": "download", "url": "https://example.com/a.pdf", "props": {"extension": 3}}),
+        ];
+
+        for data in events {
+            assert!(validate_event(&event(data.clone())).is_err(), "{data}");
+        }
+    }
+}
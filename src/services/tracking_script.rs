@@ -0,0 +1,650 @@
+use sha2::{Digest, Sha256};
+
+use crate::services::hex;
+
+/// # `TrackingScript`
+/// The tracking script served at `/stats.js`.
+///
+/// It is the same for every visitor and every site, so browsers and CDNs can cache it:
+/// the site comes from the script URL (`?site=`) or its `data-site` attribute, and the
+/// collector from a `POST /session` handshake made once the script runs.
+pub struct TrackingScript {
+    pub body: String,
+    /// Hash of the body, used both as `ETag` and as `?v=` cache-busting version
+    pub version: String,
+}
+
+impl TrackingScript {
+    /// # `new`
+    /// Renders the tracking script of this deployment.
+    ///
+    /// ## Arguments
+    /// * `app_url` - Application URL the script reports to
+    ///
+    /// ## Returns
+    /// * `TrackingScript` - The script and its version
+    #[must_use]
+    pub fn new(app_url: &str) -> Self {
+        let body = generate_analytics_js(app_url);
+        let version = hex::encode(&Sha256::digest(body.as_bytes())[..8]);
+
+        Self { body, version }
+    }
+
+    /// # `etag`
+    /// Returns the `ETag` header value of the script.
+    #[must_use]
+    pub fn etag(&self) -> String {
+        format!("\"{}\"", self.version)
+    }
+}
+
+/// File extensions tracked by the downloads module when no list is given
+const DEFAULT_DOWNLOAD_EXTENSIONS: &str =
+    "7z,apk,csv,dmg,doc,docx,epub,exe,gz,iso,mp3,mp4,msi,pdf,pkg,ppt,pptx,rar,tar,txt,xls,xlsx,zip";
+
+/// # `generate_analytics_js`
+/// Generates the JavaScript code for analytics tracking.
+///
+/// Modules are toggled with `data-*` attributes on the script tag, or the same names as
+/// query parameters of the script URL: `hash`, `outbound`, `downloads`, `contact`,
+/// `manual` and `debug`.
+///
+/// ## Arguments
+/// * `app_url` - Application URL
+///
+/// ## Returns
+/// * `String` - JavaScript code for analytics tracking
+fn generate_analytics_js(app_url: &str) -> String {
+    format!(
+        r#""use strict";
+(() => {{
+    {{
+        const appUrl = "{app_url}";
+        const script = document.currentScript;
+        const params = script
+            ? new URL(script.src).searchParams
+            : new URLSearchParams();
+
+        // `data-name` wins over `?name=`, a bare attribute turns a module on
+        function option(name) {{
+            if (script && script.dataset[name] !== undefined) {{
+                return script.dataset[name];
+            }}
+            return params.get(name);
+        }}
+
+        function enabled(name, fallback) {{
+            const value = option(name);
+            if (value === null) {{
+                return fallback;
+            }}
+            return value !== "false" && value !== "0";
+        }}
+
+        const siteId = option("site") || null;
+        const options = {{
+            // Count changes of `location.hash` as page views
+            hash: enabled("hash", false),
+            // Track clicks on links to other hosts
+            outbound: enabled("outbound", true),
+            // Track clicks on links to files, `data-downloads="pdf,zip"` picks the extensions
+            downloads: enabled("downloads", false),
+            // Track clicks on `mailto:` and `tel:` links
+            contact: enabled("contact", false),
+            // Only record page views through `stats_pageview`
+            manual: enabled("manual", false),
+            // Report Web Vitals to `/vitals`
+            vitals: enabled("vitals", true),
+            // Report uncaught errors and unhandled rejections to `/errors`
+            errors: enabled("errors", false),
+            // Log every event to the console
+            debug: enabled("debug", false),
+        }};
+        const downloadOption = option("downloads");
+        const downloadExtensions = (
+            downloadOption && !["true", "1"].includes(downloadOption)
+                ? downloadOption
+                : "{DEFAULT_DOWNLOAD_EXTENSIONS}"
+        )
+            .split(",")
+            .map((extension) => extension.trim().replace(/^\./, "").toLowerCase())
+            .filter((extension) => extension.length > 0);
+        const storageKey = `stats_session:${{siteId || ""}}`;
+
+        function log(...args) {{
+            if (options.debug) {{
+                console.log("📼", ...args);
+            }}
+        }}
+
+        // The collector of this tab, kept across page loads of the same site
+        let session = null;
+        let disabled = false;
+
+        try {{
+            session = JSON.parse(sessionStorage.getItem(storageKey));
+        }} catch (e) {{
+            session = null;
+        }}
+
+        // Page views are deduplicated, and the hash only counts in hash mode
+        let lastPage = null;
+
+        function pageKey(href) {{
+            const url = new URL(href, window.location.href);
+            if (!options.hash) {{
+                url.hash = "";
+            }}
+            return url.href;
+        }}
+
+        function pageview(url = null) {{
+            const key = pageKey(url || window.location.href);
+            if (key === lastPage) {{
+                return;
+            }}
+
+            const type = lastPage === null ? "enter" : "visit";
+            // The time spent on the previous page is reported before leaving it
+            reportEngagement();
+            lastPage = key;
+            stats_collect(type, key);
+        }}
+
+        // Active time only runs while the tab is visible and focused, and the user
+        // interacted with it within `IDLE_TIMEOUT`
+        const IDLE_TIMEOUT = 15000;
+        // Long visits report their active time periodically, not only when leaving
+        const HEARTBEAT_INTERVAL = 300000;
+        let engagedMs = 0;
+        let activeSince = null;
+        let lastInteraction = 0;
+        let idleTimer = null;
+
+        function pause() {{
+            clearTimeout(idleTimer);
+            idleTimer = null;
+            if (activeSince !== null) {{
+                const end = Math.min(Date.now(), lastInteraction + IDLE_TIMEOUT);
+                engagedMs += Math.max(0, end - activeSince);
+                activeSince = null;
+            }}
+        }}
+
+        function interact() {{
+            if (document.visibilityState !== "visible" || !document.hasFocus()) {{
+                return;
+            }}
+
+            const now = Date.now();
+            lastInteraction = now;
+            if (activeSince === null) {{
+                activeSince = now;
+            }}
+            clearTimeout(idleTimer);
+            idleTimer = setTimeout(pause, IDLE_TIMEOUT);
+        }}
+
+        function reportEngagement() {{
+            const active = activeSince !== null;
+            pause();
+            const now = Date.now();
+            if (active && now < lastInteraction + IDLE_TIMEOUT) {{
+                activeSince = now;
+                idleTimer = setTimeout(pause, lastInteraction + IDLE_TIMEOUT - now);
+            }}
+
+            const ms = Math.round(engagedMs);
+            engagedMs = 0;
+            if (ms > 0 && lastPage !== null) {{
+                send("engagement", lastPage, document.referrer, null, ms);
+            }}
+        }}
+
+        // Web Vitals of this page load, reported once when the page is first hidden
+        const vitals = {{}};
+        const vitalsUrl = pageKey(window.location.href);
+        let vitalsSent = false;
+
+        function observe(type, callback, options = {{}}) {{
+            if (
+                typeof PerformanceObserver === "undefined" ||
+                !(PerformanceObserver.supportedEntryTypes || []).includes(type)
+            ) {{
+                return;
+            }}
+
+            try {{
+                new PerformanceObserver((list) => list.getEntries().forEach(callback)).observe({{
+                    type: type,
+                    buffered: true,
+                    ...options,
+                }});
+            }} catch (e) {{}}
+        }}
+
+        function watchVitals() {{
+            const navigation =
+                performance.getEntriesByType && performance.getEntriesByType("navigation")[0];
+            // Prerendered pages count from the moment they are shown
+            const activationStart = (navigation && navigation.activationStart) || 0;
+            const sinceActivation = (time) => Math.max(time - activationStart, 0);
+
+            if (navigation && navigation.responseStart > 0) {{
+                vitals.TTFB = sinceActivation(navigation.responseStart);
+            }}
+
+            observe("paint", (entry) => {{
+                if (entry.name === "first-contentful-paint") {{
+                    vitals.FCP = sinceActivation(entry.startTime);
+                }}
+            }});
+
+            observe("largest-contentful-paint", (entry) => {{
+                vitals.LCP = sinceActivation(entry.startTime);
+            }});
+
+            // CLS is the largest burst of shifts less than 1s apart, within 5s
+            let burst = 0;
+            let burstStart = null;
+            let lastShift = 0;
+            observe("layout-shift", (entry) => {{
+                if (entry.hadRecentInput) {{
+                    return;
+                }}
+                if (
+                    burstStart === null ||
+                    entry.startTime - lastShift > 1000 ||
+                    entry.startTime - burstStart > 5000
+                ) {{
+                    burst = 0;
+                    burstStart = entry.startTime;
+                }}
+                burst += entry.value;
+                lastShift = entry.startTime;
+                vitals.CLS = Math.max(vitals.CLS || 0, burst);
+            }});
+
+            // INP is the slowest interaction, ignoring one outlier per 50 interactions
+            const interactionDurations = new Map();
+            const recordInteraction = (entry) => {{
+                if (!entry.interactionId) {{
+                    return;
+                }}
+                interactionDurations.set(
+                    entry.interactionId,
+                    Math.max(interactionDurations.get(entry.interactionId) || 0, entry.duration),
+                );
+                const durations = [...interactionDurations.values()].sort((a, b) => b - a);
+                vitals.INP = durations[Math.min(durations.length - 1, Math.floor(durations.length / 50))];
+            }};
+            observe("event", recordInteraction, {{ durationThreshold: 40 }});
+            observe("first-input", recordInteraction);
+        }}
+
+        function reportVitals() {{
+            if (vitalsSent || !session || disabled || Object.keys(vitals).length === 0) {{
+                return;
+            }}
+            vitalsSent = true;
+
+            const metrics = {{}};
+            for (const [name, value] of Object.entries(vitals)) {{
+                metrics[name] = name === "CLS" ? Math.round(value * 10000) / 10000 : Math.round(value);
+            }}
+            log("vitals", metrics);
+
+            const endpoint = `${{appUrl}}/vitals`;
+            // `collector_id` first, the rate limiter only reads the start of bodies
+            const body = JSON.stringify({{
+                collector_id: session.collector_id,
+                token: session.token,
+                site_id: siteId,
+                url: vitalsUrl,
+                metrics: metrics,
+            }});
+
+            if (navigator.sendBeacon && navigator.sendBeacon(endpoint, body)) {{
+                return;
+            }}
+
+            fetch(endpoint, {{
+                method: "POST",
+                headers: {{
+                    "Content-Type": "text/plain",
+                }},
+                body: body,
+                keepalive: true,
+            }}).catch((rejected) => {{
+                log("failed to send vitals", rejected);
+            }});
+        }}
+
+        // Uncaught errors, at most `MAX_ERRORS` distinct ones per page load
+        const MAX_ERRORS = 10;
+        const errorQueue = [];
+        const seenErrors = new Set();
+
+        function reportError(report) {{
+            const key = [report.message, report.source, report.line, report.column].join("|");
+            if (disabled || seenErrors.size >= MAX_ERRORS || seenErrors.has(key)) {{
+                return;
+            }}
+            seenErrors.add(key);
+            log("error", report);
+
+            errorQueue.push({{
+                ...report,
+                site_id: siteId,
+                url: window.location.href,
+            }});
+            flushErrors();
+        }}
+
+        function flushErrors() {{
+            // Errors thrown before the session handshake wait for it
+            if (!session || disabled) {{
+                return;
+            }}
+
+            for (const report of errorQueue.splice(0, errorQueue.length)) {{
+                fetch(`${{appUrl}}/errors`, {{
+                    method: "POST",
+                    headers: {{
+                        "Content-Type": "text/plain",
+                    }},
+                    body: JSON.stringify({{
+                        collector_id: session.collector_id,
+                        token: session.token,
+                        ...report,
+                    }}),
+                    keepalive: true,
+                }}).catch((rejected) => {{
+                    log("failed to send error", rejected);
+                }});
+            }}
+        }}
+
+        function watchErrors() {{
+            window.addEventListener("error", (event) => {{
+                // Resources failing to load fire `error` too, without a message
+                if (!event.message) {{
+                    return;
+                }}
+                reportError({{
+                    message: event.message,
+                    source: event.filename || null,
+                    line: event.lineno || null,
+                    column: event.colno || null,
+                    stack: (event.error && event.error.stack) || null,
+                }});
+            }});
+
+            window.addEventListener("unhandledrejection", (event) => {{
+                const reason = event.reason;
+                reportError({{
+                    message:
+                        reason instanceof Error
+                            ? `${{reason.name}}: ${{reason.message}}`
+                            : `Unhandled rejection: ${{String(reason)}}`,
+                    source: null,
+                    line: null,
+                    column: null,
+                    stack: (reason && reason.stack) || null,
+                }});
+            }});
+        }}
+
+        function watchEngagement() {{
+            const interactions = ["mousedown", "mousemove", "keydown", "scroll", "touchstart", "wheel"];
+            for (const type of interactions) {{
+                document.addEventListener(type, interact, {{ passive: true, capture: true }});
+            }}
+            window.addEventListener("focus", interact);
+            window.addEventListener("blur", pause);
+            document.addEventListener("visibilitychange", () => {{
+                if (document.visibilityState === "visible") {{
+                    interact();
+                }}
+            }});
+            setInterval(reportEngagement, HEARTBEAT_INTERVAL);
+            interact();
+        }}
+
+        function trackLink(event) {{
+            const link = event.target.closest && event.target.closest("a[href]");
+            if (!link) {{
+                return;
+            }}
+
+            let url;
+            try {{
+                url = new URL(link.href, window.location.href);
+            }} catch (e) {{
+                return;
+            }}
+
+            if (url.protocol === "mailto:" || url.protocol === "tel:") {{
+                if (options.contact) {{
+                    stats_collect(url.protocol.slice(0, -1));
+                }}
+                return;
+            }}
+            if (url.protocol !== "http:" && url.protocol !== "https:") {{
+                return;
+            }}
+
+            const file = url.pathname.split("/").pop();
+            const extension = file.includes(".")
+                ? file.split(".").pop().toLowerCase()
+                : "";
+            if (options.downloads && downloadExtensions.includes(extension)) {{
+                stats_collect("download", url.href, {{ extension: extension }});
+            }} else if (options.outbound && url.host !== window.location.host) {{
+                stats_collect("outbound", url.href);
+            }}
+        }}
+
+        function init() {{
+            {{
+                document.addEventListener("click", trackLink);
+                // Middle clicks open links too
+                document.addEventListener("auxclick", (event) => {{
+                    if (event.button === 1) {{
+                        trackLink(event);
+                    }}
+                }});
+
+                // Unload handlers can't wait for `fetch`, whatever is queued leaves with a beacon
+                document.addEventListener("visibilitychange", () => {{
+                    if (document.visibilityState === "hidden") {{
+                        reportEngagement();
+                        reportVitals();
+                        flush(true);
+                    }}
+                }});
+
+                window.addEventListener("pagehide", (event) => {{
+                    reportEngagement();
+                    reportVitals();
+                    stats_collect("exit");
+                    flush(true);
+                }});
+
+                if (options.manual) {{
+                    return;
+                }}
+
+                // Listen for history changes
+                function wrapHistoryMethod(method) {{
+                    const original = history[method];
+                    history[method] = function (...args) {{
+                        const [state, title, url] = args;
+                        original.apply(this, args);
+                        if (url !== undefined && url !== null) {{
+                            log("history", method, url);
+                            pageview();
+                        }}
+                    }};
+                }}
+
+                wrapHistoryMethod("pushState");
+                wrapHistoryMethod("replaceState");
+
+                // Listen for popstate event
+                window.addEventListener("popstate", (event) => {{
+                    pageview();
+                }});
+
+                if (options.hash) {{
+                    window.addEventListener("hashchange", (event) => {{
+                        pageview();
+                    }});
+                }}
+            }}
+        }}
+
+        async function start() {{
+            try {{
+                const res = await fetch(`${{appUrl}}/session`, {{
+                    method: "POST",
+                    headers: {{
+                        "Content-Type": "text/plain",
+                    }},
+                    body: JSON.stringify({{
+                        site_id: siteId,
+                        collector_id: session && session.collector_id,
+                        token: session && session.token,
+                    }}),
+                }});
+                const data = await res.json();
+
+                if (!data.success) {{
+                    throw new Error(data.error.message);
+                }}
+                if (!data.data.tracking) {{
+                    log("tracking disabled:", data.data.reason);
+                    disabled = true;
+                    queue.length = 0;
+                    errorQueue.length = 0;
+                    return;
+                }}
+
+                session = {{
+                    collector_id: data.data.collector_id,
+                    token: data.data.token,
+                }};
+                try {{
+                    sessionStorage.setItem(storageKey, JSON.stringify(session));
+                }} catch (e) {{}}
+
+                flush();
+                flushErrors();
+            }} catch (rejected) {{
+                disabled = true;
+                queue.length = 0;
+                errorQueue.length = 0;
+                log("initialization failed:", rejected);
+            }}
+        }}
+
+        const queue = [];
+        let flushTimer = null;
+
+        function flush(beacon = false) {{
+            clearTimeout(flushTimer);
+            flushTimer = null;
+
+            // Events wait for the session handshake
+            if (queue.length === 0 || !session || disabled) {{
+                return;
+            }}
+
+            const endpoint = `${{appUrl}}/event/batch`;
+            const body = JSON.stringify(
+                queue.splice(0, queue.length).map((event) => ({{
+                    collector_id: session.collector_id,
+                    token: session.token,
+                    ...event,
+                }})),
+            );
+
+            if (beacon && navigator.sendBeacon && navigator.sendBeacon(endpoint, body)) {{
+                return;
+            }}
+
+            fetch(endpoint, {{
+                method: "POST",
+                headers: {{
+                    "Content-Type": "text/plain",
+                }},
+                body: body,
+                keepalive: true,
+            }})
+                .then((res) => res.json())
+                .then((data) => {{
+                    log("sent", data);
+                }})
+                .catch((rejected) => {{
+                    log("failed to collect", rejected);
+                }});
+        }}
+
+        async function send(
+            type = "pageview",
+            url_override = null,
+            referrer = document.referrer,
+            props = null,
+            engagement_ms = null,
+        ) {{
+            if (disabled) {{
+                return;
+            }}
+
+            const event = {{
+                site_id: siteId,
+                name: type,
+                url: url_override || window.location.href,
+                referrer: referrer,
+                webdriver: navigator.webdriver === true,
+                props: props,
+            }};
+            if (engagement_ms !== null) {{
+                event.engagement_ms = engagement_ms;
+            }}
+            log(type, event);
+            queue.push(event);
+
+            // Events fired close together, like a click followed by a navigation, share a request
+            if (flushTimer === null) {{
+                flushTimer = setTimeout(flush, 1000);
+            }}
+        }}
+
+        async function stats_collect(type, url = null, props = null) {{
+            await send(type, url, document.referrer, props);
+        }}
+
+        window.stats_collect = stats_collect;
+        window.stats_pageview = pageview;
+        watchEngagement();
+        if (options.vitals) {{
+            watchVitals();
+        }}
+        if (options.errors) {{
+            watchErrors();
+        }}
+        if (!options.manual) {{
+            pageview();
+        }}
+        start();
+
+        window.addEventListener("load", () => {{
+            init();
+        }});
+    }}
+}})();
+"#
+    )
+}
@@ -0,0 +1,348 @@
+use regex::Regex;
+use serde::Deserialize;
+use serde_json::Value;
+use url::Url;
+
+use crate::services::campaign::CLICK_ID_PARAMS;
+
+/// Largest number of path templates of a site
+const MAX_PATH_TEMPLATES: usize = 50;
+/// Longest raw URL stored
+const MAX_RAW_URL_LENGTH: usize = 2048;
+
+/// # `NormalizedUrl`
+/// A URL once the rules of its site were applied.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NormalizedUrl {
+    /// URL used to count page views, e.g. `https://example.com/blog/post-1?page=2`
+    pub url: String,
+    /// URL as reported by the browser, without click IDs
+    pub raw_url: String,
+    /// Path template the URL matched (`/blog/:slug`), else its normalized path.
+    /// `None` for URLs that cannot be parsed
+    pub path_template: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct PathTemplateSettings {
+    pattern: String,
+    template: String,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(deny_unknown_fields)]
+struct UrlRulesSettings {
+    #[serde(default)]
+    query_params: Vec<String>,
+    #[serde(default)]
+    lowercase: bool,
+    #[serde(default)]
+    strip_index: bool,
+    #[serde(default)]
+    templates: Vec<PathTemplateSettings>,
+}
+
+/// # `UrlRules`
+/// How the URLs of a site are cleaned before being stored, read from the `url_rules`
+/// object of its settings:
+///
+/// ```json
+/// {"url_rules": {
+///     "query_params": ["page"],
+///     "lowercase": true,
+///     "strip_index": true,
+///     "templates": [{"pattern": "^/blog/[^/]+$", "template": "/blog/:slug"}]
+/// }}
+/// ```
+///
+/// Without rules, the query string is dropped and trailing slashes trimmed.
+#[derive(Debug, Clone, Default)]
+pub struct UrlRules {
+    /// Query parameters kept in URLs, every other one is dropped
+    query_params: Vec<String>,
+    /// Lowercase paths, for servers that ignore their case
+    lowercase: bool,
+    /// Drop a final `index.html` or `index.htm`
+    strip_index: bool,
+    /// Patterns matched against paths, with the template replacing the match
+    templates: Vec<(Regex, String)>,
+}
+
+impl UrlRules {
+    /// # `from_settings`
+    /// Reads the URL rules of a site from its settings.
+    ///
+    /// ## Arguments
+    /// * `settings` - The settings of the site, as a JSON object
+    ///
+    /// ## Errors
+    /// * `String` - Why the rules are invalid, e.g. an unknown option or a bad pattern
+    ///
+    /// ## Returns
+    /// * `UrlRules` - The rules, the defaults when the settings have none
+    pub fn from_settings(settings: &Value) -> Result<Self, String> {
+        let Some(rules) = settings.get("url_rules") else {
+            return Ok(Self::default());
+        };
+        let rules: UrlRulesSettings =
+            serde_json::from_value(rules.clone()).map_err(|e| format!("Invalid URL rules: {e}"))?;
+
+        if rules.templates.len() > MAX_PATH_TEMPLATES {
+            return Err(format!(
+                "Sites are limited to {MAX_PATH_TEMPLATES} path templates"
+            ));
+        }
+
+        let templates = rules
+            .templates
+            .into_iter()
+            .map(|template| {
+                Regex::new(&template.pattern)
+                    .map(|pattern| (pattern, template.template))
+                    .map_err(|e| format!("Invalid path pattern {}: {e}", template.pattern))
+            })
+            .collect::<Result<_, _>>()?;
+
+        Ok(Self {
+            query_params: rules.query_params,
+            lowercase: rules.lowercase,
+            strip_index: rules.strip_index,
+            templates,
+        })
+    }
+
+    /// # `from_site_settings`
+    /// Reads the URL rules of a site from its stored settings.
+    ///
+    /// ## Arguments
+    /// * `settings` - The `settings` column of the site
+    ///
+    /// ## Errors
+    /// * `String` - Why the settings or the rules are invalid
+    pub fn from_site_settings(settings: &str) -> Result<Self, String> {
+        let settings: Value =
+            serde_json::from_str(settings).map_err(|e| format!("Invalid site settings: {e}"))?;
+
+        Self::from_settings(&settings)
+    }
+
+    /// # `normalize`
+    /// Applies the rules to a URL: the query string only keeps the allowed parameters,
+    /// sorted by name, then the path is lowercased, loses its `index.html` and trailing
+    /// slash, and is matched against the path templates in order.
+    ///
+    /// ## Arguments
+    /// * `url` - The URL of the page, as reported by the tracking script
+    ///
+    /// ## Returns
+    /// * `NormalizedUrl` - The normalized URL, with the raw URL and path template
+    #[must_use]
+    pub fn normalize(&self, url: &str) -> NormalizedUrl {
+        let raw_url: String = strip_click_ids(url)
+            .chars()
+            .take(MAX_RAW_URL_LENGTH)
+            .collect();
+
+        let Ok(mut parsed) = Url::parse(url) else {
+            return NormalizedUrl {
+                url: url.trim_end_matches('/').to_string(),
+                raw_url,
+                path_template: None,
+            };
+        };
+
+        let mut params: Vec<(String, String)> = parsed
+            .query_pairs()
+            .filter(|(key, _)| self.query_params.iter().any(|param| param == key))
+            .map(|(key, value)| (key.into_owned(), value.into_owned()))
+            .collect();
+        params.sort_by(|a, b| a.0.cmp(&b.0));
+        parsed.set_query(None);
+        if !params.is_empty() {
+            parsed.query_pairs_mut().extend_pairs(params);
+        }
+
+        let mut path = parsed.path().to_string();
+        if self.lowercase {
+            path = path.to_lowercase();
+        }
+        if self.strip_index {
+            for index in ["index.html", "index.htm"] {
+                if let Some(directory) = path.strip_suffix(index)
+                    && directory.ends_with('/')
+                {
+                    path = directory.to_string();
+                    break;
+                }
+            }
+        }
+        let path = match path.trim_end_matches('/') {
+            "" => "/".to_string(),
+            trimmed => trimmed.to_string(),
+        };
+        parsed.set_path(&path);
+
+        let path_template = self
+            .templates
+            .iter()
+            .find(|(pattern, _)| pattern.is_match(&path))
+            .map_or_else(
+                || path.clone(),
+                |(pattern, template)| pattern.replace(&path, template.as_str()).to_string(),
+            );
+
+        NormalizedUrl {
+            url: parsed.to_string().trim_end_matches('/').to_string(),
+            raw_url,
+            path_template: Some(path_template),
+        }
+    }
+}
+
+/// Removes ad click IDs from a URL, as they identify a single visitor.
+fn strip_click_ids(url: &str) -> String {
+    let Ok(mut parsed) = Url::parse(url) else {
+        return url.to_string();
+    };
+    if !parsed
+        .query_pairs()
+        .any(|(key, _)| CLICK_ID_PARAMS.contains(&key.as_ref()))
+    {
+        return url.to_string();
+    }
+
+    let params: Vec<(String, String)> = parsed
+        .query_pairs()
+        .filter(|(key, _)| !CLICK_ID_PARAMS.contains(&key.as_ref()))
+        .map(|(key, value)| (key.into_owned(), value.into_owned()))
+        .collect();
+    parsed.set_query(None);
+    if !params.is_empty() {
+        parsed.query_pairs_mut().extend_pairs(params);
+    }
+
+    parsed.to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn rules() -> UrlRules {
+        UrlRules::from_settings(&json!({"url_rules": {
+            "query_params": ["page", "lang"],
+            "lowercase": true,
+            "strip_index": true,
+            "templates": [
+                {"pattern": "^/blog/[^/]+$", "template": "/blog/:slug"},
+                {"pattern": "^/users/\\d+", "template": "/users/:id"}
+            ]
+        }}))
+        .unwrap()
+    }
+
+    #[test]
+    fn applies_the_default_rules() {
+        let rules = UrlRules::default();
+        let cases = [
+            ("https://example.com/", "https://example.com", "/"),
+            (
+                "https://example.com/blog/",
+                "https://example.com/blog",
+                "/blog",
+            ),
+            (
+                "https://example.com/Blog/index.html?page=2&utm_source=x",
+                "https://example.com/Blog/index.html",
+                "/Blog/index.html",
+            ),
+        ];
+
+        for (url, expected, template) in cases {
+            let normalized = rules.normalize(url);
+            assert_eq!(normalized.url, expected, "{url}");
+            assert_eq!(normalized.path_template.as_deref(), Some(template), "{url}");
+        }
+    }
+
+    #[test]
+    fn applies_the_site_rules() {
+        let rules = rules();
+        let cases = [
+            (
+                "https://example.com/Docs/index.html",
+                "https://example.com/docs",
+                "/docs",
+            ),
+            ("https://example.com/index.htm", "https://example.com", "/"),
+            (
+                "https://example.com/docs/myindex.html",
+                "https://example.com/docs/myindex.html",
+                "/docs/myindex.html",
+            ),
+            (
+                "https://example.com/blog/Hello-World/?utm_source=x&page=2&lang=fr",
+                "https://example.com/blog/hello-world?lang=fr&page=2",
+                "/blog/:slug",
+            ),
+            (
+                "https://example.com/users/42/settings",
+                "https://example.com/users/42/settings",
+                "/users/:id/settings",
+            ),
+            (
+                "https://example.com/blog/2024/recap",
+                "https://example.com/blog/2024/recap",
+                "/blog/2024/recap",
+            ),
+        ];
+
+        for (url, expected, template) in cases {
+            let normalized = rules.normalize(url);
+            assert_eq!(normalized.url, expected, "{url}");
+            assert_eq!(normalized.path_template.as_deref(), Some(template), "{url}");
+        }
+    }
+
+    #[test]
+    fn keeps_raw_urls_without_click_ids() {
+        let rules = rules();
+
+        let normalized =
+            rules.normalize("https://example.com/Pricing?gclid=abc&plan=pro&fbclid=def");
+        assert_eq!(normalized.raw_url, "https://example.com/Pricing?plan=pro");
+        let normalized = rules.normalize("https://example.com/?gclid=abc");
+        assert_eq!(normalized.raw_url, "https://example.com/");
+        let normalized = rules.normalize("https://example.com/Pricing?plan=pro");
+        assert_eq!(normalized.raw_url, "https://example.com/Pricing?plan=pro");
+
+        let long = format!("https://example.com/?q={}", "a".repeat(3000));
+        assert_eq!(rules.normalize(&long).raw_url.len(), MAX_RAW_URL_LENGTH);
+    }
+
+    #[test]
+    fn keeps_unparsable_urls() {
+        let normalized = rules().normalize("not a url/");
+
+        assert_eq!(normalized.url, "not a url");
+        assert_eq!(normalized.raw_url, "not a url/");
+        assert_eq!(normalized.path_template, None);
+    }
+
+    #[test]
+    fn rejects_invalid_rules() {
+        assert!(UrlRules::from_settings(&json!({})).is_ok());
+        for rules in [
+            json!({"url_rules": {"lowercase": "yes"}}),
+            json!({"url_rules": {"strip_query": true}}),
+            json!({"url_rules": {"templates": [{"pattern": "(", "template": "/x"}]}}),
+            json!({"url_rules": {"templates": vec![json!({"pattern": "^/a$", "template": "/a"}); 51]}}),
+        ] {
+            assert!(UrlRules::from_settings(&rules).is_err(), "{rules}");
+        }
+        assert!(UrlRules::from_site_settings("{").is_err());
+    }
+}
@@ -0,0 +1,438 @@
+use std::{fmt::Display, fs, path::Path, str::FromStr};
+
+use regex::{Captures, Regex};
+use serde::{Deserialize, Serialize};
+
+/// Rules shipped with the application, used unless `USER_AGENT_RULES` points to another file
+const BUNDLED_RULES: &str = include_str!("../../resources/user_agent_rules.json");
+
+/// # `DeviceType`
+/// Broad class of device a user agent runs on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum DeviceType {
+    #[default]
+    Desktop,
+    Mobile,
+    Tablet,
+    Tv,
+    Bot,
+}
+
+impl DeviceType {
+    #[must_use]
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            DeviceType::Desktop => "desktop",
+            DeviceType::Mobile => "mobile",
+            DeviceType::Tablet => "tablet",
+            DeviceType::Tv => "tv",
+            DeviceType::Bot => "bot",
+        }
+    }
+}
+
+impl Display for DeviceType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+impl FromStr for DeviceType {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "desktop" => Ok(DeviceType::Desktop),
+            "mobile" => Ok(DeviceType::Mobile),
+            "tablet" => Ok(DeviceType::Tablet),
+            "tv" => Ok(DeviceType::Tv),
+            "bot" => Ok(DeviceType::Bot),
+            other => Err(format!("Unknown device type: {other}")),
+        }
+    }
+}
+
+/// # `ParsedUserAgent`
+/// Everything the parser could extract from a `User-Agent` header.
+#[derive(Debug, Clone, Serialize)]
+pub struct ParsedUserAgent {
+    pub os: String,
+    pub os_version: Option<String>,
+    pub browser: String,
+    pub browser_version: Option<String>,
+    pub device_type: DeviceType,
+    pub engine: String,
+}
+
+/// One rule as written in the rules file.
+///
+/// `name` and `version` are templates where `$1`..`$9` are replaced by the
+/// matching capture groups of `regex`. A rule is skipped when `exclude` matches.
+#[derive(Deserialize)]
+struct RawRule {
+    regex: String,
+    exclude: Option<String>,
+    name: String,
+    version: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct RawRules {
+    browsers: Vec<RawRule>,
+    operating_systems: Vec<RawRule>,
+    devices: Vec<RawRule>,
+    engines: Vec<RawRule>,
+}
+
+struct Rule {
+    regex: Regex,
+    exclude: Option<Regex>,
+    name: String,
+    version: Option<String>,
+}
+
+impl Rule {
+    fn compile(raw: RawRule) -> Result<Self, String> {
+        let regex =
+            Regex::new(&raw.regex).map_err(|e| format!("Invalid rule {}: {e}", raw.regex))?;
+        let exclude = raw
+            .exclude
+            .map(|exclude| Regex::new(&exclude).map_err(|e| format!("Invalid rule {exclude}: {e}")))
+            .transpose()?;
+
+        Ok(Self {
+            regex,
+            exclude,
+            name: raw.name,
+            version: raw.version,
+        })
+    }
+
+    /// Returns the name and version extracted by this rule, if it applies.
+    fn apply(&self, user_agent: &str) -> Option<(String, Option<String>)> {
+        if self
+            .exclude
+            .as_ref()
+            .is_some_and(|exclude| exclude.is_match(user_agent))
+        {
+            return None;
+        }
+
+        let captures = self.regex.captures(user_agent)?;
+        let name = expand(&self.name, &captures);
+        let version = self
+            .version
+            .as_ref()
+            .map(|template| {
+                expand(template, &captures)
+                    .split('.')
+                    .filter(|part| !part.is_empty())
+                    .collect::<Vec<_>>()
+                    .join(".")
+            })
+            .filter(|version| !version.is_empty());
+
+        Some((name, version))
+    }
+}
+
+/// Replaces `$1`..`$9` in a template by the matching capture groups.
+fn expand(template: &str, captures: &Captures) -> String {
+    (1..=9).fold(template.to_string(), |acc, index| {
+        let group = captures.get(index).map_or("", |m| m.as_str());
+        acc.replace(&format!("${index}"), group)
+    })
+}
+
+fn first_match(rules: &[Rule], user_agent: &str) -> Option<(String, Option<String>)> {
+    rules.iter().find_map(|rule| rule.apply(user_agent))
+}
+
+/// # `UserAgentParser`
+/// User agent parser driven by a JSON rules file, in the spirit of uap-core:
+/// for each of browsers, operating systems, devices and engines, the first
+/// matching regex wins. Order in the file therefore matters (Edge before Chrome,
+/// Android before Linux...).
+pub struct UserAgentParser {
+    browsers: Vec<Rule>,
+    operating_systems: Vec<Rule>,
+    devices: Vec<(Rule, DeviceType)>,
+    engines: Vec<Rule>,
+}
+
+impl UserAgentParser {
+    /// # `from_json`
+    /// Builds a parser from the content of a rules file.
+    ///
+    /// ## Arguments
+    /// * `json` - The rules, see `resources/user_agent_rules.json`
+    ///
+    /// ## Errors
+    /// If the JSON is malformed, a regex is invalid or a device type is unknown.
+    ///
+    /// ## Returns
+    /// * `Result<UserAgentParser, String>` - The parser
+    pub fn from_json(json: &str) -> Result<Self, String> {
+        let raw: RawRules =
+            serde_json::from_str(json).map_err(|e| format!("Invalid rules file: {e}"))?;
+        let compile = |rules: Vec<RawRule>| {
+            rules
+                .into_iter()
+                .map(Rule::compile)
+                .collect::<Result<Vec<_>, String>>()
+        };
+
+        let devices = compile(raw.devices)?
+            .into_iter()
+            .map(|rule| {
+                let device = rule.name.parse::<DeviceType>()?;
+                Ok((rule, device))
+            })
+            .collect::<Result<Vec<_>, String>>()?;
+
+        Ok(Self {
+            browsers: compile(raw.browsers)?,
+            operating_systems: compile(raw.operating_systems)?,
+            devices,
+            engines: compile(raw.engines)?,
+        })
+    }
+
+    /// # `from_file`
+    /// Builds a parser from a rules file on disk.
+    ///
+    /// ## Arguments
+    /// * `path` - Path to the rules file
+    ///
+    /// ## Errors
+    /// If the file cannot be read or is not a valid rules file.
+    ///
+    /// ## Returns
+    /// * `Result<UserAgentParser, String>` - The parser
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self, String> {
+        let content = fs::read_to_string(path.as_ref())
+            .map_err(|e| format!("Failed to read {}: {e}", path.as_ref().display()))?;
+
+        Self::from_json(&content)
+    }
+
+    /// # `bundled`
+    /// Builds a parser from the rules shipped with the application.
+    ///
+    /// ## Panics
+    /// If the bundled rules are invalid, which is a bug.
+    ///
+    /// ## Returns
+    /// * `UserAgentParser` - The parser
+    #[must_use]
+    pub fn bundled() -> Self {
+        Self::from_json(BUNDLED_RULES).expect("Bundled user agent rules are invalid")
+    }
+
+    /// # `parse`
+    /// Extracts the OS, browser, device type and engine of a user agent.
+    ///
+    /// ## Arguments
+    /// * `user_agent` - The raw `User-Agent` header
+    ///
+    /// ## Returns
+    /// * `ParsedUserAgent` - The parsed user agent, with `Other` for unknown parts
+    #[must_use]
+    pub fn parse(&self, user_agent: &str) -> ParsedUserAgent {
+        let (browser, browser_version) =
+            first_match(&self.browsers, user_agent).unwrap_or(("Other".to_string(), None));
+        let (os, os_version) =
+            first_match(&self.operating_systems, user_agent).unwrap_or(("Other".to_string(), None));
+        let device_type = self
+            .devices
+            .iter()
+            .find(|(rule, _)| rule.apply(user_agent).is_some())
+            .map_or(DeviceType::Desktop, |(_, device)| *device);
+        let engine = first_match(&self.engines, user_agent)
+            .map_or("Other".to_string(), |(engine, _)| engine);
+
+        ParsedUserAgent {
+            os,
+            os_version,
+            browser,
+            browser_version,
+            device_type,
+            engine,
+        }
+    }
+}
+
+impl Default for UserAgentParser {
+    fn default() -> Self {
+        Self::bundled()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// User agent, browser, browser version, OS, OS version, engine, device type
+    type Case = (
+        &'static str,
+        &'static str,
+        Option<&'static str>,
+        &'static str,
+        Option<&'static str>,
+        &'static str,
+        DeviceType,
+    );
+
+    const CORPUS: [Case; 12] = [
+        (
+            "Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/124.0.6367.91 Safari/537.36",
+            "Chrome",
+            Some("124.0.6367"),
+            "Windows",
+            Some("10"),
+            "Blink",
+            DeviceType::Desktop,
+        ),
+        (
+            "Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/124.0.0.0 Safari/537.36 Edg/124.0.2478.67",
+            "Edge",
+            Some("124.0.2478"),
+            "Windows",
+            Some("10"),
+            "Blink",
+            DeviceType::Desktop,
+        ),
+        (
+            "Mozilla/5.0 (Macintosh; Intel Mac OS X 10_15_7) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/123.0.0.0 Safari/537.36 OPR/109.0.0.0",
+            "Opera",
+            Some("109.0.0"),
+            "macOS",
+            Some("10.15.7"),
+            "Blink",
+            DeviceType::Desktop,
+        ),
+        (
+            "Mozilla/5.0 (Linux; Android 13; SAMSUNG SM-S911B) AppleWebKit/537.36 (KHTML, like Gecko) SamsungBrowser/24.0 Chrome/117.0.0.0 Mobile Safari/537.36",
+            "Samsung Internet",
+            Some("24.0"),
+            "Android",
+            Some("13"),
+            "Blink",
+            DeviceType::Mobile,
+        ),
+        (
+            "Mozilla/5.0 (Linux; Android 14; Pixel 8) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/124.0.6367.82 Mobile Safari/537.36",
+            "Chrome",
+            Some("124.0.6367"),
+            "Android",
+            Some("14"),
+            "Blink",
+            DeviceType::Mobile,
+        ),
+        (
+            "Mozilla/5.0 (Linux; Android 13; SM-X710) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/124.0.6367.82 Safari/537.36",
+            "Chrome",
+            Some("124.0.6367"),
+            "Android",
+            Some("13"),
+            "Blink",
+            DeviceType::Tablet,
+        ),
+        (
+            "Mozilla/5.0 (X11; Ubuntu; Linux x86_64; rv:125.0) Gecko/20100101 Firefox/125.0",
+            "Firefox",
+            Some("125.0"),
+            "Ubuntu",
+            None,
+            "Gecko",
+            DeviceType::Desktop,
+        ),
+        (
+            "Mozilla/5.0 (X11; Linux x86_64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/124.0.0.0 Safari/537.36",
+            "Chrome",
+            Some("124.0.0"),
+            "Linux",
+            None,
+            "Blink",
+            DeviceType::Desktop,
+        ),
+        (
+            "Mozilla/5.0 (iPhone; CPU iPhone OS 17_4_1 like Mac OS X) AppleWebKit/605.1.15 (KHTML, like Gecko) Version/17.4.1 Mobile/15E148 Safari/604.1",
+            "Safari",
+            Some("17.4.1"),
+            "iOS",
+            Some("17.4.1"),
+            "WebKit",
+            DeviceType::Mobile,
+        ),
+        (
+            "Mozilla/5.0 (iPad; CPU OS 16_6 like Mac OS X) AppleWebKit/605.1.15 (KHTML, like Gecko) Version/16.6 Mobile/15E148 Safari/604.1",
+            "Safari",
+            Some("16.6"),
+            "iOS",
+            Some("16.6"),
+            "WebKit",
+            DeviceType::Tablet,
+        ),
+        (
+            "Mozilla/5.0 (SMART-TV; LINUX; Tizen 6.5) AppleWebKit/537.36 (KHTML, like Gecko) 85.0.4183.93/6.5 TV Safari/537.36",
+            "Other",
+            None,
+            "Tizen",
+            Some("6.5"),
+            "WebKit",
+            DeviceType::Tv,
+        ),
+        (
+            "Mozilla/5.0 (compatible; Googlebot/2.1; +http://www.google.com/bot.html)",
+            "Googlebot",
+            Some("2.1"),
+            "Other",
+            None,
+            "Other",
+            DeviceType::Bot,
+        ),
+    ];
+
+    #[test]
+    fn parses_real_user_agents() {
+        let parser = UserAgentParser::bundled();
+
+        for (user_agent, browser, browser_version, os, os_version, engine, device_type) in CORPUS {
+            let parsed = parser.parse(user_agent);
+            assert_eq!(parsed.browser, browser, "{user_agent}");
+            assert_eq!(
+                parsed.browser_version.as_deref(),
+                browser_version,
+                "{user_agent}"
+            );
+            assert_eq!(parsed.os, os, "{user_agent}");
+            assert_eq!(parsed.os_version.as_deref(), os_version, "{user_agent}");
+            assert_eq!(parsed.engine, engine, "{user_agent}");
+            assert_eq!(parsed.device_type, device_type, "{user_agent}");
+        }
+    }
+
+    #[test]
+    fn classifies_scripts_as_bots() {
+        let parser = UserAgentParser::bundled();
+
+        let parsed = parser.parse("curl/8.5.0");
+        assert_eq!(parsed.browser, "curl");
+        assert_eq!(parsed.browser_version.as_deref(), Some("8.5.0"));
+        assert_eq!(parsed.device_type, DeviceType::Bot);
+
+        let parsed = parser.parse("Wget/1.21.4");
+        assert_eq!(parsed.browser, "Wget");
+        assert_eq!(parsed.device_type, DeviceType::Bot);
+    }
+
+    #[test]
+    fn rejects_unknown_device_types() {
+        let json = r#"{"browsers": [], "operating_systems": [], "engines": [],
+            "devices": [{"regex": "Watch", "name": "watch"}]}"#;
+
+        assert!(UserAgentParser::from_json(json).is_err());
+    }
+}
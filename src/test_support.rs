@@ -0,0 +1,77 @@
+//! Shared fixtures for tests that need a database or a running application.
+
+use std::{env, sync::Arc};
+
+use diesel::{Connection, SqliteConnection};
+use diesel_migrations::{EmbeddedMigrations, MigrationHarness, embed_migrations};
+use rocket::{Build, Rocket, figment::Figment};
+
+use crate::{
+    AppState, DbConn,
+    metrics::Metrics,
+    services::{
+        anonymization::{IpAnonymization, IpAnonymizer},
+        bot_detection::BotDetector,
+        collector_token::CollectorTokenSigner,
+        error_fingerprint::ErrorFingerprinter,
+        privacy::PrivacyPolicy,
+        referrer::ReferrerClassifier,
+        static_locator::StaticLocator,
+        tracking_script::TrackingScript,
+        user_agent::UserAgentParser,
+    },
+};
+
+const MIGRATIONS: EmbeddedMigrations = embed_migrations!("migrations");
+
+/// # `database`
+/// Creates a fresh SQLite database in the temporary directory with every migration applied.
+///
+/// ## Returns
+/// * `String` - Path to the database file
+pub fn database() -> String {
+    let path = env::temp_dir()
+        .join(format!("website_stats_{}.sqlite", ulid::Ulid::new()))
+        .to_string_lossy()
+        .into_owned();
+    let mut conn = SqliteConnection::establish(&path).expect("Failed to create test database");
+    conn.run_pending_migrations(MIGRATIONS)
+        .expect("Failed to run migrations");
+
+    path
+}
+
+/// # `app_state`
+/// Builds an application state that never reaches the network.
+pub fn app_state() -> AppState {
+    let address = "http://localhost:8000".to_string();
+
+    AppState {
+        tracking_script: TrackingScript::new(&address),
+        address,
+        dev_mode: true,
+        geo_locator: Box::new(StaticLocator::development()),
+        geo_cache_ttl: 3600,
+        metrics: Metrics::default(),
+        user_agent_parser: UserAgentParser::bundled(),
+        bot_detector: BotDetector::default(),
+        ip_anonymizer: IpAnonymizer::new(IpAnonymization::Truncate, None)
+            .expect("Truncating needs no key"),
+        privacy_policy: PrivacyPolicy::Ignore,
+        token_signer: CollectorTokenSigner::new(Some("test_secret"), 3600),
+        error_fingerprinter: ErrorFingerprinter::new(),
+        referrer_classifier: ReferrerClassifier::bundled(),
+    }
+}
+
+/// # `rocket`
+/// Builds an application backed by a fresh database, without any routes mounted.
+pub fn rocket() -> Rocket<Build> {
+    let figment = Figment::from(rocket::Config::debug_default())
+        .merge(("databases.sqlite_database.url", database()))
+        .merge(("log_level", "off"));
+
+    rocket::custom(figment)
+        .attach(DbConn::fairing())
+        .manage(Arc::new(app_state()))
+}
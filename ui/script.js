@@ -1,3 +1,10 @@
+// Scope every request to the site given in the dashboard URL (`?site=<id>`)
+const siteId = new URLSearchParams(window.location.search).get("site");
+
+function withSite(path) {
+    return siteId ? `${path}?site=${encodeURIComponent(siteId)}` : path;
+}
+
 function formatFromNow(timestamp) {
     const now = new Date();
     // Stats timestamps always in UTC, so we need to append a "Z" to indicate
@@ -74,7 +81,7 @@ function mapHourlyEventsToLocalTime(events) {
 }
 
 async function renderHourlySummary() {
-    const response = await fetch("/summary/hourly");
+    const response = await fetch(withSite("/summary/hourly"));
     const hourlyEvents = (await response.json()).data.summary;
     const hourlyDiv = document.getElementById("hourly");
     const localEvents = mapHourlyEventsToLocalTime(hourlyEvents);
@@ -109,7 +116,7 @@ async function renderHourlySummary() {
 }
 
 async function renderUrls() {
-    const response = await fetch("/summary/urls");
+    const response = await fetch(withSite("/summary/urls"));
     const urls = (await response.json()).data.summary;
     const urlsDiv = document.getElementById("urls");
 
@@ -153,7 +160,7 @@ async function renderUrls() {
 }
 
 async function renderBrowsers() {
-    const response = await fetch("/summary/osbrowsers");
+    const response = await fetch(withSite("/summary/osbrowsers"));
     const urls = (await response.json()).data.summary;
     const urlsDiv = document.getElementById("browsers");
 
@@ -184,7 +191,7 @@ async function renderBrowsers() {
 }
 
 async function renderReferrers() {
-    const response = await fetch("/summary/referrers");
+    const response = await fetch(withSite("/summary/referrers"));
     const urls = (await response.json()).data.summary;
     const urlsDiv = document.getElementById("referrers");
 
@@ -249,7 +256,7 @@ function prettyPrintTimeDifference(utcTimestamp1, utcTimestamp2) {
 }
 
 async function renderSessions() {
-    const response = await fetch("/session/");
+    const response = await fetch(withSite("/session/"));
     const sessions = (await response.json()).data.sessions;
     const sessionsDiv = document.getElementById("sessions");
 
@@ -319,7 +326,7 @@ async function renderSessions() {
 }
 
 async function renderSummary() {
-    const summaryResponse = await fetch("/summary/events");
+    const summaryResponse = await fetch(withSite("/summary/events"));
     const summary = (await summaryResponse.json()).data.summary;
     Object.keys(summary).forEach((key) => {
         const element = document.getElementById(key);
@@ -349,7 +356,7 @@ const renderSinglePercentageChange = (element, percentage) => {
 };
 
 async function renderPercentageChanges() {
-    const percentagesResponse = await fetch("/summary/percentages");
+    const percentagesResponse = await fetch(withSite("/summary/percentages"));
     const percentages = (await percentagesResponse.json()).data.summary;
 
 
@@ -393,7 +400,7 @@ function convertUtcToLocal(utcDay, utcHour, offset) {
 }
 
 async function renderWeeklyHeatmap() {
-    const response = await fetch("/summary/weekly");
+    const response = await fetch(withSite("/summary/weekly"));
     const utcEventCounts = (await response.json()).data.summary;
     const heatmapDiv = document.getElementById("weekly");
 
@@ -449,7 +456,7 @@ async function renderWeeklyHeatmap() {
 let world;
 
 async function renderGlobe() {
-    const response = await fetch("/session/map");
+    const response = await fetch(withSite("/session/map"));
     const coordinates = (await response.json()).data.cities;
     const globeDiv = document.getElementById("globe");
     const globeLeaderboardDiv = document.getElementById("globeleaderboard");